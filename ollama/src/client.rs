@@ -1,6 +1,7 @@
 use reqwest::Client as HttpClient;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
+use tokio_util::sync::CancellationToken;
 use url::Url;
 
 use crate::error::OllamaError;
@@ -51,4 +52,16 @@ impl OllamaClient {
     pub async fn chat(&self, req: &ChatRequest) -> Result<ChatResponse, OllamaError> {
         self.post_json("chat", req).await
     }
+
+    /// POST /api/chat, aborted with `OllamaError::Cancelled` once `cancel` fires.
+    pub async fn chat_cancellable(
+        &self,
+        req: &ChatRequest,
+        cancel: &CancellationToken,
+    ) -> Result<ChatResponse, OllamaError> {
+        tokio::select! {
+            res = self.chat(req) => res,
+            _ = cancel.cancelled() => Err(OllamaError::Cancelled),
+        }
+    }
 }
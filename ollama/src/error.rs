@@ -19,4 +19,7 @@ pub enum OllamaError {
 
     #[error("unexpected server response [{status}]: {body}")]
     ServerError { status: StatusCode, body: String },
+
+    #[error("request cancelled")]
+    Cancelled,
 }
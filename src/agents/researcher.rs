@@ -1,9 +1,29 @@
-use ollama::types::{ChatMessage, MessageRole};
+use ollama::{
+    types::{ChatMessage, MessageRole},
+    OllamaClient,
+};
+use std::{error::Error, path::Path};
 
-use crate::config::guidelines;
+use crate::{
+    config::guidelines,
+    interrupt::Interrupt,
+    session::Session,
+    tool_handling::{run_tool_calls, tool_defs},
+};
 
-pub fn researcher_prompt() -> String {
-    let guidelines = guidelines::load_guidelines()
+/// Research steps before the final output is requested regardless.
+pub const MAX_TOOL_CALL_LOOPS: usize = 10;
+
+/// How a research run ended.
+pub enum Research {
+    /// The model is ready for the final output, or the step limit was reached.
+    Done,
+    /// A step was cancelled and rewound.
+    Cancelled,
+}
+
+pub fn researcher_prompt(root: &Path) -> String {
+    let guidelines = guidelines::load_guidelines_from(root)
         .unwrap_or_default()
         .unwrap_or_default();
 
@@ -22,6 +42,7 @@ Note: You must thoroughly gather information about codebase before proceeding wi
 1. Understand the objective and constraints
 2. Locate relevant files using crawler tool
 3. Verify file contents match requirements
+   - Use `shell.run` (e.g. `cargo check`) to confirm compile errors and test names instead of guessing
 4. Ensure tasks are simple, specific, and sequential
 
 **Guidelines:**
@@ -30,19 +51,67 @@ Note: You must thoroughly gather information about codebase before proceeding wi
     )
 }
 
-pub fn get_initial_messages(user_prompt: String) -> Vec<ChatMessage> {
+pub fn get_initial_messages(root: &Path, user_prompt: String) -> Vec<ChatMessage> {
     vec![
         ChatMessage {
             role: MessageRole::System,
-            content: researcher_prompt(),
+            content: researcher_prompt(root),
+            thinking: None,
             images: None,
             tool_calls: None,
         },
         ChatMessage {
             role: MessageRole::User,
             content: user_prompt,
+            thinking: None,
             images: None,
             tool_calls: None,
         },
     ]
 }
+
+/// Lets the model call tools until it marks its reply `<FINAL>` or the step
+/// limit is reached. Each step can be cancelled through `interrupt`.
+pub async fn research(
+    client: &OllamaClient,
+    session: &mut Session,
+    interrupt: &Interrupt,
+) -> Result<Research, Box<dyn Error>> {
+    for current_tool_loop in 1..=MAX_TOOL_CALL_LOOPS {
+        println!(
+            "\n=== Reasoning Step {}/{} ===",
+            current_tool_loop, MAX_TOOL_CALL_LOOPS
+        );
+        session.record_step(current_tool_loop, MAX_TOOL_CALL_LOOPS);
+
+        let before = session.messages.len();
+        if interrupt.is_cancelled() {
+            return Ok(Research::Cancelled);
+        }
+        let step = interrupt.step();
+        let assistant_msg = match session
+            .chat(client, Some(tool_defs(session)), None, false, step.token())
+            .await
+        {
+            Ok(msg) => msg,
+            Err(_) if step.is_cancelled() => return Ok(Research::Cancelled),
+            Err(e) => return Err(e),
+        };
+
+        if let Some(tool_calls) = assistant_msg.tool_calls {
+            if assistant_msg.content.contains("<FINAL>") {
+                println!(
+                    "\n🧠 Assistant (reasoning complete, preparing final output): {}",
+                    assistant_msg.content
+                );
+                return Ok(Research::Done);
+            }
+            run_tool_calls(session, tool_calls, step.token()).await;
+            if step.is_cancelled() {
+                session.rewind(before);
+                return Ok(Research::Cancelled);
+            }
+        }
+    }
+    Ok(Research::Done)
+}
@@ -0,0 +1,156 @@
+use std::{env, path::PathBuf, process};
+
+use crate::{response::GraphFormat, session::ExportFormat};
+
+/// What viktor was asked to do on the command line.
+pub enum Command {
+    Init,
+    Plan(PlanArgs),
+    /// `viktor sessions list`
+    SessionsList,
+    /// `viktor sessions show <id>`
+    SessionsShow(String),
+    /// `viktor resume <id>`
+    Resume(String),
+    Export(ExportArgs),
+    /// `viktor mcp-serve`
+    McpServe,
+    Serve(ServeArgs),
+}
+
+/// `viktor export <id> [--format md|html|json] [--output <path>]`
+pub struct ExportArgs {
+    pub id: String,
+    pub format: ExportFormat,
+    /// Write to this file instead of stdout.
+    pub output: Option<PathBuf>,
+}
+
+/// `viktor serve [--port N] [--root <path>]...`
+pub struct ServeArgs {
+    pub port: u16,
+    /// Repositories jobs may run on; the current directory when empty.
+    pub roots: Vec<PathBuf>,
+}
+
+pub struct PlanArgs {
+    pub prompt: String,
+    /// Also print the task dependency graph in this format.
+    pub graph: Option<GraphFormat>,
+    /// Print the final plan as JSON instead of the human-readable layout.
+    pub json: bool,
+}
+
+/// Port `viktor serve` listens on unless `--port` says otherwise.
+const DEFAULT_PORT: u16 = 7341;
+
+fn usage() -> ! {
+    eprintln!("Sir, a prompt is required to begin the conversation.");
+    eprintln!("Usage: cargo run -- [--json] [--graph dot|mermaid] \"<your initial question>\"");
+    eprintln!("       cargo run -- init");
+    eprintln!("       cargo run -- sessions list | sessions show <id>");
+    eprintln!("       cargo run -- resume <id>");
+    eprintln!("       cargo run -- export <id> [--format md|html|json] [--output <path>]");
+    eprintln!("       cargo run -- mcp-serve");
+    eprintln!("       cargo run -- serve [--port N] [--root <path>]...");
+    process::exit(1);
+}
+
+pub fn parse_args() -> Command {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    match args
+        .iter()
+        .map(|s| s.as_str())
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        ["init"] => return Command::Init,
+        ["sessions"] | ["sessions", "list"] => return Command::SessionsList,
+        ["sessions", "show", id] => return Command::SessionsShow(id.to_string()),
+        ["resume", id] => return Command::Resume(id.to_string()),
+        ["export", ..] => return Command::Export(parse_export(&args[1..])),
+        ["mcp-serve"] => return Command::McpServe,
+        ["serve", ..] => return Command::Serve(parse_serve(&args[1..])),
+        _ => {}
+    }
+
+    let mut graph = None;
+    let mut json = false;
+    let mut prompt = Vec::new();
+    let mut iter = args.into_iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--graph" => {
+                let value = iter.next().unwrap_or_else(|| usage());
+                match value.parse() {
+                    Ok(format) => graph = Some(format),
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        process::exit(1);
+                    }
+                }
+            }
+            "--json" => json = true,
+            _ => prompt.push(arg),
+        }
+    }
+
+    if prompt.is_empty() {
+        usage();
+    }
+    Command::Plan(PlanArgs {
+        prompt: prompt.join(" "),
+        graph,
+        json,
+    })
+}
+
+fn parse_export(args: &[String]) -> ExportArgs {
+    let mut id = None;
+    let mut format = ExportFormat::Markdown;
+    let mut output = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--format" => {
+                let value = iter.next().unwrap_or_else(|| usage());
+                format = value.parse().unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    process::exit(1);
+                });
+            }
+            "--output" | "-o" => {
+                output = Some(PathBuf::from(iter.next().unwrap_or_else(|| usage())));
+            }
+            _ if id.is_none() => id = Some(arg.clone()),
+            _ => usage(),
+        }
+    }
+
+    ExportArgs {
+        id: id.unwrap_or_else(|| usage()),
+        format,
+        output,
+    }
+}
+
+fn parse_serve(args: &[String]) -> ServeArgs {
+    let mut port = DEFAULT_PORT;
+    let mut roots = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--port" | "-p" => {
+                let value = iter.next().unwrap_or_else(|| usage());
+                port = value.parse().unwrap_or_else(|_| {
+                    eprintln!("Invalid port '{}'", value);
+                    process::exit(1);
+                });
+            }
+            "--root" => roots.push(PathBuf::from(iter.next().unwrap_or_else(|| usage()))),
+            _ => usage(),
+        }
+    }
+    ServeArgs { port, roots }
+}
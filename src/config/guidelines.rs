@@ -1,14 +1,14 @@
-use std::{env, error::Error, fs};
+use std::{error::Error, fs, path::Path};
 
-/// Attempts to load the contents of `.viktor/guidelines.md`.
+/// Attempts to load the contents of `.viktor/guidelines.md` in the
+/// repository at `root`.
 ///
 /// Returns:
 /// - Ok(Some(String)) ⇒ file existed (even if empty)
 /// - Ok(None) ⇒ `.viktor/guidelines.md` does not exist
-/// - Err(_) ⇒ I/O or env error
-pub fn load_guidelines() -> Result<Option<String>, Box<dyn Error>> {
-    let cwd = env::current_dir()?;
-    let path = cwd.join(".viktor").join("guidelines.md");
+/// - Err(_) ⇒ I/O error
+pub fn load_guidelines_from(root: &Path) -> Result<Option<String>, Box<dyn Error>> {
+    let path = root.join(".viktor").join("guidelines.md");
     if !path.exists() {
         return Ok(None);
     }
@@ -16,11 +16,11 @@ pub fn load_guidelines() -> Result<Option<String>, Box<dyn Error>> {
     Ok(Some(content))
 }
 
-/// Loads and prints the guidelines to stdout.
+/// Loads and prints the guidelines of the repository at `root` to stdout.
 ///
 /// If no file exists, prints a hint to run `viktor init`.
-pub fn print_guidelines() -> Result<(), Box<dyn Error>> {
-    match load_guidelines()? {
+pub fn print_guidelines(root: &Path) -> Result<(), Box<dyn Error>> {
+    match load_guidelines_from(root)? {
         Some(s) if !s.trim().is_empty() => {
             println!("{}", s);
         }
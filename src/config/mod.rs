@@ -1,2 +1,3 @@
 pub mod guidelines;
 pub mod init;
+pub mod settings;
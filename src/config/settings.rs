@@ -0,0 +1,307 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    env,
+    error::Error,
+    fs,
+    path::Path,
+};
+
+/// Contents of `.viktor/config.toml`. Every section is optional.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Settings {
+    pub context: ContextSettings,
+    pub tools: ToolSettings,
+    pub permissions: PermissionSettings,
+    pub hooks: HookSettings,
+    pub redaction: RedactionSettings,
+    pub mcp: McpSettings,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ContextSettings {
+    /// Context window requested from Ollama through `options.num_ctx`.
+    pub num_ctx: usize,
+    /// Fraction of `num_ctx` at which the conversation gets compacted.
+    pub compact_threshold: f32,
+    /// Number of most recent messages compaction never touches.
+    pub keep_recent: usize,
+}
+
+impl Default for ContextSettings {
+    fn default() -> Self {
+        Self {
+            num_ctx: 16384,
+            compact_threshold: 0.8,
+            keep_recent: 6,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ToolSettings {
+    /// How many read-only tool calls from one model reply may run at once.
+    pub max_parallel: usize,
+    /// Largest tool output, in characters, passed to the model unabridged.
+    pub max_output_chars: usize,
+    /// Per-tool overrides of `max_output_chars`, keyed by tool name.
+    pub output_limits: HashMap<String, usize>,
+    /// Combined budget for all tool outputs answering one model reply.
+    pub max_step_output_chars: usize,
+    pub crawler: CrawlerSettings,
+    pub shell: ShellSettings,
+    pub lsp: LspSettings,
+}
+
+impl ToolSettings {
+    /// Output budget for a single call of `tool`.
+    pub fn output_limit(&self, tool: &str) -> usize {
+        self.output_limits
+            .get(tool)
+            .copied()
+            .unwrap_or(self.max_output_chars)
+    }
+}
+
+impl Default for ToolSettings {
+    fn default() -> Self {
+        Self {
+            max_parallel: 4,
+            max_output_chars: 16_000,
+            output_limits: HashMap::new(),
+            max_step_output_chars: 32_000,
+            crawler: CrawlerSettings::default(),
+            shell: ShellSettings::default(),
+            lsp: LspSettings::default(),
+        }
+    }
+}
+
+/// The `[tools.crawler]` section. Ignored paths come from `.gitignore` and
+/// `.viktorignore`; `.git`, `target` and `node_modules` are always excluded.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct CrawlerSettings {
+    /// Lets crawler calls pass `include_hidden` to see dotfiles.
+    pub allow_hidden: bool,
+}
+
+/// The `[tools.shell]` section, governing `shell.run`.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ShellSettings {
+    /// Command prefixes the model may run, e.g. `"cargo test"`.
+    pub allow: Vec<String>,
+    pub timeout_secs: u64,
+    /// Output kept per stream (stdout, stderr), in bytes.
+    pub max_output_chars: usize,
+    /// Environment variables passed through on top of PATH, HOME and friends.
+    pub pass_env: Vec<String>,
+}
+
+impl Default for ShellSettings {
+    fn default() -> Self {
+        Self {
+            allow: [
+                "cargo check",
+                "cargo test",
+                "cargo tree",
+                "ls",
+                "git status",
+                "git diff",
+            ]
+            .map(String::from)
+            .to_vec(),
+            timeout_secs: 120,
+            max_output_chars: 12_000,
+            pass_env: Vec::new(),
+        }
+    }
+}
+
+/// The `[tools.lsp]` section. The `lsp.*` tools are offered only when enabled
+/// and the command is installed; the server starts on first use.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct LspSettings {
+    pub enabled: bool,
+    pub command: String,
+    pub args: Vec<String>,
+    /// How long the server may take to start and load the workspace.
+    pub startup_timeout_secs: u64,
+    pub request_timeout_secs: u64,
+}
+
+impl Default for LspSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            command: "rust-analyzer".to_string(),
+            args: Vec::new(),
+            startup_timeout_secs: 120,
+            request_timeout_secs: 30,
+        }
+    }
+}
+
+/// The `[mcp]` section: external MCP servers whose tools are offered as
+/// `mcp.<server>.<tool>`.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct McpSettings {
+    pub servers: BTreeMap<String, McpServerSettings>,
+}
+
+/// One `[mcp.servers.<name>]` entry, started over stdio.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct McpServerSettings {
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Extra environment variables for the server process.
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    #[serde(default = "default_mcp_timeout")]
+    pub timeout_secs: u64,
+}
+
+fn default_mcp_timeout() -> u64 {
+    30
+}
+
+/// What happens to a tool call a permission rule matches.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Allow,
+    #[default]
+    Ask,
+    Deny,
+}
+
+/// The `[permissions]` section. Rules are checked in order and the first
+/// match decides; calls no rule matches are allowed for read-only tools and
+/// get `default` otherwise.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct PermissionSettings {
+    pub default: Action,
+    pub rules: Vec<PermissionRule>,
+}
+
+/// One `[[permissions.rules]]` entry.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct PermissionRule {
+    /// Tool name or glob, e.g. `shell.run` or `crawler.*`.
+    pub tool: String,
+    /// Glob matched against `path`/`paths` arguments; any match counts.
+    pub path: Option<String>,
+    /// Command prefix for `shell.run`, matched word by word.
+    pub command: Option<String>,
+    pub action: Action,
+    /// Passed to the model when the rule denies a call.
+    pub message: Option<String>,
+}
+
+/// The `[hooks]` section: commands run around tool calls and the final plan.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct HookSettings {
+    pub pre_tool: Vec<HookCommand>,
+    pub post_tool: Vec<HookCommand>,
+    pub post_plan: Vec<HookCommand>,
+}
+
+/// One `[[hooks.<point>]]` entry.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct HookCommand {
+    /// Run with `sh -c` in the repository root.
+    pub command: String,
+    /// Tool name or glob the hook is limited to; tool hooks only.
+    pub tool: Option<String>,
+    #[serde(default = "default_hook_timeout")]
+    pub timeout_secs: u64,
+    /// What a failing or timed-out hook amounts to; by default pre_tool
+    /// hooks block and the others are ignored.
+    pub on_error: Option<OnError>,
+}
+
+/// What happens when a hook cannot run, times out or exits with neither 0
+/// nor 2.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OnError {
+    Ignore,
+    Block,
+}
+
+fn default_hook_timeout() -> u64 {
+    30
+}
+
+/// The `[redaction]` section: secret masking in tool outputs.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct RedactionSettings {
+    pub enabled: bool,
+    /// Also mask long random-looking tokens.
+    pub entropy: bool,
+    /// Bits per character from which a token counts as random.
+    pub min_entropy: f64,
+    /// Extra regexes to mask; with a capture group only the group is masked.
+    pub patterns: Vec<String>,
+    /// Globs of files whose contents never reach the model.
+    pub sensitive_paths: Vec<String>,
+}
+
+impl Default for RedactionSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            entropy: true,
+            min_entropy: 4.5,
+            patterns: Vec::new(),
+            sensitive_paths: [
+                "**/.env",
+                "**/.env.*",
+                "**/*.pem",
+                "**/*.key",
+                "**/*.p12",
+                "**/*.pfx",
+                "**/*.keystore",
+                "**/id_rsa",
+                "**/id_dsa",
+                "**/id_ecdsa",
+                "**/id_ed25519",
+                "**/.aws/credentials",
+                "**/.netrc",
+                "**/.npmrc",
+                "**/.pypirc",
+                "**/.git-credentials",
+            ]
+            .map(String::from)
+            .to_vec(),
+        }
+    }
+}
+
+/// Loads `.viktor/config.toml`, falling back to defaults when it does not exist.
+pub fn load_settings() -> Result<Settings, Box<dyn Error>> {
+    load_settings_from(&env::current_dir()?)
+}
+
+/// Loads the settings of the repository at `root`.
+pub fn load_settings_from(root: &Path) -> Result<Settings, Box<dyn Error>> {
+    let path = root.join(".viktor").join("config.toml");
+    if !path.exists() {
+        return Ok(Settings::default());
+    }
+    let content = fs::read_to_string(&path)?;
+    toml::from_str(&content).map_err(|e| format!("{}: {}", path.display(), e).into())
+}
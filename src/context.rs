@@ -0,0 +1,544 @@
+use ollama::{
+    types::{ChatMessage, ChatRequest, MessageRole, ToolCall, ToolDefinition},
+    OllamaClient,
+};
+use serde_json::{json, Value};
+use std::{collections::HashSet, error::Error};
+use tokio_util::sync::CancellationToken;
+
+use crate::{config::settings::ContextSettings, session::Session};
+
+/// Rough average for code and prose; good enough to stay ahead of truncation.
+const CHARS_PER_TOKEN: usize = 4;
+/// Role markers and template tokens Ollama wraps around every message.
+const MESSAGE_OVERHEAD: usize = 4;
+/// Tool outputs shorter than this are not worth summarizing.
+const MIN_SUMMARIZED_CHARS: usize = 400;
+
+const SUMMARY_MARKER: &str = "Summary of earlier research:";
+const STALE_READ: &str = "[stale: this file was read again later in the conversation]";
+
+/// Token budget for one conversation, derived from the model's `num_ctx`.
+#[derive(Debug, Clone)]
+pub struct ContextBudget {
+    pub num_ctx: usize,
+    pub compact_threshold: f32,
+    pub keep_recent: usize,
+}
+
+impl From<&ContextSettings> for ContextBudget {
+    fn from(settings: &ContextSettings) -> Self {
+        Self {
+            num_ctx: settings.num_ctx,
+            compact_threshold: settings.compact_threshold,
+            keep_recent: settings.keep_recent,
+        }
+    }
+}
+
+impl ContextBudget {
+    /// Estimated prompt size above which the conversation is compacted.
+    pub fn limit(&self) -> usize {
+        (self.num_ctx as f32 * self.compact_threshold) as usize
+    }
+
+    /// Runtime options for `ChatRequest::options`.
+    pub fn options(&self) -> Value {
+        json!({ "num_ctx": self.num_ctx })
+    }
+}
+
+pub fn estimate_tokens(message: &ChatMessage) -> usize {
+    let mut chars = message.content.len();
+    for call in message.tool_calls.iter().flatten() {
+        chars += call.function.name.len() + call.function.arguments.to_string().len();
+    }
+    chars / CHARS_PER_TOKEN + MESSAGE_OVERHEAD
+}
+
+pub fn estimate_messages(messages: &[ChatMessage]) -> usize {
+    messages.iter().map(estimate_tokens).sum()
+}
+
+/// Tool definitions are sent with every request and count against the window too.
+pub fn estimate_tools(tools: &[ToolDefinition]) -> usize {
+    serde_json::to_string(tools).map_or(0, |s| s.len() / CHARS_PER_TOKEN)
+}
+
+/// The tool call that produced the tool message at `index`, matched by
+/// position after the assistant message that requested it.
+pub fn tool_call_for(messages: &[ChatMessage], index: usize) -> Option<&ToolCall> {
+    let mut offset = 0;
+    for i in (0..index).rev() {
+        match messages[i].role {
+            MessageRole::Tool => offset += 1,
+            MessageRole::Assistant => {
+                return messages[i].tool_calls.as_ref()?.get(offset);
+            }
+            _ => return None,
+        }
+    }
+    None
+}
+
+/// Compacts the session when its estimated size plus `reserved` tokens
+/// would cross the budget.
+///
+/// The system prompt and the first user message (the goal) are pinned, as are
+/// the `keep_recent` latest messages. Older file reads superseded by a later
+/// read of the same lines are dropped first; if that is not enough, the
+/// remaining old tool outputs are summarized by the model.
+pub async fn compact_if_needed(
+    client: &OllamaClient,
+    session: &mut Session,
+    reserved: usize,
+    cancel: &CancellationToken,
+) -> Result<(), Box<dyn Error>> {
+    let limit = session.context.limit();
+    let before = estimate_messages(&session.messages) + reserved;
+    if before <= limit {
+        return Ok(());
+    }
+
+    let mut messages = session.messages.clone();
+    drop_stale_reads(&mut messages);
+
+    if estimate_messages(&messages) + reserved > limit {
+        let cutoff = messages.len().saturating_sub(session.context.keep_recent);
+        summarize_tool_outputs(client, session, &mut messages, cutoff, limit, cancel).await;
+    }
+
+    let after = estimate_messages(&messages) + reserved;
+    if after == before {
+        eprintln!(
+            "\n⚠️ Context is at ~{} of {} tokens and nothing is left to compact",
+            before, session.context.num_ctx
+        );
+        return Ok(());
+    }
+    println!(
+        "\n🗜️ Compacted context: ~{} → ~{} tokens (budget {} of num_ctx {})",
+        before, after, limit, session.context.num_ctx
+    );
+    session.replace_messages(messages);
+    Ok(())
+}
+
+/// Replaces the contents of file reads that a later `read_file_contents`
+/// call fetched again: a later whole-file read supersedes every earlier read
+/// of the path, a later ranged read only earlier reads of the same lines.
+fn drop_stale_reads(messages: &mut [ChatMessage]) {
+    let mut whole = HashSet::new();
+    let mut ranges = HashSet::new();
+    for i in (0..messages.len()).rev() {
+        let is_read = matches!(messages[i].role, MessageRole::Tool)
+            && tool_call_for(messages, i)
+                .is_some_and(|c| c.function.name == "crawler.read_file_contents");
+        if !is_read {
+            continue;
+        }
+        let Ok(mut output) = serde_json::from_str::<Value>(&messages[i].content) else {
+            continue;
+        };
+        let Some(results) = output.get_mut("results").and_then(Value::as_array_mut) else {
+            continue;
+        };
+
+        let mut changed = false;
+        for result in results {
+            // Failed reads fetched nothing and supersede nothing.
+            let (Some(path), Some(_)) = (
+                result.get("path").and_then(Value::as_str),
+                result.get("content"),
+            ) else {
+                continue;
+            };
+            let path = path.to_string();
+            let range = result
+                .get("start_line")
+                .and_then(Value::as_u64)
+                .zip(result.get("end_line").and_then(Value::as_u64));
+            let stale = whole.contains(&path)
+                || range.is_some_and(|range| ranges.contains(&(path.clone(), range)));
+            match range {
+                Some(range) => ranges.insert((path, range)),
+                None => whole.insert(path),
+            };
+            if stale && result["content"] != STALE_READ {
+                result["content"] = json!(STALE_READ);
+                changed = true;
+            }
+        }
+        if changed {
+            messages[i].content = output.to_string();
+        }
+    }
+}
+
+/// Folds tool outputs before `cutoff` into a single pinned summary message.
+async fn summarize_tool_outputs(
+    client: &OllamaClient,
+    session: &Session,
+    messages: &mut Vec<ChatMessage>,
+    cutoff: usize,
+    limit: usize,
+    cancel: &CancellationToken,
+) {
+    let goal = messages
+        .iter()
+        .find(|m| matches!(m.role, MessageRole::User))
+        .map(|m| m.content.clone())
+        .unwrap_or_default();
+    let previous = messages.iter().position(|m| {
+        matches!(m.role, MessageRole::System) && m.content.starts_with(SUMMARY_MARKER)
+    });
+
+    // Keep the summarization request itself inside the window.
+    let max_input_chars = limit * CHARS_PER_TOKEN / 2;
+    let (input, folded) = foldable_outputs(messages, previous, cutoff, max_input_chars);
+    if folded.is_empty() {
+        return;
+    }
+
+    let summary = match request_summary(client, session, &goal, &input, cancel).await {
+        Ok(summary) => summary,
+        Err(_) if cancel.is_cancelled() => return,
+        Err(e) => {
+            eprintln!("⚠️ Unable to summarize old tool outputs: {}", e);
+            return;
+        }
+    };
+    fold_into_summary(messages, previous, folded, &summary);
+}
+
+/// The summarization input: the previous summary, if any, then labelled
+/// tool outputs before `cutoff` while they fit in `max_input_chars`. Also
+/// returns which messages went in, with their labels.
+fn foldable_outputs(
+    messages: &[ChatMessage],
+    previous: Option<usize>,
+    cutoff: usize,
+    max_input_chars: usize,
+) -> (String, Vec<(usize, String)>) {
+    let mut input = String::new();
+    if let Some(i) = previous {
+        input.push_str(&messages[i].content);
+        input.push_str("\n\n");
+    }
+
+    let mut folded = Vec::new();
+    for i in 0..cutoff.min(messages.len()) {
+        let message = &messages[i];
+        if !matches!(message.role, MessageRole::Tool)
+            || message.content.len() < MIN_SUMMARIZED_CHARS
+        {
+            continue;
+        }
+        let label = tool_call_for(messages, i)
+            .map(|c| format!("{} {}", c.function.name, c.function.arguments))
+            .unwrap_or_else(|| "tool".to_string());
+        let chunk = format!("### {}\n{}\n\n", label, message.content);
+        if input.len() + chunk.len() > max_input_chars {
+            break;
+        }
+        input.push_str(&chunk);
+        folded.push((i, label));
+    }
+    (input, folded)
+}
+
+/// Stubs the folded tool outputs and pins `summary` in place of the
+/// previous one, or right after the system prompt and goal. Messages are
+/// only rewritten, never removed, so tool outputs stay paired with the
+/// assistant message that asked for them.
+fn fold_into_summary(
+    messages: &mut Vec<ChatMessage>,
+    previous: Option<usize>,
+    folded: Vec<(usize, String)>,
+    summary: &str,
+) {
+    for (i, label) in folded {
+        messages[i].content = format!(
+            "[compacted: output of {} is folded into the research summary]",
+            label
+        );
+    }
+    let summary_message = ChatMessage {
+        role: MessageRole::System,
+        content: format!("{}\n{}", SUMMARY_MARKER, summary.trim()),
+        thinking: None,
+        images: None,
+        tool_calls: None,
+    };
+    match previous {
+        Some(i) => messages[i] = summary_message,
+        None => {
+            // Right after the pinned system prompt and goal.
+            let at = messages
+                .iter()
+                .position(|m| matches!(m.role, MessageRole::User))
+                .map_or(messages.len(), |i| i + 1);
+            messages.insert(at, summary_message);
+        }
+    }
+}
+
+async fn request_summary(
+    client: &OllamaClient,
+    session: &Session,
+    goal: &str,
+    input: &str,
+    cancel: &CancellationToken,
+) -> Result<String, Box<dyn Error>> {
+    let chat_req = ChatRequest {
+        model: session.model.clone(),
+        messages: vec![
+            ChatMessage {
+                role: MessageRole::System,
+                content: "You condense tool outputs gathered while researching a codebase. \
+Keep every file path, symbol name, signature and fact that matters for the goal. \
+Drop boilerplate and anything unrelated. Answer with the summary only."
+                    .to_string(),
+                thinking: None,
+                images: None,
+                tool_calls: None,
+            },
+            ChatMessage {
+                role: MessageRole::User,
+                content: format!("Goal:\n{}\n\nTool outputs:\n{}", goal, input),
+                thinking: None,
+                images: None,
+                tool_calls: None,
+            },
+        ],
+        tools: None,
+        stream: false,
+        format: None,
+        think: false,
+        options: Some(session.context.options()),
+        keep_alive: None,
+    };
+    Ok(client
+        .chat_cancellable(&chat_req, cancel)
+        .await?
+        .message
+        .content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ollama::types::FunctionRef;
+
+    fn message(role: MessageRole, content: &str) -> ChatMessage {
+        ChatMessage {
+            role,
+            content: content.to_string(),
+            thinking: None,
+            images: None,
+            tool_calls: None,
+        }
+    }
+
+    /// An assistant message reading `arguments` and the tool message with
+    /// `results`.
+    fn read(arguments: Value, results: Value) -> [ChatMessage; 2] {
+        let mut call = message(MessageRole::Assistant, "");
+        call.tool_calls = Some(vec![ToolCall {
+            function: FunctionRef {
+                name: "crawler.read_file_contents".to_string(),
+                arguments,
+            },
+        }]);
+        let output = message(
+            MessageRole::Tool,
+            &json!({ "results": results }).to_string(),
+        );
+        [call, output]
+    }
+
+    fn ranged(path: &str, start: u64, end: u64, content: &str) -> [ChatMessage; 2] {
+        read(
+            json!({"paths": [path], "start_line": start, "end_line": end}),
+            json!([{
+                "path": path,
+                "start_line": start,
+                "end_line": end,
+                "total_lines": 400,
+                "content": content,
+            }]),
+        )
+    }
+
+    fn whole(path: &str, content: &str) -> [ChatMessage; 2] {
+        read(
+            json!({ "paths": [path] }),
+            json!([{ "path": path, "content": content }]),
+        )
+    }
+
+    /// The `content` of every read result, in order.
+    fn contents(messages: &[ChatMessage]) -> Vec<String> {
+        messages
+            .iter()
+            .filter(|m| matches!(m.role, MessageRole::Tool))
+            .flat_map(|m| {
+                let output: Value = serde_json::from_str(&m.content).unwrap();
+                output["results"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|r| r["content"].as_str().unwrap_or_default().to_string())
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn disjoint_ranges_of_a_file_are_both_kept() {
+        let mut messages = vec![message(MessageRole::System, "prompt")];
+        messages.extend(ranged("src/a.rs", 1, 200, "first"));
+        messages.extend(ranged("src/a.rs", 200, 400, "second"));
+        drop_stale_reads(&mut messages);
+        assert_eq!(contents(&messages), ["first", "second"]);
+    }
+
+    #[test]
+    fn a_range_is_superseded_by_the_same_range_or_a_whole_read() {
+        let mut messages = vec![message(MessageRole::System, "prompt")];
+        messages.extend(ranged("src/a.rs", 1, 200, "first"));
+        messages.extend(ranged("src/a.rs", 1, 200, "again"));
+        messages.extend(ranged("src/a.rs", 200, 400, "second"));
+        messages.extend(whole("src/a.rs", "everything"));
+        drop_stale_reads(&mut messages);
+        assert_eq!(
+            contents(&messages),
+            [STALE_READ, STALE_READ, STALE_READ, "everything"]
+        );
+
+        // A whole read does not go stale because of a later ranged one.
+        let mut messages = whole("src/b.rs", "everything").to_vec();
+        messages.extend(ranged("src/b.rs", 1, 10, "head"));
+        drop_stale_reads(&mut messages);
+        assert_eq!(contents(&messages), ["everything", "head"]);
+    }
+
+    fn tool_call_names(messages: &[ChatMessage]) -> Vec<Option<String>> {
+        (0..messages.len())
+            .map(|i| tool_call_for(messages, i).map(|c| c.function.name.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn an_earlier_read_of_a_re_read_file_is_stubbed() {
+        let mut messages = vec![
+            message(MessageRole::System, "prompt"),
+            message(MessageRole::User, "goal"),
+        ];
+        messages.extend(whole("src/a.rs", "old"));
+        messages.extend(whole("src/b.rs", "other"));
+        messages.extend(whole("src/a.rs", "new"));
+        drop_stale_reads(&mut messages);
+        assert_eq!(contents(&messages), [STALE_READ, "other", "new"]);
+    }
+
+    #[test]
+    fn tool_outputs_are_matched_to_their_calls() {
+        let mut call = message(MessageRole::Assistant, "");
+        call.tool_calls = Some(
+            ["crawler.tree", "cargo.metadata"]
+                .map(|name| ToolCall {
+                    function: FunctionRef {
+                        name: name.to_string(),
+                        arguments: json!({}),
+                    },
+                })
+                .to_vec(),
+        );
+        let messages = vec![
+            message(MessageRole::User, "goal"),
+            call,
+            message(MessageRole::Tool, "tree"),
+            message(MessageRole::Tool, "metadata"),
+            message(MessageRole::Tool, "orphan"),
+        ];
+        assert_eq!(
+            tool_call_names(&messages),
+            [
+                None,
+                None,
+                Some("crawler.tree".to_string()),
+                Some("cargo.metadata".to_string()),
+                None,
+            ]
+        );
+    }
+
+    /// System prompt, goal, then `reads` whole-file reads of distinct files.
+    fn conversation(reads: usize) -> Vec<ChatMessage> {
+        let mut messages = vec![
+            message(MessageRole::System, "prompt"),
+            message(MessageRole::User, "goal"),
+        ];
+        for i in 0..reads {
+            let content = format!("{}{}", i, "x".repeat(MIN_SUMMARIZED_CHARS));
+            messages.extend(whole(&format!("src/{}.rs", i), &content));
+        }
+        messages
+    }
+
+    #[test]
+    fn folding_pins_the_summary_after_the_goal_and_keeps_pairs() {
+        let mut messages = conversation(3);
+        let before = tool_call_names(&messages);
+        // The last pair is recent and stays as it is.
+        let cutoff = messages.len() - 2;
+        let (input, folded) = foldable_outputs(&messages, None, cutoff, usize::MAX);
+        assert_eq!(folded.iter().map(|(i, _)| *i).collect::<Vec<_>>(), [3, 5]);
+        assert!(input.contains("### crawler.read_file_contents"));
+
+        fold_into_summary(&mut messages, None, folded, "what was found");
+        assert_eq!(messages[0].content, "prompt");
+        assert_eq!(messages[1].content, "goal");
+        assert_eq!(
+            messages[2].content,
+            format!("{}\nwhat was found", SUMMARY_MARKER)
+        );
+        // Only the summary was added; every output still follows its call.
+        let mut expected = before.clone();
+        expected.insert(2, None);
+        assert_eq!(tool_call_names(&messages), expected);
+        assert!(messages[4].content.starts_with("[compacted:"));
+        assert!(messages[8].content.contains("2xxx"));
+    }
+
+    #[test]
+    fn a_second_fold_replaces_the_pinned_summary() {
+        let mut messages = conversation(2);
+        let cutoff = messages.len();
+        let (_, folded) = foldable_outputs(&messages, None, cutoff, usize::MAX);
+        fold_into_summary(&mut messages, None, folded, "first");
+        messages.extend(whole("src/late.rs", &"y".repeat(MIN_SUMMARIZED_CHARS)));
+
+        let previous = Some(2);
+        let cutoff = messages.len();
+        let (input, folded) = foldable_outputs(&messages, previous, cutoff, usize::MAX);
+        assert!(input.starts_with(SUMMARY_MARKER));
+        assert_eq!(folded.len(), 1);
+        let count = messages.len();
+        fold_into_summary(&mut messages, previous, folded, "second");
+        assert_eq!(messages.len(), count);
+        assert_eq!(messages[2].content, format!("{}\nsecond", SUMMARY_MARKER));
+        assert_eq!(messages[0].content, "prompt");
+        assert_eq!(messages[1].content, "goal");
+    }
+
+    #[test]
+    fn folding_stops_at_the_input_budget() {
+        let messages = conversation(3);
+        let cutoff = messages.len();
+        let (input, folded) = foldable_outputs(&messages, None, cutoff, MIN_SUMMARIZED_CHARS * 2);
+        assert_eq!(folded.len(), 1);
+        assert!(input.len() <= MIN_SUMMARIZED_CHARS * 2);
+    }
+}
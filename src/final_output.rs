@@ -0,0 +1,179 @@
+use ollama::{
+    types::{ChatMessage, MessageRole},
+    OllamaClient,
+};
+use std::error::Error;
+use tokio_util::sync::CancellationToken;
+use tools::crawler::Crawler;
+
+use crate::{
+    repl::confirm,
+    response::{res_format, FileCheck, FileStatus, Response},
+    session::Session,
+};
+
+/// How many times the model may retry after returning an unusable plan.
+const MAX_REPAIR_ATTEMPTS: usize = 3;
+
+const FINAL_OUTPUT_PROMPT: &str = "Based on all the information gathered and your reasoning, please provide the complete task breakdown in the precise JSON format you were instructed to use. Ensure the output is a valid JSON object matching the updated `tasks` schema with id, objective, affected_files, changes and depends_on fields. Give every task a short unique id (T1, T2, ...) and list in depends_on the ids of the tasks that must be finished first, so independent tasks can be worked on in parallel.";
+
+/// Asks the model for the structured plan, feeding parse errors back to it
+/// until it produces a valid `Response` or the repair budget runs out.
+async fn request_final_output(
+    client: &OllamaClient,
+    session: &mut Session,
+    cancel: &CancellationToken,
+) -> Result<Response, Box<dyn Error>> {
+    request_structured_output(client, session, FINAL_OUTPUT_PROMPT.to_string(), cancel).await
+}
+
+/// Requests the final plan, verifies its `affected_files` against the
+/// repository and offers to let the model fix invalid paths. The plan is then
+/// shown to the post_plan hooks, which may revise it or object; objections go
+/// back to the model. The accepted plan is recorded in the session.
+pub async fn produce_plan(
+    client: &OllamaClient,
+    session: &mut Session,
+    cancel: &CancellationToken,
+) -> Result<Response, Box<dyn Error>> {
+    let crawler = Crawler::new(&session.root);
+    let mut res = request_final_output(client, session, cancel).await?;
+    res.verify_files(&crawler).await;
+
+    while !res.invalid_files().is_empty() {
+        let invalid = res.invalid_files();
+        println!("\n⚠️ {} affected file(s) do not exist:", invalid.len());
+        for check in &invalid {
+            match &check.status {
+                FileStatus::Invalid {
+                    suggestion: Some(s),
+                } => println!("    ✗ {} (did you mean {}?)", check.path, s),
+                _ => println!("    ✗ {}", check.path),
+            }
+        }
+        if !session.interactive || !confirm("Ask the model to fix them?")? {
+            break;
+        }
+        res = match request_path_fix(client, session, &invalid, cancel).await {
+            Ok(fixed) => fixed,
+            Err(e) if cancel.is_cancelled() => return Err(e),
+            Err(e) => {
+                eprintln!("\n❌ Unable to fix affected files: {}", e);
+                break;
+            }
+        };
+        res.verify_files(&crawler).await;
+    }
+
+    let hooks = session.hooks.clone();
+    let mut revisions = 0;
+    let res = loop {
+        let reason = match hooks.post_plan(session, res).await {
+            Ok(mut accepted) => {
+                // A hook may have swapped in a revised plan.
+                accepted.verify_files(&crawler).await;
+                break accepted;
+            }
+            Err(reason) => reason,
+        };
+        println!("\n🪝 A post_plan hook rejected the plan: {}", reason);
+        if revisions == MAX_REPAIR_ATTEMPTS {
+            return Err(format!("plan rejected by a post_plan hook: {}", reason).into());
+        }
+        revisions += 1;
+        let instruction = format!(
+            "The task breakdown was rejected: {}\n\nRevise it accordingly and return the \
+complete task breakdown again in the same JSON format.",
+            reason
+        );
+        res = request_structured_output(client, session, instruction, cancel).await?;
+        res.verify_files(&crawler).await;
+    };
+
+    session.record_plan(&res);
+    Ok(res)
+}
+
+/// Sends the invalid `affected_files` entries back to the model and asks for
+/// a corrected plan.
+async fn request_path_fix(
+    client: &OllamaClient,
+    session: &mut Session,
+    invalid: &[&FileCheck],
+    cancel: &CancellationToken,
+) -> Result<Response, Box<dyn Error>> {
+    let listing = invalid
+        .iter()
+        .map(|check| match &check.status {
+            FileStatus::Invalid {
+                suggestion: Some(s),
+            } => format!("- {} (closest existing path: {})", check.path, s),
+            _ => format!("- {}", check.path),
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let instruction = format!(
+        "These affected_files entries do not exist in the repository and their parent \
+directories do not exist either:\n{}\n\nCorrect the paths (use the closest existing path \
+where it is the intended file) and return the complete task breakdown again in the same JSON format.",
+        listing
+    );
+    request_structured_output(client, session, instruction, cancel).await
+}
+
+async fn request_structured_output(
+    client: &OllamaClient,
+    session: &mut Session,
+    instruction: String,
+    cancel: &CancellationToken,
+) -> Result<Response, Box<dyn Error>> {
+    session.push(ChatMessage {
+        role: MessageRole::User,
+        content: instruction,
+        thinking: None,
+        images: None,
+        tool_calls: None,
+    });
+
+    let mut last_error = String::new();
+    for attempt in 0..=MAX_REPAIR_ATTEMPTS {
+        if attempt > 0 {
+            println!(
+                "\n🔁 Repairing structured output (attempt {}/{})",
+                attempt, MAX_REPAIR_ATTEMPTS
+            );
+        }
+
+        let content = session
+            .chat(client, None, Some(res_format()), false, cancel)
+            .await?
+            .content;
+
+        match Response::parse(&content) {
+            Ok(response) => return Ok(response),
+            Err(e) => {
+                eprintln!("\n⚠️ Structured output rejected: {}", e);
+                last_error = e.clone();
+                session.push(ChatMessage {
+                    role: MessageRole::User,
+                    content: format!(
+                        "Your previous answer could not be used: {}. \
+Reply with only a single JSON object that matches the `tasks` schema exactly, \
+with no prose or code fences around it.",
+                        e
+                    ),
+                    thinking: None,
+                    images: None,
+                    tool_calls: None,
+                });
+            }
+        }
+    }
+
+    Err(format!(
+        "model did not produce a valid task breakdown after {} repair attempt(s): {}",
+        MAX_REPAIR_ATTEMPTS, last_error
+    )
+    .into())
+}
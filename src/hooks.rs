@@ -0,0 +1,428 @@
+use globset::{Glob, GlobMatcher};
+use ollama::types::ToolCall;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{error::Error, fmt, path::Path, process::Stdio, time::Duration};
+use tokio::{io::AsyncWriteExt, process::Command, time};
+
+use crate::{
+    config::settings::{HookCommand, HookSettings, OnError},
+    response::Response,
+    session::Session,
+};
+
+/// Exit code with which a hook blocks the call or plan it was shown.
+const BLOCK_EXIT_CODE: i32 = 2;
+
+/// Where in the run a hook fires.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HookPoint {
+    PreTool,
+    PostTool,
+    PostPlan,
+}
+
+impl fmt::Display for HookPoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            HookPoint::PreTool => "pre_tool",
+            HookPoint::PostTool => "post_tool",
+            HookPoint::PostPlan => "post_plan",
+        })
+    }
+}
+
+/// The `[hooks]` commands of a session.
+///
+/// A hook is run with `sh -c` in the repository root and gets a JSON object
+/// on stdin. Exit code 0 lets the run continue, 2 blocks it. Anything else
+/// (or a timeout) is reported and then handled as its `on_error` says:
+/// pre_tool hooks block by default, the others are ignored. On stdout a hook
+/// may print a JSON object whose `arguments` (pre_tool), `output`
+/// (post_tool) or `plan` (post_plan) replace the original and whose
+/// `message` is shown to the model; plain text counts as a message.
+#[derive(Clone, Default)]
+pub struct Hooks {
+    pre_tool: Vec<Hook>,
+    post_tool: Vec<Hook>,
+    post_plan: Vec<Hook>,
+}
+
+#[derive(Clone)]
+struct Hook {
+    command: String,
+    tool: Option<GlobMatcher>,
+    timeout: Duration,
+    on_error: OnError,
+}
+
+/// What a single hook run amounted to.
+struct Reply {
+    blocked: bool,
+    fields: serde_json::Map<String, Value>,
+}
+
+impl Reply {
+    /// Reads a hook's stdout: a JSON object, or plain text as its message.
+    fn new(blocked: bool, stdout: &str) -> Self {
+        let stdout = stdout.trim();
+        let fields = match serde_json::from_str::<Value>(stdout) {
+            Ok(Value::Object(fields)) => fields,
+            _ if stdout.is_empty() => serde_json::Map::new(),
+            _ => serde_json::Map::from_iter([("message".to_string(), json!(stdout))]),
+        };
+        Reply { blocked, fields }
+    }
+
+    fn message(&self) -> Option<String> {
+        self.fields
+            .get("message")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+    }
+}
+
+impl Hooks {
+    pub fn new(settings: &HookSettings) -> Result<Self, Box<dyn Error>> {
+        let compile = |commands: &[HookCommand], on_error: OnError| {
+            commands
+                .iter()
+                .map(|c| {
+                    let tool = match &c.tool {
+                        Some(pattern) => Some(
+                            Glob::new(pattern)
+                                .map_err(|e| format!("hook tool '{}': {}", pattern, e))?
+                                .compile_matcher(),
+                        ),
+                        None => None,
+                    };
+                    Ok(Hook {
+                        command: c.command.clone(),
+                        tool,
+                        timeout: Duration::from_secs(c.timeout_secs),
+                        on_error: c.on_error.unwrap_or(on_error),
+                    })
+                })
+                .collect::<Result<Vec<_>, String>>()
+        };
+        Ok(Self {
+            pre_tool: compile(&settings.pre_tool, OnError::Block)?,
+            post_tool: compile(&settings.post_tool, OnError::Ignore)?,
+            post_plan: compile(&settings.post_plan, OnError::Ignore)?,
+        })
+    }
+
+    /// Runs the pre_tool hooks for `call`, which they may rewrite. Returns
+    /// their messages, or the tool output to send instead when one blocks.
+    pub async fn pre_tool(
+        &self,
+        session: &mut Session,
+        call: &mut ToolCall,
+    ) -> Result<Vec<String>, String> {
+        let mut messages = Vec::new();
+        for hook in Self::for_tool(&self.pre_tool, &call.function.name) {
+            let input = json!({
+                "hook": HookPoint::PreTool,
+                "session_id": session.id,
+                "tool": call.function.name,
+                "arguments": call.function.arguments,
+            });
+            let Some(reply) = hook.run(&session.root, &input).await else {
+                continue;
+            };
+            let message = reply.message();
+            if reply.blocked {
+                let reason =
+                    message.unwrap_or_else(|| format!("blocked by hook `{}`", hook.command));
+                println!("🪝 {} blocked by hook: {}", call.function.name, reason);
+                session.record_hook(HookPoint::PreTool, &hook.command, true, Some(&reason));
+                return Err(json!({"error": "Blocked by a hook", "message": reason}).to_string());
+            }
+            if let Some(arguments) = reply.fields.get("arguments") {
+                call.function.arguments = arguments.clone();
+            }
+            if reply.fields.contains_key("arguments") || message.is_some() {
+                session.record_hook(HookPoint::PreTool, &hook.command, false, message.as_deref());
+            }
+            messages.extend(message);
+        }
+        Ok(messages)
+    }
+
+    /// Runs the post_tool hooks over `output`, which they may rewrite or
+    /// annotate. A blocking hook withholds the output from the model.
+    pub async fn post_tool(
+        &self,
+        session: &mut Session,
+        call: &ToolCall,
+        output: String,
+    ) -> String {
+        let mut output = output;
+        let mut messages = Vec::new();
+        for hook in Self::for_tool(&self.post_tool, &call.function.name) {
+            let input = json!({
+                "hook": HookPoint::PostTool,
+                "session_id": session.id,
+                "tool": call.function.name,
+                "arguments": call.function.arguments,
+                "output": output,
+            });
+            let Some(reply) = hook.run(&session.root, &input).await else {
+                continue;
+            };
+            let message = reply.message();
+            if reply.blocked {
+                let reason =
+                    message.unwrap_or_else(|| format!("withheld by hook `{}`", hook.command));
+                println!(
+                    "🪝 {} output withheld by hook: {}",
+                    call.function.name, reason
+                );
+                session.record_hook(HookPoint::PostTool, &hook.command, true, Some(&reason));
+                return json!({"error": "Output withheld by a hook", "message": reason})
+                    .to_string();
+            }
+            if let Some(replaced) = reply.fields.get("output") {
+                output = match replaced {
+                    Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                };
+            }
+            if reply.fields.contains_key("output") || message.is_some() {
+                session.record_hook(
+                    HookPoint::PostTool,
+                    &hook.command,
+                    false,
+                    message.as_deref(),
+                );
+            }
+            messages.extend(message);
+        }
+        annotate(output, &messages)
+    }
+
+    /// Shows the plan to the post_plan hooks, each of which may replace it
+    /// with a revised one. Returns the final plan, or the reason when one of
+    /// them rejects it.
+    pub async fn post_plan(
+        &self,
+        session: &mut Session,
+        plan: Response,
+    ) -> Result<Response, String> {
+        let mut plan = plan;
+        for hook in &self.post_plan {
+            let input = json!({
+                "hook": HookPoint::PostPlan,
+                "session_id": session.id,
+                "plan": plan,
+            });
+            let Some(reply) = hook.run(&session.root, &input).await else {
+                continue;
+            };
+            let message = reply.message();
+            if reply.blocked {
+                let reason =
+                    message.unwrap_or_else(|| format!("rejected by hook `{}`", hook.command));
+                session.record_hook(HookPoint::PostPlan, &hook.command, true, Some(&reason));
+                return Err(reason);
+            }
+            if let Some(revised) = reply.fields.get("plan") {
+                // A hook may echo back the file checks it was shown; they
+                // are redone on the revised plan.
+                let mut revised = revised.clone();
+                let tasks = revised.get_mut("tasks").and_then(Value::as_array_mut);
+                for task in tasks.into_iter().flatten().filter_map(Value::as_object_mut) {
+                    task.remove("file_checks");
+                }
+                match Response::parse(&revised.to_string()) {
+                    Ok(revised) => {
+                        println!("🪝 Plan revised by hook `{}`", hook.command);
+                        plan = revised;
+                    }
+                    Err(e) => {
+                        let reason =
+                            format!("hook `{}` returned an invalid plan: {}", hook.command, e);
+                        eprintln!("⚠️ {}", reason);
+                        if hook.on_error == OnError::Block {
+                            session.record_hook(
+                                HookPoint::PostPlan,
+                                &hook.command,
+                                true,
+                                Some(&reason),
+                            );
+                            return Err(reason);
+                        }
+                    }
+                }
+            }
+            if let Some(message) = &message {
+                println!("🪝 {}", message);
+            }
+            if reply.fields.contains_key("plan") || message.is_some() {
+                session.record_hook(
+                    HookPoint::PostPlan,
+                    &hook.command,
+                    false,
+                    message.as_deref(),
+                );
+            }
+        }
+        Ok(plan)
+    }
+
+    /// The hooks in `hooks` that apply to `tool`.
+    fn for_tool<'a>(hooks: &'a [Hook], tool: &str) -> Vec<&'a Hook> {
+        hooks
+            .iter()
+            .filter(|h| h.tool.as_ref().is_none_or(|glob| glob.is_match(tool)))
+            .collect()
+    }
+}
+
+impl Hook {
+    /// Runs the hook in `root` with `input` on stdin. `None` means it failed
+    /// and is ignored; with `on_error = "block"` a failure blocks instead.
+    async fn run(&self, root: &Path, input: &Value) -> Option<Reply> {
+        let result = time::timeout(self.timeout, self.exec(root, input)).await;
+        let failure = match result {
+            Ok(Ok((code, stdout))) if code == Some(0) || code == Some(BLOCK_EXIT_CODE) => {
+                return Some(Reply::new(code == Some(BLOCK_EXIT_CODE), &stdout));
+            }
+            Ok(Ok((code, _))) => format!(
+                "failed ({})",
+                code.map_or("killed".to_string(), |c| format!("exit code {}", c))
+            ),
+            Ok(Err(e)) => format!("could not run: {}", e),
+            Err(_) => format!("timed out after {}s", self.timeout.as_secs()),
+        };
+        match self.on_error {
+            OnError::Ignore => {
+                eprintln!("⚠️ Hook `{}` {}; ignoring it", self.command, failure);
+                None
+            }
+            OnError::Block => {
+                eprintln!("⚠️ Hook `{}` {}; blocking", self.command, failure);
+                let message = format!("hook `{}` {}", self.command, failure);
+                Some(Reply {
+                    blocked: true,
+                    fields: serde_json::Map::from_iter([("message".to_string(), json!(message))]),
+                })
+            }
+        }
+    }
+
+    async fn exec(&self, root: &Path, input: &Value) -> std::io::Result<(Option<i32>, String)> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(&self.command)
+            .current_dir(root)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .kill_on_drop(true)
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            let input = input.to_string();
+            // Written alongside reading stdout, so a hook echoing a large
+            // input cannot fill both pipes. One that ignores its input may
+            // close stdin early.
+            tokio::spawn(async move {
+                let _ = stdin.write_all(input.as_bytes()).await;
+            });
+        }
+        let output = child.wait_with_output().await?;
+        Ok((
+            output.status.code(),
+            String::from_utf8_lossy(&output.stdout).into_owned(),
+        ))
+    }
+}
+
+/// Appends hook messages to a tool output for the model to see.
+pub fn annotate(output: String, messages: &[String]) -> String {
+    messages.iter().fold(output, |mut output, message| {
+        output.push_str("\n[hook] ");
+        output.push_str(message);
+        output
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hook(command: &str, on_error: OnError) -> Hook {
+        Hook {
+            command: command.to_string(),
+            tool: None,
+            timeout: Duration::from_secs(5),
+            on_error,
+        }
+    }
+
+    async fn run(hook: &Hook) -> Option<Reply> {
+        hook.run(Path::new("."), &json!({})).await
+    }
+
+    #[tokio::test]
+    async fn failures_are_ignored_or_block_as_configured() {
+        assert!(run(&hook("exit 1", OnError::Ignore)).await.is_none());
+
+        let reply = run(&hook("exit 1", OnError::Block)).await.unwrap();
+        assert!(reply.blocked);
+        assert!(reply.message().unwrap().contains("exit code 1"));
+
+        let mut slow = hook("sleep 5", OnError::Block);
+        slow.timeout = Duration::from_millis(50);
+        let reply = run(&slow).await.unwrap();
+        assert!(reply.blocked);
+        assert!(reply.message().unwrap().contains("timed out"));
+    }
+
+    #[tokio::test]
+    async fn exit_codes_and_stdout_make_the_reply() {
+        let reply = run(&hook("echo looks fine", OnError::Block)).await.unwrap();
+        assert!(!reply.blocked);
+        assert_eq!(reply.message().as_deref(), Some("looks fine"));
+
+        let reply = run(&hook(
+            r#"echo '{"message": "no"}'; exit 2"#,
+            OnError::Ignore,
+        ))
+        .await
+        .unwrap();
+        assert!(reply.blocked);
+        assert_eq!(reply.message().as_deref(), Some("no"));
+    }
+
+    #[tokio::test]
+    async fn hooks_may_echo_inputs_larger_than_a_pipe() {
+        let input = json!({"output": "x".repeat(256 * 1024)});
+        let reply = hook("cat", OnError::Ignore)
+            .run(Path::new("."), &input)
+            .await
+            .unwrap();
+        assert!(!reply.blocked);
+        assert_eq!(reply.fields.get("output"), input.get("output"));
+    }
+
+    #[test]
+    fn pre_tool_hooks_block_on_error_by_default() {
+        let command = |on_error| HookCommand {
+            command: "true".to_string(),
+            tool: None,
+            timeout_secs: 1,
+            on_error,
+        };
+        let hooks = Hooks::new(&HookSettings {
+            pre_tool: vec![command(None)],
+            post_tool: vec![command(None)],
+            post_plan: vec![command(Some(OnError::Block))],
+        })
+        .unwrap();
+        assert_eq!(hooks.pre_tool[0].on_error, OnError::Block);
+        assert_eq!(hooks.post_tool[0].on_error, OnError::Ignore);
+        assert_eq!(hooks.post_plan[0].on_error, OnError::Block);
+    }
+}
@@ -0,0 +1,171 @@
+use std::{
+    process,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+use tokio_util::sync::CancellationToken;
+
+/// Ctrl-C handling for the whole process.
+///
+/// The first Ctrl-C cancels the running step (a model request or tool run)
+/// and returns control to the prompt. A second Ctrl-C before the step has
+/// unwound, or one while nothing is running, exits. Session logs are written
+/// line by line, so exiting never loses recorded history.
+#[derive(Clone)]
+pub struct Interrupt {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    step: Mutex<Option<CancellationToken>>,
+    session_id: Mutex<Option<String>>,
+    /// Set by [`Interrupt::cancel`] until [`Interrupt::reset`], so a cancel
+    /// that arrives between steps still stops the run.
+    cancelled: AtomicBool,
+}
+
+/// A cancellable unit of work. The step ends when this guard is dropped.
+pub struct Step {
+    token: CancellationToken,
+    inner: Arc<Inner>,
+}
+
+impl Interrupt {
+    /// Replaces the default SIGINT behavior with step cancellation.
+    pub fn install() -> Self {
+        let interrupt = Self::detached();
+        let listener = interrupt.inner.clone();
+        tokio::spawn(async move {
+            while tokio::signal::ctrl_c().await.is_ok() {
+                let running = listener
+                    .step
+                    .lock()
+                    .unwrap()
+                    .clone()
+                    .filter(|t| !t.is_cancelled());
+                match running {
+                    Some(token) => {
+                        token.cancel();
+                        eprintln!("\n⏹ Cancelling… press Ctrl-C again to exit.");
+                    }
+                    None => listener.exit(),
+                }
+            }
+        });
+
+        interrupt
+    }
+
+    /// Step cancellation without signal handling, for runs driven by
+    /// something other than a terminal such as `viktor serve`.
+    pub fn detached() -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                step: Mutex::new(None),
+                session_id: Mutex::new(None),
+                cancelled: AtomicBool::new(false),
+            }),
+        }
+    }
+
+    /// Cancels the running step, if any, and every step started until
+    /// [`Interrupt::reset`].
+    pub fn cancel(&self) {
+        let step = self.inner.step.lock().unwrap();
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+        if let Some(token) = step.as_ref() {
+            token.cancel();
+        }
+    }
+
+    /// Whether [`Interrupt::cancel`] was called since the last reset.
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Lets steps run again after a cancel, e.g. for the next request.
+    pub fn reset(&self) {
+        self.inner.cancelled.store(false, Ordering::SeqCst);
+    }
+
+    /// Session to point at in the exit message.
+    pub fn set_session(&self, id: &str) {
+        *self.inner.session_id.lock().unwrap() = Some(id.to_string());
+    }
+
+    /// Starts a new cancellable step; it starts out cancelled after a
+    /// [`Interrupt::cancel`] that has not been reset.
+    pub fn step(&self) -> Step {
+        let token = CancellationToken::new();
+        let mut step = self.inner.step.lock().unwrap();
+        if self.is_cancelled() {
+            token.cancel();
+        }
+        *step = Some(token.clone());
+        drop(step);
+        Step {
+            token,
+            inner: self.inner.clone(),
+        }
+    }
+
+    /// Exits right away, e.g. after a second Ctrl-C at the prompt.
+    pub fn exit(&self) -> ! {
+        self.inner.exit()
+    }
+}
+
+impl Inner {
+    fn exit(&self) -> ! {
+        println!("\nVery well. Concluding session.");
+        if let Some(id) = self.session_id.lock().unwrap().as_deref() {
+            println!("Session saved. Resume later with: viktor resume {}", id);
+        }
+        process::exit(130);
+    }
+}
+
+impl Step {
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+}
+
+impl Drop for Step {
+    fn drop(&mut self) {
+        // Steps never overlap, so the running step is always this one.
+        *self.inner.step.lock().unwrap() = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_cancel_between_steps_cancels_the_next_one() {
+        let interrupt = Interrupt::detached();
+        assert!(!interrupt.step().is_cancelled());
+
+        interrupt.cancel();
+        assert!(interrupt.is_cancelled());
+        assert!(interrupt.step().is_cancelled());
+
+        interrupt.reset();
+        assert!(!interrupt.step().is_cancelled());
+    }
+
+    #[test]
+    fn a_cancel_stops_the_running_step() {
+        let interrupt = Interrupt::detached();
+        let step = interrupt.step();
+        interrupt.cancel();
+        assert!(step.is_cancelled());
+    }
+}
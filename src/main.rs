@@ -1,188 +1,123 @@
 mod agents;
+mod cli;
 mod config;
+mod context;
+mod final_output;
+mod hooks;
+mod interrupt;
+mod redact;
+mod repl;
 mod response;
-mod system_prompt;
+mod schema;
+mod server;
+mod session;
 mod tool_handling;
 
-use agents::researcher::get_initial_messages;
-use config::init::ViktorInit;
+use agents::researcher::{get_initial_messages, research, Research};
+use cli::{parse_args, Command};
+use config::{init::ViktorInit, settings::load_settings};
+use final_output::produce_plan;
+use interrupt::Interrupt;
 
-use ollama::{
-    types::{ChatMessage, ChatRequest, MessageRole},
-    OllamaClient,
-};
-use response::{res_format, Response};
-use serde_json::{json, Value};
-use std::{
-    env,
-    error::Error,
-    io::{self, Write},
-    process,
-};
-use tool_handling::handle_tool_calls;
-use tools::{crawler::Crawler, Tool};
+use ollama::OllamaClient;
+use session::{Session, Transcript};
+use std::{env, error::Error, fs, process};
+use tool_handling::{connect_mcp, Served};
 
 const MODEL: &str = "qwen3:latest";
 
-fn get_user_prompt() -> String {
-    let args: Vec<String> = env::args().skip(1).collect();
-
-    if args.first().map(|s| s.as_str()) == Some("init") {
-        let init = ViktorInit::new().expect("Unable to init");
-        init.execute().expect("Unable to init")
-    }
-
-    if args.is_empty() {
-        eprintln!("Sir, a prompt is required to begin the conversation.");
-        eprintln!("Usage: cargo run -- \"<your initial question>\"");
-        eprintln!("       cargo run -- init");
-        process::exit(1);
-    }
-    args.join(" ")
-}
-
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
     let client = OllamaClient::new("http://127.0.0.1:11434")?;
 
-    let initial_prompt = get_user_prompt();
-
-    let mut messages = get_initial_messages(initial_prompt);
-    const MAX_TOOL_CALL_LOOPS: usize = 10;
-    let mut current_tool_loop = 0;
-    let mut final_output_requested = false;
+    let settings = load_settings()?;
 
-    while current_tool_loop < MAX_TOOL_CALL_LOOPS && !final_output_requested {
-        current_tool_loop += 1;
-        println!(
-            "\n=== Reasoning Step {}/{} ===",
-            current_tool_loop, MAX_TOOL_CALL_LOOPS
-        );
-
-        let chat_req = ChatRequest {
-            model: MODEL.to_string().clone(),
-            messages: messages.clone(),
-            tools: Some(Crawler::get_tool_defs()),
-            stream: false,
-            format: None,
-            think: false,
-            options: None,
-            keep_alive: None,
-        };
-
-        let res = client.chat(&chat_req).await?;
-        let assistant_msg = res.message.clone();
-        messages.push(assistant_msg.clone());
-
-        if let Some(tool_calls) = assistant_msg.tool_calls {
-            if assistant_msg.content.contains("<FINAL>") {
-                println!(
-                    "\n🧠 Assistant (reasoning complete, preparing final output): {}",
-                    assistant_msg.content
-                );
-                final_output_requested = true;
-                break;
-            }
-            for call in tool_calls {
-                call.log();
-                let name = &call.function.name;
-                let prefix: Vec<&str> = name.split('.').collect();
-                let prefix = prefix.first().expect("Bad tool call name format");
-
-                let tool_output = match *prefix {
-                    "crawler" => Crawler::handle_tool_call(call).await,
-                    _ => {
-                        eprintln!("Error: Unexpected tool call prefix: {}", prefix);
-                        json!({"error": format!("Unexpected tool call prefix: {}", prefix)})
-                            .to_string()
-                    }
-                };
-
-                messages.push(ChatMessage {
-                    role: MessageRole::Tool,
-                    content: tool_output,
-                    images: None,
-                    tool_calls: None,
-                });
+    let args = match parse_args() {
+        Command::Init => {
+            let init = ViktorInit::new().expect("Unable to init");
+            init.execute().expect("Unable to init");
+            return Ok(());
+        }
+        Command::SessionsList => return session::print_list(),
+        Command::SessionsShow(id) => return session::print_show(&id),
+        Command::Resume(id) => {
+            let mut session = Session::resume(&id, &settings)?;
+            let interrupt = Interrupt::install();
+            interrupt.set_session(&session.id);
+            println!(
+                "📂 Resuming session {} ({} messages, model {})",
+                session.id,
+                session.messages.len(),
+                session.model
+            );
+            if let Some(plan) = session::load(&id).ok().and_then(|e| session::last_plan(&e)) {
+                println!("\n{plan}");
             }
+            session.mcp = connect_mcp(&session.root, &settings.mcp).await;
+            return repl::run(&client, &mut session, &interrupt).await;
         }
-    }
-
-    if final_output_requested || current_tool_loop >= MAX_TOOL_CALL_LOOPS {
-        println!("\n=== Requesting Final Structured Output ===");
-        messages.push(ChatMessage {
-            role: MessageRole::User,
-            content: "Based on all the information gathered and your reasoning, please provide the complete task breakdown in the precise JSON format you were instructed to use. Ensure the output is a valid JSON object matching the updated `tasks` schema with objective, affected_files, changes fields.".to_string(),
-            images: None,
-            tool_calls: None,
-        });
-
-        let chat_req_final = ChatRequest {
-            model: MODEL.to_string().clone(),
-            messages: messages.clone(),
-            tools: None,
-            stream: false,
-            format: Some(res_format().clone()),
-            think: false,
-            options: None,
-            keep_alive: None,
-        };
-
-        match client.chat(&chat_req_final).await {
-            Ok(res_final) => {
-                let final_message_content = res_final.message.content;
-                match serde_json::from_str::<Value>(&final_message_content) {
-                    Ok(parsed_json) => {
-                        let res: Response =
-                            serde_json::from_value(parsed_json).expect("Bad response structure");
-                        println!("{res}");
-                    }
-                    Err(e) => {
-                        eprintln!("\n❌ Error parsing final JSON output: {}", e);
-                        eprintln!("Raw output:\n{}", final_message_content);
-                    }
+        Command::Export(args) => {
+            let rendered = Transcript::load(&args.id)?.render(args.format)?;
+            match args.output {
+                Some(path) => {
+                    fs::write(&path, rendered)?;
+                    println!("✓ Exported session {} to {}", args.id, path.display());
                 }
+                None => print!("{}", rendered),
             }
-            Err(e) => {
-                eprintln!("\n❌ Error during final output request: {}", e);
-            }
+            return Ok(());
         }
-    } else {
-        println!("\nExiting without final formatted output (tool loop limit reached or unexpected state).");
+        Command::McpServe => {
+            // stdout carries the protocol from here on.
+            tools::mcp::serve(&Served::new(&env::current_dir()?, &settings)?).await?;
+            return Ok(());
+        }
+        Command::Serve(args) => return server::serve(client, MODEL, args).await,
+        Command::Plan(args) => args,
+    };
+
+    let root = env::current_dir()?;
+    let mut session = Session::create(
+        &root,
+        MODEL,
+        &settings,
+        get_initial_messages(&root, args.prompt),
+    )?;
+    println!("📁 Session {}", session.id);
+    session.mcp = connect_mcp(&root, &settings.mcp).await;
+    let interrupt = Interrupt::install();
+    interrupt.set_session(&session.id);
+
+    if let Research::Cancelled = research(&client, &mut session, &interrupt).await? {
+        println!(
+            "\n⏹ Research cancelled. Use /plan to get a task breakdown from what was gathered."
+        );
+        return repl::run(&client, &mut session, &interrupt).await;
     }
 
-    println!("\n--- Task completed. Entering interactive mode ---");
-
-    print!("\n> ");
-    loop {
-        io::stdout().flush()?;
-
-        let mut user_input = String::new();
-        io::stdin().read_line(&mut user_input)?;
-        let trimmed_input = user_input.trim();
-
-        if trimmed_input.is_empty()
-            || trimmed_input.eq_ignore_ascii_case("exit")
-            || trimmed_input.eq_ignore_ascii_case("quit")
-        {
-            println!("Very well. Concluding session.");
-            break;
+    println!("\n=== Requesting Final Structured Output ===");
+    let step = interrupt.step();
+    let res = match produce_plan(&client, &mut session, step.token()).await {
+        Ok(res) => res,
+        Err(_) if step.is_cancelled() => {
+            println!("\n⏹ Cancelled before the task breakdown was ready.");
+            return repl::run(&client, &mut session, &interrupt).await;
         }
-
-        messages.push(ChatMessage {
-            role: MessageRole::User,
-            content: trimmed_input.to_string(),
-            images: None,
-            tool_calls: None,
-        });
-
-        match handle_tool_calls(&mut messages, &client, MODEL).await {
-            Ok(_) => {} // Tool calls handled successfully
-            Err(e) => {
-                eprintln!("\n❌ Error during interactive chat: {}", e);
-            }
+        Err(e) => {
+            eprintln!("\n❌ Unable to produce a task breakdown: {}", e);
+            process::exit(1);
         }
+    };
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&res)?);
+    } else {
+        println!("{res}");
+    }
+    if let Some(format) = args.graph {
+        println!("\n=== Task Graph ===\n{}", res.render_graph(format));
     }
 
-    Ok(())
+    println!("\n--- Task completed. ---");
+    repl::run(&client, &mut session, &interrupt).await
 }
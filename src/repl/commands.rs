@@ -0,0 +1,302 @@
+use ollama::{
+    types::{ChatMessage, MessageRole},
+    OllamaClient,
+};
+use std::{error::Error, fs, path::PathBuf};
+use tokio_util::sync::CancellationToken;
+use tools::crawler::Crawler;
+
+use crate::{
+    config::guidelines::print_guidelines,
+    context::{estimate_messages, estimate_tools},
+    final_output::produce_plan,
+    redact::{describe, Redactions},
+    session::{sessions_dir, ExportFormat, Session, Transcript},
+    tool_handling::tool_defs,
+};
+
+/// Every slash command with a one-line description, in `/help` order.
+pub const COMMANDS: &[(&str, &str)] = &[
+    ("/plan", "Re-emit the structured task breakdown"),
+    ("/tools", "List the tools available to the model"),
+    (
+        "/model <name>",
+        "Switch to another model for the rest of the session",
+    ),
+    ("/reset", "Drop everything after the original request"),
+    ("/save [path]", "Write a Markdown transcript of the session"),
+    ("/undo", "Drop the last user turn and everything after it"),
+    (
+        "/context",
+        "Show estimated token usage against the context budget",
+    ),
+    ("/guidelines", "Print .viktor/guidelines.md"),
+    ("/read <path>", "Add a file's contents to the conversation"),
+    ("/help", "Show this list"),
+    ("/exit", "End the session"),
+];
+
+pub enum SlashCommand {
+    Plan,
+    Tools,
+    Model(String),
+    Reset,
+    Save(Option<PathBuf>),
+    Undo,
+    Context,
+    Guidelines,
+    Read(String),
+    Help,
+    Exit,
+}
+
+impl SlashCommand {
+    /// Parses a line starting with `/`.
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let line = line.trim();
+        let (name, arg) = match line.split_once(char::is_whitespace) {
+            Some((name, arg)) => (name, arg.trim()),
+            None => (line, ""),
+        };
+        let required = |what: &str| {
+            if arg.is_empty() {
+                Err(format!("{} needs {}", name, what))
+            } else {
+                Ok(arg.to_string())
+            }
+        };
+
+        match name {
+            "/plan" => Ok(SlashCommand::Plan),
+            "/tools" => Ok(SlashCommand::Tools),
+            "/model" => required("a model name").map(SlashCommand::Model),
+            "/reset" => Ok(SlashCommand::Reset),
+            "/save" => Ok(SlashCommand::Save(
+                (!arg.is_empty()).then(|| PathBuf::from(arg)),
+            )),
+            "/undo" => Ok(SlashCommand::Undo),
+            "/context" => Ok(SlashCommand::Context),
+            "/guidelines" => Ok(SlashCommand::Guidelines),
+            "/read" => required("a file path").map(SlashCommand::Read),
+            "/help" | "/?" => Ok(SlashCommand::Help),
+            "/exit" | "/quit" => Ok(SlashCommand::Exit),
+            other => Err(format!("Unknown command {}. Type /help for a list.", other)),
+        }
+    }
+}
+
+/// Runs a slash command. Returns `false` when the session should end.
+pub async fn execute(
+    command: SlashCommand,
+    client: &OllamaClient,
+    session: &mut Session,
+    cancel: &CancellationToken,
+) -> Result<bool, Box<dyn Error>> {
+    match command {
+        SlashCommand::Plan => {
+            let res = produce_plan(client, session, cancel).await?;
+            println!("{res}");
+        }
+        SlashCommand::Tools => {
+            for tool in tool_defs(session) {
+                println!("  {:<32} {}", tool.function.name, tool.function.description);
+            }
+        }
+        SlashCommand::Model(model) => {
+            session.set_model(&model);
+            println!("✓ Using {} from now on", model);
+        }
+        SlashCommand::Reset => {
+            let keep = goal_index(session).map_or(session.messages.len(), |i| i + 1);
+            session.rewind(keep);
+            println!("✓ Conversation reset to the original request");
+        }
+        SlashCommand::Save(path) => {
+            let path = match path {
+                Some(path) => path,
+                None => sessions_dir()?.join(format!("{}.md", session.id)),
+            };
+            let rendered = Transcript::load(&session.id)?.render(ExportFormat::Markdown)?;
+            fs::write(&path, rendered)?;
+            println!("✓ Saved transcript to {}", path.display());
+        }
+        SlashCommand::Undo => {
+            let goal = goal_index(session);
+            let last_turn = session
+                .messages
+                .iter()
+                .rposition(|m| matches!(m.role, MessageRole::User))
+                .filter(|&i| Some(i) != goal);
+            match last_turn {
+                Some(i) => {
+                    let dropped = session.messages.len() - i;
+                    session.rewind(i);
+                    println!("✓ Dropped the last turn ({} messages)", dropped);
+                }
+                None => println!("Nothing to undo."),
+            }
+        }
+        SlashCommand::Context => print_context(session),
+        SlashCommand::Guidelines => print_guidelines(&session.root)?,
+        SlashCommand::Read(path) => {
+            let crawler = Crawler::new(&session.root)
+                .allow_hidden(session.tools.crawler.allow_hidden)
+                .sensitive(session.redactor.sensitive_files())
+                .including_hidden(true);
+            if !crawler.root_path().join(&path).is_file() {
+                return Err(format!(
+                    "{} is not a file under {}",
+                    path,
+                    crawler.root_path().display()
+                )
+                .into());
+            }
+            if !crawler.allows(&path) {
+                return Err(format!(
+                    "{} is excluded by .gitignore, .viktorignore or the hidden-file rules",
+                    path
+                )
+                .into());
+            }
+            if crawler.is_sensitive(&path) {
+                return Err(format!(
+                    "{} is a sensitive file and is never sent to the model",
+                    path
+                )
+                .into());
+            }
+            let content = crawler.read_file_contents(&path).await;
+            let lines = content.lines().count();
+            let mut counts = Redactions::new();
+            let content = session.redactor.redact_text(&content, &mut counts);
+            if !counts.is_empty() {
+                println!("🔒 Redacted {} from {}", describe(&counts), path);
+            }
+            session.push(ChatMessage {
+                role: MessageRole::User,
+                content: format!(
+                    "Here are the contents of `{}`:\n```\n{}\n```",
+                    path, content
+                ),
+                thinking: None,
+                images: None,
+                tool_calls: None,
+            });
+            println!("✓ Added {} ({} lines) to the conversation", path, lines);
+        }
+        SlashCommand::Help => {
+            println!("Commands:");
+            for (usage, description) in COMMANDS {
+                println!("  {:<16} {}", usage, description);
+            }
+            println!("Anything else is sent to the model.");
+        }
+        SlashCommand::Exit => return Ok(false),
+    }
+    Ok(true)
+}
+
+/// Index of the user's original request, which `/reset` and `/undo` keep.
+fn goal_index(session: &Session) -> Option<usize> {
+    session
+        .messages
+        .iter()
+        .position(|m| matches!(m.role, MessageRole::User))
+}
+
+fn print_context(session: &Session) {
+    let budget = &session.context;
+    let messages = estimate_messages(&session.messages);
+    let tools = estimate_tools(&tool_defs(session));
+    let used = messages + tools;
+
+    println!(
+        "Context: ~{} tokens used, compaction at {} of num_ctx {} ({:.0}% used)",
+        used,
+        budget.limit(),
+        budget.num_ctx,
+        used as f32 * 100.0 / budget.num_ctx as f32
+    );
+
+    let count = |role: fn(&MessageRole) -> bool| {
+        let picked = session
+            .messages
+            .iter()
+            .filter(|m| role(&m.role))
+            .cloned()
+            .collect::<Vec<_>>();
+        (picked.len(), estimate_messages(&picked))
+    };
+    for (label, (n, tokens)) in [
+        ("system", count(|r| matches!(r, MessageRole::System))),
+        ("user", count(|r| matches!(r, MessageRole::User))),
+        ("assistant", count(|r| matches!(r, MessageRole::Assistant))),
+        ("tool", count(|r| matches!(r, MessageRole::Tool))),
+    ] {
+        println!("  {:<10} {:>4} message(s)  ~{} tokens", label, n, tokens);
+    }
+    println!(
+        "  {:<10} {:>4} definition(s) ~{} tokens",
+        "tools",
+        tool_defs(session).len(),
+        tools
+    );
+    if let Some(actual) = session.last_prompt_tokens {
+        println!("Last prompt as counted by Ollama: {} tokens", actual);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_take_their_arguments() {
+        assert!(matches!(
+            SlashCommand::parse("/model  qwen3:8b "),
+            Ok(SlashCommand::Model(m)) if m == "qwen3:8b"
+        ));
+        assert!(matches!(
+            SlashCommand::parse("/read src/main.rs"),
+            Ok(SlashCommand::Read(p)) if p == "src/main.rs"
+        ));
+        assert!(matches!(
+            SlashCommand::parse("/save out/t.md"),
+            Ok(SlashCommand::Save(Some(p))) if p.as_path() == std::path::Path::new("out/t.md")
+        ));
+        assert!(matches!(
+            SlashCommand::parse("/save"),
+            Ok(SlashCommand::Save(None))
+        ));
+        assert!(matches!(SlashCommand::parse("/?"), Ok(SlashCommand::Help)));
+        assert!(matches!(
+            SlashCommand::parse("/quit"),
+            Ok(SlashCommand::Exit)
+        ));
+    }
+
+    #[test]
+    fn missing_arguments_and_unknown_commands_are_errors() {
+        assert_eq!(
+            SlashCommand::parse("/model").err().unwrap(),
+            "/model needs a model name"
+        );
+        assert_eq!(
+            SlashCommand::parse("/read   ").err().unwrap(),
+            "/read needs a file path"
+        );
+        assert_eq!(
+            SlashCommand::parse("/frobnicate x").err().unwrap(),
+            "Unknown command /frobnicate. Type /help for a list."
+        );
+    }
+
+    #[test]
+    fn every_listed_command_parses() {
+        for (usage, _) in COMMANDS {
+            let line = usage.replace("<name>", "m").replace("<path>", "p");
+            let line = line.replace(" [path]", "");
+            assert!(SlashCommand::parse(&line).is_ok(), "{line}");
+        }
+    }
+}
@@ -0,0 +1,210 @@
+use rustyline::{
+    completion::{Completer, Pair},
+    error::ReadlineError,
+    highlight::Highlighter,
+    hint::Hinter,
+    history::DefaultHistory,
+    validate::{ValidationContext, ValidationResult, Validator},
+    Context, Editor, Helper,
+};
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use super::commands::COMMANDS;
+
+/// Opens and closes a multi-line block, e.g. for pasted code.
+const BLOCK_DELIMITER: &str = "\"\"\"";
+
+/// What the user did at the prompt.
+pub enum Input {
+    Line(String),
+    /// Ctrl-C: the current line was discarded.
+    Interrupted,
+    /// Ctrl-D or closed stdin.
+    Eof,
+}
+
+/// Prompt with history in the repository's `.viktor/history`, tab completion for slash
+/// commands and repository paths, and `"""`-delimited multi-line input.
+pub struct LineEditor {
+    editor: Editor<ReplHelper, DefaultHistory>,
+    history_path: PathBuf,
+}
+
+impl LineEditor {
+    /// `paths` are the `root`-relative repository paths offered for completion.
+    pub fn new(root: &Path, paths: Vec<String>) -> Result<Self, Box<dyn Error>> {
+        let mut editor = Editor::new()?;
+        editor.set_helper(Some(ReplHelper { paths }));
+
+        let history_path = root.join(".viktor").join("history");
+        if history_path.exists() {
+            // A corrupt history file should not keep the REPL from starting.
+            let _ = editor.load_history(&history_path);
+        }
+
+        Ok(Self {
+            editor,
+            history_path,
+        })
+    }
+
+    pub fn read(&mut self, prompt: &str) -> Result<Input, ReadlineError> {
+        let line = match self.editor.readline(prompt) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => return Ok(Input::Interrupted),
+            Err(ReadlineError::Eof) => return Ok(Input::Eof),
+            Err(e) => return Err(e),
+        };
+
+        if !line.trim().is_empty() {
+            self.editor.add_history_entry(line.as_str())?;
+            self.save_history();
+        }
+        Ok(Input::Line(unwrap_block(&line)))
+    }
+
+    fn save_history(&mut self) {
+        let path = &self.history_path;
+        if let Some(dir) = path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        if let Err(e) = self.editor.save_history(path) {
+            eprintln!("⚠️ Unable to save history to {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// Strips the `"""` delimiters and `\` line continuations from a finished input.
+fn unwrap_block(input: &str) -> String {
+    let trimmed = input.trim();
+    if let Some(body) = trimmed.strip_prefix(BLOCK_DELIMITER) {
+        let body = body.strip_suffix(BLOCK_DELIMITER).unwrap_or(body);
+        return body.trim_matches('\n').to_string();
+    }
+    input.replace("\\\n", "\n")
+}
+
+struct ReplHelper {
+    paths: Vec<String>,
+}
+
+impl Completer for ReplHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        Ok(self.candidates(&line[..pos]))
+    }
+}
+
+impl ReplHelper {
+    /// Where the word before the cursor starts, and what could replace it.
+    fn candidates(&self, before: &str) -> (usize, Vec<Pair>) {
+        let start = before
+            .rfind(|c: char| c.is_whitespace() || c == '`')
+            .map_or(0, |i| i + 1);
+        let word = &before[start..];
+
+        // Slash commands are only completed as the first word.
+        if start == 0 && word.starts_with('/') {
+            let candidates = COMMANDS
+                .iter()
+                .filter_map(|(usage, _)| usage.split_whitespace().next())
+                .filter(|name| name.starts_with(word))
+                .map(|name| Pair {
+                    display: name.to_string(),
+                    replacement: format!("{} ", name),
+                })
+                .collect();
+            return (start, candidates);
+        }
+        if word.is_empty() {
+            return (start, Vec::new());
+        }
+
+        let candidates = self
+            .paths
+            .iter()
+            .filter(|p| p.starts_with(word))
+            .take(100)
+            .map(|p| Pair {
+                display: p.clone(),
+                replacement: p.clone(),
+            })
+            .collect();
+        (start, candidates)
+    }
+}
+
+impl Validator for ReplHelper {
+    /// Keeps reading lines while a `"""` block is open or a line ends with `\`.
+    fn validate(&self, ctx: &mut ValidationContext<'_>) -> rustyline::Result<ValidationResult> {
+        let input = ctx.input();
+        let trimmed = input.trim();
+        let open_block =
+            trimmed.starts_with(BLOCK_DELIMITER) && trimmed.matches(BLOCK_DELIMITER).count() < 2;
+        if open_block || input.ends_with('\\') {
+            Ok(ValidationResult::Incomplete)
+        } else {
+            Ok(ValidationResult::Valid(None))
+        }
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper {}
+
+impl Helper for ReplHelper {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn complete(before: &str) -> (usize, Vec<String>) {
+        let helper = ReplHelper {
+            paths: ["src/main.rs", "src/repl/mod.rs", "README.md"]
+                .map(String::from)
+                .to_vec(),
+        };
+        let (start, pairs) = helper.candidates(before);
+        (start, pairs.into_iter().map(|p| p.replacement).collect())
+    }
+
+    #[test]
+    fn blocks_lose_their_delimiters() {
+        assert_eq!(unwrap_block("\"\"\"\nfn main() {}\n\"\"\""), "fn main() {}");
+        assert_eq!(unwrap_block("  \"\"\"\nunclosed\n"), "unclosed");
+        assert_eq!(unwrap_block("first \\\nsecond"), "first \nsecond");
+        assert_eq!(unwrap_block("plain"), "plain");
+    }
+
+    #[test]
+    fn slash_commands_complete_only_as_the_first_word() {
+        assert_eq!(
+            complete("/re"),
+            (0, vec!["/reset ".into(), "/read ".into()])
+        );
+        assert_eq!(complete("explain /re"), (8, Vec::new()));
+    }
+
+    #[test]
+    fn paths_complete_after_spaces_and_backticks() {
+        assert_eq!(
+            complete("look at `src/"),
+            (9, vec!["src/main.rs".into(), "src/repl/mod.rs".into()])
+        );
+        assert_eq!(complete("/read READ"), (6, vec!["README.md".into()]));
+        assert_eq!(complete("look at "), (8, Vec::new()));
+    }
+}
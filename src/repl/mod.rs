@@ -0,0 +1,129 @@
+use ollama::{
+    types::{ChatMessage, MessageRole},
+    OllamaClient,
+};
+use std::{
+    error::Error,
+    io::{self, Write},
+};
+use tools::crawler::Crawler;
+
+use crate::{interrupt::Interrupt, session::Session, tool_handling::handle_tool_calls};
+
+mod commands;
+mod editor;
+
+use commands::SlashCommand;
+use editor::{Input, LineEditor};
+
+/// Prints `question` and reads one trimmed line from stdin.
+pub fn ask(question: &str) -> io::Result<String> {
+    print!("{}", question);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(answer.trim().to_string())
+}
+
+/// Asks a yes/no question on stdin; anything but "y"/"yes" counts as no.
+pub fn confirm(question: &str) -> io::Result<bool> {
+    let answer = ask(&format!("{} [y/N] ", question))?;
+    Ok(answer.eq_ignore_ascii_case("y") || answer.eq_ignore_ascii_case("yes"))
+}
+
+/// Interactive follow-up loop on top of an existing session.
+///
+/// Ctrl-C during a model request or tool run cancels that turn and keeps the
+/// history before it; Ctrl-C twice in a row at the prompt ends the session.
+pub async fn run(
+    client: &OllamaClient,
+    session: &mut Session,
+    interrupt: &Interrupt,
+) -> Result<(), Box<dyn Error>> {
+    println!(
+        "\n--- Entering interactive mode (session {}) ---",
+        session.id
+    );
+    println!("Type /help for commands, \"\"\" to start a multi-line block.");
+
+    let crawler = Crawler::new(&session.root);
+    let paths = tokio::task::spawn_blocking(move || crawler.list_files())
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|p| p.to_string_lossy().into_owned())
+        .collect();
+    let mut editor = LineEditor::new(&session.root, paths)?;
+    let mut interrupted = false;
+
+    loop {
+        println!();
+        let user_input = match editor.read("> ")? {
+            Input::Line(line) => line,
+            Input::Interrupted if interrupted => interrupt.exit(),
+            Input::Interrupted => {
+                interrupted = true;
+                println!("(Ctrl-C again to exit, or /exit)");
+                continue;
+            }
+            Input::Eof => break,
+        };
+        interrupted = false;
+        let trimmed_input = user_input.trim();
+
+        if trimmed_input.is_empty() {
+            continue;
+        }
+        if trimmed_input.eq_ignore_ascii_case("exit") || trimmed_input.eq_ignore_ascii_case("quit")
+        {
+            break;
+        }
+
+        let before = session.messages.len();
+        if trimmed_input.starts_with('/') {
+            let command = match SlashCommand::parse(trimmed_input) {
+                Ok(command) => command,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    continue;
+                }
+            };
+            let step = interrupt.step();
+            match commands::execute(command, client, session, step.token()).await {
+                Ok(true) => continue,
+                Ok(false) => break,
+                Err(_) if step.is_cancelled() => {
+                    session.rewind(before);
+                    println!("\n⏹ Cancelled.");
+                    continue;
+                }
+                Err(e) => {
+                    eprintln!("\n❌ {}", e);
+                    continue;
+                }
+            }
+        }
+
+        session.push(ChatMessage {
+            role: MessageRole::User,
+            content: trimmed_input.to_string(),
+            thinking: None,
+            images: None,
+            tool_calls: None,
+        });
+
+        let step = interrupt.step();
+        let result = handle_tool_calls(session, client, step.token()).await;
+        if step.is_cancelled() {
+            // Keep the question, drop the half-finished answer.
+            session.rewind(before + 1);
+            println!("\n⏹ Cancelled.");
+        } else if let Err(e) = result {
+            eprintln!("\n❌ Error during interactive chat: {}", e);
+        }
+    }
+
+    println!("Very well. Concluding session.");
+    println!("Resume later with: viktor resume {}", session.id);
+    Ok(())
+}
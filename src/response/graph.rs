@@ -0,0 +1,301 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write,
+    str::FromStr,
+};
+
+use super::Response;
+
+/// Output formats for the task dependency graph.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphFormat {
+    Dot,
+    Mermaid,
+}
+
+impl FromStr for GraphFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "dot" | "graphviz" => Ok(GraphFormat::Dot),
+            "mermaid" => Ok(GraphFormat::Mermaid),
+            other => Err(format!(
+                "unknown graph format '{}' (expected dot or mermaid)",
+                other
+            )),
+        }
+    }
+}
+
+impl Response {
+    /// Checks that task ids are unique and that `depends_on` forms a DAG
+    /// over existing tasks.
+    pub fn validate(&self) -> Result<(), String> {
+        let mut seen = HashSet::new();
+        for task in &self.tasks {
+            if task.id.trim().is_empty() {
+                return Err(format!("task '{}' has an empty id", task.objective));
+            }
+            if !seen.insert(task.id.as_str()) {
+                return Err(format!("duplicate task id '{}'", task.id));
+            }
+        }
+
+        for task in &self.tasks {
+            for dep in &task.depends_on {
+                if dep == &task.id {
+                    return Err(format!("task '{}' depends on itself", task.id));
+                }
+                if !seen.contains(dep.as_str()) {
+                    return Err(format!(
+                        "task '{}' depends on unknown task '{}'",
+                        task.id, dep
+                    ));
+                }
+            }
+        }
+
+        let order = self.topological_order();
+        if order.len() != self.tasks.len() {
+            let placed: HashSet<usize> = order.into_iter().collect();
+            let cycle = self
+                .tasks
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| !placed.contains(i))
+                .map(|(_, t)| t.id.as_str())
+                .collect::<Vec<_>>();
+            return Err(format!(
+                "dependency cycle between tasks: {}",
+                cycle.join(", ")
+            ));
+        }
+        Ok(())
+    }
+
+    /// Task indices in dependency order. Ties keep the model's original order.
+    ///
+    /// Tasks caught in a cycle or depending on unknown ids are left out, so
+    /// callers should `validate` first.
+    pub fn topological_order(&self) -> Vec<usize> {
+        let index = self.index_by_id();
+        let mut remaining: Vec<usize> = self
+            .tasks
+            .iter()
+            .map(|t| {
+                t.depends_on
+                    .iter()
+                    .filter(|d| index.contains_key(d.as_str()))
+                    .count()
+            })
+            .collect();
+
+        let mut order = Vec::with_capacity(self.tasks.len());
+        let mut done = vec![false; self.tasks.len()];
+        loop {
+            let next = (0..self.tasks.len()).find(|&i| !done[i] && remaining[i] == 0);
+            let Some(i) = next else { break };
+            done[i] = true;
+            order.push(i);
+            let id = &self.tasks[i].id;
+            for (j, task) in self.tasks.iter().enumerate() {
+                remaining[j] -= task.depends_on.iter().filter(|d| *d == id).count();
+            }
+        }
+        order
+    }
+
+    /// Groups tasks into stages: every task in a stage only depends on tasks
+    /// from earlier stages, so a whole stage can be worked on in parallel.
+    pub fn stages(&self) -> Vec<Vec<usize>> {
+        let index = self.index_by_id();
+        let mut level = vec![0usize; self.tasks.len()];
+        for i in self.topological_order() {
+            level[i] = self.tasks[i]
+                .depends_on
+                .iter()
+                .filter_map(|d| index.get(d.as_str()))
+                .map(|&d| level[d] + 1)
+                .max()
+                .unwrap_or(0);
+        }
+
+        let mut stages: Vec<Vec<usize>> = Vec::new();
+        for i in self.topological_order() {
+            if stages.len() <= level[i] {
+                stages.resize_with(level[i] + 1, Vec::new);
+            }
+            stages[level[i]].push(i);
+        }
+        stages
+    }
+
+    /// Renders the dependency graph in the requested format.
+    pub fn render_graph(&self, format: GraphFormat) -> String {
+        match format {
+            GraphFormat::Dot => self.to_dot(),
+            GraphFormat::Mermaid => self.to_mermaid(),
+        }
+    }
+
+    /// Graphviz DOT; edges point from a dependency to the task that needs it.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph tasks {\n    rankdir=LR;\n    node [shape=box];\n");
+        for task in &self.tasks {
+            let _ = writeln!(
+                out,
+                "    \"{}\" [label=\"{}\\n{}\"];",
+                escape_dot(&task.id),
+                escape_dot(&task.id),
+                escape_dot(&task.objective)
+            );
+        }
+        for task in &self.tasks {
+            for dep in &task.depends_on {
+                let _ = writeln!(
+                    out,
+                    "    \"{}\" -> \"{}\";",
+                    escape_dot(dep),
+                    escape_dot(&task.id)
+                );
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// Mermaid flowchart; edges point from a dependency to the task that needs it.
+    pub fn to_mermaid(&self) -> String {
+        let index = self.index_by_id();
+        let node = |i: usize| format!("t{}", i);
+
+        let mut out = String::from("flowchart LR\n");
+        for (i, task) in self.tasks.iter().enumerate() {
+            let _ = writeln!(
+                out,
+                "    {}[\"{}: {}\"]",
+                node(i),
+                escape_mermaid(&task.id),
+                escape_mermaid(&task.objective)
+            );
+        }
+        for (i, task) in self.tasks.iter().enumerate() {
+            for dep in &task.depends_on {
+                if let Some(&d) = index.get(dep.as_str()) {
+                    let _ = writeln!(out, "    {} --> {}", node(d), node(i));
+                }
+            }
+        }
+        out
+    }
+
+    fn index_by_id(&self) -> HashMap<&str, usize> {
+        self.tasks
+            .iter()
+            .enumerate()
+            .map(|(i, t)| (t.id.as_str(), i))
+            .collect()
+    }
+}
+
+fn escape_dot(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Replaces what would end a quoted Mermaid label or be read as markup with
+/// entity codes, and line breaks with `<br>`. `#` goes first so the codes
+/// themselves are not escaped again.
+fn escape_mermaid(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '#' => out.push_str("#35;"),
+            '"' => out.push_str("#quot;"),
+            '[' => out.push_str("#91;"),
+            ']' => out.push_str("#93;"),
+            '|' => out.push_str("#124;"),
+            '<' => out.push_str("#lt;"),
+            '>' => out.push_str("#gt;"),
+            '\n' => out.push_str("<br>"),
+            '\r' => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// A plan of `(id, depends_on)` tasks, objectives named after the ids.
+    fn plan(tasks: &[(&str, &[&str])]) -> Response {
+        let tasks: Vec<_> = tasks
+            .iter()
+            .map(|(id, deps)| {
+                json!({
+                    "id": id,
+                    "objective": format!("do {}", id),
+                    "affected_files": [],
+                    "changes": {"code": ""},
+                    "depends_on": deps,
+                })
+            })
+            .collect();
+        serde_json::from_value(json!({"tasks": tasks})).unwrap()
+    }
+
+    #[test]
+    fn a_diamond_is_staged_by_depth() {
+        let response = plan(&[("D", &["B", "C"]), ("B", &["A"]), ("C", &["A"]), ("A", &[])]);
+        assert_eq!(response.validate(), Ok(()));
+        assert_eq!(response.topological_order(), [3, 1, 2, 0]);
+        assert_eq!(response.stages(), [vec![3], vec![1, 2], vec![0]]);
+    }
+
+    #[test]
+    fn a_cycle_is_reported_with_its_tasks() {
+        let response = plan(&[("A", &[]), ("B", &["C"]), ("C", &["B"])]);
+        assert_eq!(
+            response.validate(),
+            Err("dependency cycle between tasks: B, C".to_string())
+        );
+        assert_eq!(response.topological_order(), [0]);
+    }
+
+    #[test]
+    fn a_dangling_dependency_is_reported() {
+        let response = plan(&[("A", &["Z"])]);
+        assert_eq!(
+            response.validate(),
+            Err("task 'A' depends on unknown task 'Z'".to_string())
+        );
+        // Unknown ids do not hold a task back, and draw no edge.
+        assert_eq!(response.topological_order(), [0]);
+        assert!(!response.to_mermaid().contains("-->"));
+    }
+
+    #[test]
+    fn a_duplicate_id_is_reported() {
+        let response = plan(&[("A", &[]), ("A", &[])]);
+        assert_eq!(
+            response.validate(),
+            Err("duplicate task id 'A'".to_string())
+        );
+    }
+
+    #[test]
+    fn mermaid_labels_escape_markup_and_line_breaks() {
+        assert_eq!(
+            escape_mermaid("a \"b\" [c] | <d>\r\n#quot;"),
+            "a #quot;b#quot; #91;c#93; #124; #lt;d#gt;<br>#35;quot;"
+        );
+        let response = plan(&[("A", &[]), ("B", &["A"])]);
+        assert_eq!(
+            response.to_mermaid(),
+            "flowchart LR\n    t0[\"A: do A\"]\n    t1[\"B: do B\"]\n    t0 --> t1\n"
+        );
+    }
+}
@@ -0,0 +1,259 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+
+use crate::schema::output_format;
+
+mod graph;
+mod verify;
+
+pub use graph::GraphFormat;
+pub use verify::{FileCheck, FileStatus};
+
+#[derive(Deserialize, Serialize, JsonSchema, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Response {
+    /// A list of tasks.
+    tasks: Vec<Task>,
+}
+
+#[derive(Deserialize, Serialize, JsonSchema, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Task {
+    /// Short stable identifier for the task, e.g. "T1".
+    id: String,
+    /// The main objective of the task.
+    objective: String,
+    /// A list of file paths that will be affected by this task.
+    affected_files: Vec<String>,
+    /// Details about the changes to be made.
+    changes: Changes,
+    /// Ids of the tasks that must be completed before this one can start.
+    depends_on: Vec<String>,
+    /// Filled in by `Response::verify_files`; hidden from the model's schema
+    /// and never taken from its answer.
+    #[serde(default, skip_deserializing, skip_serializing_if = "Vec::is_empty")]
+    #[schemars(skip)]
+    file_checks: Vec<FileCheck>,
+}
+
+#[derive(Deserialize, Serialize, JsonSchema, Debug)]
+#[serde(deny_unknown_fields)]
+struct Changes {
+    /// Description of the code changes to be implemented.
+    code: String,
+}
+
+impl Response {
+    /// Parses the model's final message into a `Response`.
+    ///
+    /// The raw text is tried as-is first; if that fails, the first JSON object
+    /// found inside prose or a code fence is tried instead. The error string is
+    /// meant to be sent back to the model so it can repair its answer.
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        let res = match serde_json::from_str::<Response>(raw.trim()) {
+            Ok(res) => res,
+            Err(direct) => match extract_json(raw) {
+                Some(candidate) if candidate != raw.trim() => {
+                    serde_json::from_str::<Response>(candidate).map_err(|e| e.to_string())?
+                }
+                _ => return Err(direct.to_string()),
+            },
+        };
+        res.validate()?;
+        Ok(res)
+    }
+}
+
+/// Finds the first balanced JSON object in `raw`.
+///
+/// Handles answers wrapped in ```` ```json ```` fences or surrounded by prose.
+/// Braces inside string literals are ignored.
+pub fn extract_json(raw: &str) -> Option<&str> {
+    let start = raw.find('{')?;
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (i, c) in raw[start..].char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(&raw[start..start + i + 1]);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+impl fmt::Display for Response {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "=== Response: {} task(s) ===", self.tasks.len())?;
+        let mut position = 0;
+        for (stage, tasks) in self.stages().iter().enumerate() {
+            writeln!(
+                f,
+                "\n=== Stage {} ({} task(s) that can run in parallel) ===",
+                stage + 1,
+                tasks.len()
+            )?;
+            for &i in tasks {
+                position += 1;
+                let task = &self.tasks[i];
+                writeln!(
+                    f,
+                    "\n--- Task {}/{} [{}] ---",
+                    position,
+                    self.tasks.len(),
+                    task.id
+                )?;
+                write!(f, "{}", task)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Response {
+    /// Tasks in the order they should be worked on, with their stage number.
+    pub fn ordered_tasks(&self) -> Vec<(usize, &Task)> {
+        self.stages()
+            .into_iter()
+            .enumerate()
+            .flat_map(|(stage, tasks)| tasks.into_iter().map(move |i| (stage + 1, i)))
+            .map(|(stage, i)| (stage, &self.tasks[i]))
+            .collect()
+    }
+}
+
+impl Task {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn objective(&self) -> &str {
+        &self.objective
+    }
+
+    pub fn affected_files(&self) -> &[String] {
+        &self.affected_files
+    }
+
+    /// Verification results, empty until `Response::verify_files` ran.
+    pub fn file_checks(&self) -> &[FileCheck] {
+        &self.file_checks
+    }
+
+    pub fn depends_on(&self) -> &[String] {
+        &self.depends_on
+    }
+
+    pub fn code(&self) -> &str {
+        &self.changes.code
+    }
+}
+
+impl fmt::Display for Task {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Objective
+        writeln!(f, "Objective: {}", self.objective)?;
+        // Affected files
+        writeln!(f, "Affected files:")?;
+        if self.file_checks.is_empty() {
+            for file in &self.affected_files {
+                writeln!(f, "    • {}", file)?;
+            }
+        }
+        for check in &self.file_checks {
+            match &check.status {
+                FileStatus::Existing => writeln!(f, "    • {}", check.path)?,
+                FileStatus::New => writeln!(f, "    • {} (new)", check.path)?,
+                FileStatus::Invalid {
+                    suggestion: Some(s),
+                } => writeln!(f, "    ✗ {} (not found, did you mean {}?)", check.path, s)?,
+                FileStatus::Invalid { suggestion: None } => {
+                    writeln!(f, "    ✗ {} (not found)", check.path)?
+                }
+            }
+        }
+        // Dependencies
+        if !self.depends_on.is_empty() {
+            writeln!(f, "Depends on: {}", self.depends_on.join(", "))?;
+        }
+        // Changes block
+        writeln!(f, "Changes:")?;
+        for line in self.changes.code.lines() {
+            writeln!(f, "    {}", line)?;
+        }
+        Ok(())
+    }
+}
+
+/// The `format` schema for the final task breakdown.
+pub fn res_format() -> Value {
+    output_format::<Response>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAN: &str = r#"{"tasks": [{
+        "id": "T1",
+        "objective": "Add {braces} to the \"docs\"",
+        "affected_files": ["README.md"],
+        "changes": {"code": "fn main() {}"},
+        "depends_on": []
+    }]}"#;
+
+    #[test]
+    fn a_fenced_plan_is_extracted() {
+        let raw = format!("```json\n{}\n```", PLAN);
+        assert_eq!(extract_json(&raw), Some(PLAN));
+        let res = Response::parse(&raw).unwrap();
+        assert_eq!(res.tasks[0].objective(), "Add {braces} to the \"docs\"");
+    }
+
+    #[test]
+    fn prose_around_a_plan_is_skipped() {
+        let raw = format!(
+            "Here is the plan:\n{}\nLet me know if {{this}} works.",
+            PLAN
+        );
+        assert_eq!(extract_json(&raw), Some(PLAN));
+        assert_eq!(Response::parse(&raw).unwrap().tasks[0].id(), "T1");
+    }
+
+    #[test]
+    fn invalid_json_reports_the_parse_error() {
+        let error = Response::parse(r#"{"tasks": [}"#).unwrap_err();
+        assert!(error.contains("line 1"), "{error}");
+        assert_eq!(extract_json("no json here"), None);
+        assert_eq!(extract_json(r#"{"open": "}"#), None);
+    }
+
+    #[test]
+    fn file_checks_are_not_taken_from_the_model() {
+        let raw = PLAN.replace(
+            r#""depends_on": []"#,
+            r#""depends_on": [], "file_checks": [{"path": "README.md", "status": "existing"}]"#,
+        );
+        let error = Response::parse(&raw).unwrap_err();
+        assert!(error.contains("file_checks"), "{error}");
+    }
+}
@@ -0,0 +1,166 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tools::crawler::Crawler;
+
+use super::Response;
+
+/// How an `affected_files` entry relates to the repository on disk.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum FileStatus {
+    /// The file already exists under the crawler root.
+    Existing,
+    /// The file does not exist yet, but its parent directory does.
+    New,
+    /// Neither the file nor its parent directory exists.
+    Invalid { suggestion: Option<String> },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FileCheck {
+    pub path: String,
+    #[serde(flatten)]
+    pub status: FileStatus,
+}
+
+impl Response {
+    /// Classifies every `affected_files` entry against the crawler root and
+    /// stores the result on its task.
+    pub async fn verify_files(&mut self, crawler: &Crawler) {
+        for task in &mut self.tasks {
+            let mut checks = Vec::new();
+            for path in &task.affected_files {
+                checks.push(FileCheck {
+                    path: path.clone(),
+                    status: classify(crawler, path).await,
+                });
+            }
+            task.file_checks = checks;
+        }
+    }
+
+    /// All checks that came back invalid, in task order.
+    pub fn invalid_files(&self) -> Vec<&FileCheck> {
+        self.tasks
+            .iter()
+            .flat_map(|t| &t.file_checks)
+            .filter(|c| matches!(c.status, FileStatus::Invalid { .. }))
+            .collect()
+    }
+}
+
+async fn classify(crawler: &Crawler, path: &str) -> FileStatus {
+    let root = crawler.root_path();
+    let rel = relative_to(root, Path::new(path));
+    let full = root.join(&rel);
+
+    let inside_root = full
+        .canonicalize()
+        .map(|c| c.starts_with(root))
+        .unwrap_or(true);
+    if inside_root && full.is_file() {
+        return FileStatus::Existing;
+    }
+
+    let parent_exists = full
+        .parent()
+        .and_then(|p| p.canonicalize().ok())
+        .is_some_and(|p| p.starts_with(root) && p.is_dir());
+    if inside_root && parent_exists && !full.is_dir() {
+        return FileStatus::New;
+    }
+
+    let suggestion = crawler
+        .fuzzy_search_paths(&[path])
+        .await
+        .into_iter()
+        .find(|(_, p)| p.is_file())
+        .map(|(_, p)| relative_to(root, &p).to_string_lossy().into_owned());
+    FileStatus::Invalid { suggestion }
+}
+
+/// Strips `root` (and a leading `./`) so paths can be compared and printed
+/// root-relative regardless of how the model spelled them.
+fn relative_to(root: &Path, path: &Path) -> PathBuf {
+    let stripped = path.strip_prefix(root).unwrap_or(path);
+    stripped
+        .strip_prefix("./")
+        .unwrap_or(stripped)
+        .to_path_buf()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::fs;
+    use tempfile::TempDir;
+
+    async fn checks(root: &Path, files: &[&str]) -> Vec<FileCheck> {
+        let mut response: Response = serde_json::from_value(json!({"tasks": [{
+            "id": "T1",
+            "objective": "",
+            "affected_files": files,
+            "changes": {"code": ""},
+            "depends_on": [],
+        }]}))
+        .unwrap();
+        response.verify_files(&Crawler::new(root)).await;
+        response.tasks.remove(0).file_checks
+    }
+
+    #[tokio::test]
+    async fn paths_are_classified_against_the_root() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        fs::create_dir_all(root.join("src/session")).unwrap();
+        fs::write(root.join("src/session/export.rs"), "").unwrap();
+        let absolute = root.join("src/session/export.rs");
+
+        let checks = checks(
+            &root,
+            &[
+                "src/session/export.rs",
+                absolute.to_str().unwrap(),
+                "./src/session/view.rs",
+                "src/sesion/export.rs",
+                "src",
+            ],
+        )
+        .await;
+        let statuses: Vec<_> = checks.iter().map(|c| c.status.clone()).collect();
+        assert_eq!(
+            statuses,
+            [
+                FileStatus::Existing,
+                FileStatus::Existing,
+                FileStatus::New,
+                FileStatus::Invalid {
+                    suggestion: Some("src/session/export.rs".to_string())
+                },
+                FileStatus::Invalid {
+                    suggestion: Some("src/session/export.rs".to_string())
+                },
+            ]
+        );
+        assert_eq!(checks[1].path, absolute.to_str().unwrap());
+    }
+
+    #[tokio::test]
+    async fn paths_outside_the_root_are_invalid() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().join("repo");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(dir.path().join("outside.rs"), "").unwrap();
+
+        let checks = checks(&root, &["../outside.rs", "../new.rs"]).await;
+        for check in checks {
+            assert_eq!(
+                check.status,
+                FileStatus::Invalid { suggestion: None },
+                "{}",
+                check.path
+            );
+        }
+    }
+}
@@ -0,0 +1,19 @@
+use schemars::{generate::SchemaSettings, JsonSchema};
+use serde_json::Value;
+
+/// Builds the `format` schema Ollama should constrain output to, derived from `T`.
+///
+/// Subschemas are inlined because Ollama's grammar conversion does not follow
+/// `$ref`s, and the `$schema`/`title` keys are dropped as noise for the model.
+pub fn output_format<T: JsonSchema>() -> Value {
+    let generator = SchemaSettings::draft2020_12()
+        .with(|s| s.inline_subschemas = true)
+        .into_generator();
+    let mut schema = generator.into_root_schema_for::<T>().to_value();
+
+    if let Some(obj) = schema.as_object_mut() {
+        obj.remove("$schema");
+        obj.remove("title");
+    }
+    schema
+}
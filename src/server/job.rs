@@ -0,0 +1,300 @@
+use chrono::Local;
+use ollama::{
+    types::{ChatMessage, MessageRole},
+    OllamaClient,
+};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::{
+    collections::VecDeque,
+    error::Error,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::{watch, Mutex as SessionLock};
+
+use crate::{
+    agents::researcher::{get_initial_messages, research, Research},
+    config::settings::load_settings_from,
+    final_output::produce_plan,
+    interrupt::Interrupt,
+    session::Session,
+    tool_handling::{connect_mcp, handle_tool_calls},
+};
+
+/// Events kept per job; older ones are dropped; the session log still has
+/// them all.
+const MAX_EVENTS: usize = 10_000;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    /// Researching, planning or answering a message.
+    Running,
+    /// Finished the last request; follow-up messages are accepted.
+    Done,
+    /// The last request was cancelled; follow-up messages are accepted.
+    Cancelled,
+    Failed,
+}
+
+/// What a client sees of a job.
+#[derive(Serialize, Debug, Clone)]
+pub struct Summary {
+    pub id: String,
+    pub root: PathBuf,
+    pub session_id: String,
+    pub model: String,
+    pub status: Status,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// The final `Response`, once research is over.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plan: Option<Value>,
+    /// The model's answer to the latest follow-up message.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reply: Option<String>,
+}
+
+/// A planning session run in the background. Everything it logs, plus its
+/// status changes, is kept as a list of events clients can stream, up to
+/// [`MAX_EVENTS`] of the latest.
+pub struct Job {
+    summary: Mutex<Summary>,
+    events: Mutex<Events>,
+    /// Bumped on every new event.
+    changed: watch::Sender<usize>,
+    interrupt: Interrupt,
+    session: SessionLock<Session>,
+}
+
+/// The latest events; indexes count every event since the job started.
+#[derive(Default)]
+struct Events {
+    /// Index of the oldest event kept.
+    first: usize,
+    kept: VecDeque<Value>,
+}
+
+impl Job {
+    /// Creates the session on `root` and starts researching `prompt`.
+    pub fn start(
+        id: String,
+        root: PathBuf,
+        model: &str,
+        prompt: String,
+        client: OllamaClient,
+    ) -> Result<Arc<Self>, Box<dyn Error>> {
+        let settings = load_settings_from(&root)?;
+        let messages = get_initial_messages(&root, prompt);
+        let mut session = Session::create(&root, model, &settings, messages)?;
+        session.interactive = false;
+
+        let job = Arc::new_cyclic(|job: &std::sync::Weak<Job>| {
+            let observer = job.clone();
+            session.observe(move |entry| {
+                if let (Some(job), Ok(event)) = (observer.upgrade(), serde_json::to_value(entry)) {
+                    job.push(event);
+                }
+            });
+            Job {
+                summary: Mutex::new(Summary {
+                    id,
+                    root,
+                    session_id: session.id.clone(),
+                    model: model.to_string(),
+                    status: Status::Running,
+                    error: None,
+                    plan: None,
+                    reply: None,
+                }),
+                events: Mutex::new(Events::default()),
+                changed: watch::Sender::new(0),
+                interrupt: Interrupt::detached(),
+                session: SessionLock::new(session),
+            }
+        });
+        job.push_status(Status::Running, None);
+
+        let runner = job.clone();
+        tokio::spawn(async move {
+            let mut session = runner.session.lock().await;
+            let root = session.root.clone();
+            session.mcp = connect_mcp(&root, &settings.mcp).await;
+            let outcome = runner.plan(&client, &mut session).await;
+            drop(session);
+            runner.finish(outcome, |summary, plan| summary.plan = Some(plan));
+        });
+        Ok(job)
+    }
+
+    /// Researches, then asks for the final plan. `Ok(None)` means cancelled,
+    /// including by a cancel that arrived before research started.
+    async fn plan(
+        &self,
+        client: &OllamaClient,
+        session: &mut Session,
+    ) -> Result<Option<Value>, String> {
+        if self.interrupt.is_cancelled() {
+            return Ok(None);
+        }
+        match research(client, session, &self.interrupt).await {
+            Ok(Research::Done) => {}
+            Ok(Research::Cancelled) => return Ok(None),
+            Err(e) => return Err(e.to_string()),
+        }
+        if self.interrupt.is_cancelled() {
+            return Ok(None);
+        }
+        let step = self.interrupt.step();
+        match produce_plan(client, session, step.token()).await {
+            Ok(plan) => serde_json::to_value(&plan)
+                .map(Some)
+                .map_err(|e| e.to_string()),
+            Err(_) if step.is_cancelled() => Ok(None),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    /// Continues the conversation with `message` in the background. Refused
+    /// while the job is still running.
+    pub fn send(self: &Arc<Self>, message: String, client: OllamaClient) -> Result<(), String> {
+        {
+            let mut summary = self.summary.lock().unwrap();
+            if summary.status == Status::Running {
+                return Err(format!("Job {} is still running.", summary.id));
+            }
+            summary.status = Status::Running;
+            summary.error = None;
+            self.interrupt.reset();
+            self.push_status(Status::Running, None);
+        }
+
+        let runner = self.clone();
+        tokio::spawn(async move {
+            let mut session = runner.session.lock().await;
+            let before = session.messages.len();
+            session.push(ChatMessage {
+                role: MessageRole::User,
+                content: message,
+                thinking: None,
+                images: None,
+                tool_calls: None,
+            });
+            // Cancelled before it started: the step below starts out cancelled.
+            let step = runner.interrupt.step();
+            let result = handle_tool_calls(&mut session, &client, step.token()).await;
+            let outcome = if step.is_cancelled() {
+                // Keep the question, drop the half-finished answer.
+                session.rewind(before + 1);
+                Ok(None)
+            } else {
+                match result {
+                    Ok(()) => Ok(session.messages.last().map(|m| m.content.clone())),
+                    Err(e) => Err(e.to_string()),
+                }
+            };
+            drop(step);
+            drop(session);
+            runner.finish(outcome, |summary, reply| summary.reply = Some(reply));
+        });
+        Ok(())
+    }
+
+    /// Cancels the running request, whichever step it is in: a cancel
+    /// before or between steps stops it at the next one.
+    pub fn cancel(&self) {
+        self.interrupt.cancel();
+    }
+
+    pub fn summary(&self) -> Summary {
+        self.summary.lock().unwrap().clone()
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.summary.lock().unwrap().status == Status::Running
+    }
+
+    /// Events from index `from` on (or from the oldest one kept), the index
+    /// following them, and whether more may follow.
+    pub fn events_from(&self, from: usize) -> (Vec<Value>, usize, bool) {
+        // Status changes push their event under the summary lock, so a job
+        // seen finished already has its final event.
+        let summary = self.summary.lock().unwrap();
+        let events = self.events.lock().unwrap();
+        let running = summary.status == Status::Running;
+        let (shown, next) = events.from(from);
+        (shown, next, running)
+    }
+
+    pub fn subscribe(&self) -> watch::Receiver<usize> {
+        self.changed.subscribe()
+    }
+
+    fn finish<T>(&self, outcome: Result<Option<T>, String>, store: impl FnOnce(&mut Summary, T)) {
+        let mut summary = self.summary.lock().unwrap();
+        match outcome {
+            Ok(Some(result)) => {
+                store(&mut summary, result);
+                summary.status = Status::Done;
+            }
+            Ok(None) => summary.status = Status::Cancelled,
+            Err(e) => {
+                summary.status = Status::Failed;
+                summary.error = Some(e);
+            }
+        }
+        self.push_status(summary.status, summary.error.clone());
+    }
+
+    fn push_status(&self, status: Status, error: Option<String>) {
+        let mut event = json!({"at": Local::now(), "event": "status", "status": status});
+        if let Some(error) = error {
+            event["error"] = json!(error);
+        }
+        self.push(event);
+    }
+
+    fn push(&self, event: Value) {
+        let mut events = self.events.lock().unwrap();
+        let count = events.push(event, MAX_EVENTS);
+        self.changed.send_replace(count);
+    }
+}
+
+impl Events {
+    /// Keeps `event` and at most `limit - 1` older ones. Returns how many
+    /// events there have been.
+    fn push(&mut self, event: Value, limit: usize) -> usize {
+        self.kept.push_back(event);
+        while self.kept.len() > limit {
+            self.kept.pop_front();
+            self.first += 1;
+        }
+        self.first + self.kept.len()
+    }
+
+    /// The events kept from index `from` on, and the index following them.
+    fn from(&self, from: usize) -> (Vec<Value>, usize) {
+        let skip = from.saturating_sub(self.first);
+        let shown = self.kept.iter().skip(skip).cloned().collect();
+        (shown, (self.first + self.kept.len()).max(from))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn old_events_are_dropped_but_indexes_keep_counting() {
+        let mut events = Events::default();
+        for i in 0..5 {
+            events.push(json!(i), 3);
+        }
+        assert_eq!(events.from(0), (vec![json!(2), json!(3), json!(4)], 5));
+        assert_eq!(events.from(3), (vec![json!(3), json!(4)], 5));
+        assert_eq!(events.from(5), (Vec::new(), 5));
+        assert_eq!(events.from(9), (Vec::new(), 9));
+    }
+}
@@ -0,0 +1,294 @@
+//! `viktor serve`: planning jobs over a local HTTP API, for dashboards and
+//! editors that would otherwise spawn the CLI and parse its output.
+//!
+//! - `POST /jobs` `{"prompt", "root"?, "model"?}` starts a job
+//! - `GET /jobs` and `GET /jobs/{id}` show jobs and their status
+//! - `GET /jobs/{id}/events?from=N` streams the job's session log as NDJSON
+//! - `GET /jobs/{id}/plan` returns the final `Response` JSON
+//! - `POST /jobs/{id}/messages` `{"message"}` continues the conversation
+//! - `DELETE /jobs/{id}` cancels the running request
+//!
+//! Jobs run unattended: tool calls the permission policy would ask about are
+//! refused. Only the repositories given with `--root` can be planned on. At
+//! most [`MAX_JOBS`] jobs are kept; the oldest finished ones make room.
+
+mod job;
+
+use axum::{
+    body::{Body, Bytes},
+    extract::{Path as UrlPath, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response as HttpResponse},
+    routing::get,
+    Json, Router,
+};
+use futures::stream;
+use ollama::OllamaClient;
+use serde::Deserialize;
+use serde_json::json;
+use std::{
+    collections::BTreeMap,
+    convert::Infallible,
+    error::Error,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+use tokio::net::TcpListener;
+
+use self::job::{Job, Summary};
+use crate::cli::ServeArgs;
+
+/// Jobs kept in memory, running or not.
+const MAX_JOBS: usize = 100;
+
+#[derive(Clone)]
+struct Server {
+    client: OllamaClient,
+    model: String,
+    roots: Arc<Vec<PathBuf>>,
+    jobs: Arc<Mutex<BTreeMap<u64, Arc<Job>>>>,
+    /// Ids are never reused, even for evicted jobs.
+    last_id: Arc<AtomicU64>,
+}
+
+/// A failed request, answered as `{"error": ...}`.
+struct ApiError(StatusCode, String);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> HttpResponse {
+        (self.0, Json(json!({"error": self.1}))).into_response()
+    }
+}
+
+#[derive(Deserialize)]
+struct NewJob {
+    prompt: String,
+    /// May be left out when only one repository is served.
+    root: Option<PathBuf>,
+    model: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct NewMessage {
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct EventsQuery {
+    /// Number of events the client already has.
+    #[serde(default)]
+    from: usize,
+}
+
+/// Listens on 127.0.0.1 until the process is stopped.
+pub async fn serve(
+    client: OllamaClient,
+    model: &str,
+    args: ServeArgs,
+) -> Result<(), Box<dyn Error>> {
+    let roots = if args.roots.is_empty() {
+        vec![std::env::current_dir()?]
+    } else {
+        args.roots
+    };
+    let roots = roots
+        .iter()
+        .map(|root| {
+            root.canonicalize()
+                .ok()
+                .filter(|r| r.is_dir())
+                .ok_or_else(|| format!("'{}' is not a directory", root.display()))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let server = Server {
+        client,
+        model: model.to_string(),
+        roots: Arc::new(roots),
+        jobs: Arc::new(Mutex::new(BTreeMap::new())),
+        last_id: Arc::new(AtomicU64::new(0)),
+    };
+    let app = Router::new()
+        .route("/jobs", get(list_jobs).post(create_job))
+        .route("/jobs/{id}", get(show_job).delete(cancel_job))
+        .route("/jobs/{id}/events", get(stream_events))
+        .route("/jobs/{id}/plan", get(show_plan))
+        .route("/jobs/{id}/messages", axum::routing::post(send_message))
+        .with_state(server.clone());
+
+    let listener = TcpListener::bind(("127.0.0.1", args.port)).await?;
+    println!("🌐 Serving on http://{}", listener.local_addr()?);
+    for root in server.roots.iter() {
+        println!("📁 {}", root.display());
+    }
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+impl Server {
+    fn job(&self, id: u64) -> Result<Arc<Job>, ApiError> {
+        self.jobs
+            .lock()
+            .unwrap()
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| ApiError(StatusCode::NOT_FOUND, format!("No job {}.", id)))
+    }
+
+    /// The served repository a request names, or the only one.
+    fn root(&self, requested: Option<PathBuf>) -> Result<PathBuf, ApiError> {
+        let bad_request = |message: String| ApiError(StatusCode::BAD_REQUEST, message);
+        match requested {
+            Some(root) => {
+                let canonical = root.canonicalize().ok();
+                self.roots
+                    .iter()
+                    .find(|r| Some(*r) == canonical.as_ref())
+                    .cloned()
+                    .ok_or_else(|| bad_request(format!("'{}' is not served.", root.display())))
+            }
+            None => match self.roots.as_slice() {
+                [root] => Ok(root.clone()),
+                _ => Err(bad_request(
+                    "Several repositories are served; name one in 'root'.".to_string(),
+                )),
+            },
+        }
+    }
+}
+
+async fn create_job(
+    State(server): State<Server>,
+    Json(request): Json<NewJob>,
+) -> Result<(StatusCode, Json<Summary>), ApiError> {
+    let root = server.root(request.root)?;
+    let model = request.model.unwrap_or_else(|| server.model.clone());
+    let full = || {
+        ApiError(
+            StatusCode::SERVICE_UNAVAILABLE,
+            format!("{} jobs are running; try again later.", MAX_JOBS),
+        )
+    };
+    if !make_room(&mut server.jobs.lock().unwrap()) {
+        return Err(full());
+    }
+    // Starting writes the session file; other requests need not wait on it.
+    let id = server.last_id.fetch_add(1, Ordering::SeqCst) + 1;
+    let job = Job::start(
+        id.to_string(),
+        root,
+        &model,
+        request.prompt,
+        server.client.clone(),
+    )
+    .map_err(|e| ApiError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let mut jobs = server.jobs.lock().unwrap();
+    if !make_room(&mut jobs) {
+        // Others filled the room meanwhile.
+        job.cancel();
+        return Err(full());
+    }
+    jobs.insert(id, job.clone());
+    Ok((StatusCode::ACCEPTED, Json(job.summary())))
+}
+
+/// Evicts the oldest finished jobs until there is room for one more.
+/// `false` when every job kept is still running.
+fn make_room(jobs: &mut BTreeMap<u64, Arc<Job>>) -> bool {
+    while jobs.len() >= MAX_JOBS {
+        let finished = jobs
+            .iter()
+            .find(|(_, job)| !job.is_running())
+            .map(|(&id, _)| id);
+        match finished {
+            Some(id) => jobs.remove(&id),
+            None => return false,
+        };
+    }
+    true
+}
+
+async fn list_jobs(State(server): State<Server>) -> Json<Vec<Summary>> {
+    let jobs = server.jobs.lock().unwrap();
+    Json(jobs.values().map(|job| job.summary()).collect())
+}
+
+async fn show_job(
+    State(server): State<Server>,
+    UrlPath(id): UrlPath<u64>,
+) -> Result<Json<Summary>, ApiError> {
+    Ok(Json(server.job(id)?.summary()))
+}
+
+async fn show_plan(
+    State(server): State<Server>,
+    UrlPath(id): UrlPath<u64>,
+) -> Result<HttpResponse, ApiError> {
+    match server.job(id)?.summary().plan {
+        Some(plan) => Ok(Json(plan).into_response()),
+        None => Err(ApiError(
+            StatusCode::CONFLICT,
+            format!("Job {} has no plan yet.", id),
+        )),
+    }
+}
+
+async fn send_message(
+    State(server): State<Server>,
+    UrlPath(id): UrlPath<u64>,
+    Json(request): Json<NewMessage>,
+) -> Result<(StatusCode, Json<Summary>), ApiError> {
+    let job = server.job(id)?;
+    job.send(request.message, server.client.clone())
+        .map_err(|e| ApiError(StatusCode::CONFLICT, e))?;
+    Ok((StatusCode::ACCEPTED, Json(job.summary())))
+}
+
+async fn cancel_job(
+    State(server): State<Server>,
+    UrlPath(id): UrlPath<u64>,
+) -> Result<(StatusCode, Json<Summary>), ApiError> {
+    let job = server.job(id)?;
+    job.cancel();
+    Ok((StatusCode::ACCEPTED, Json(job.summary())))
+}
+
+/// One JSON object per line, starting at event `from`, until the job stops
+/// running. Reconnect with `from` set to the number of lines received; events
+/// the job no longer keeps are skipped.
+async fn stream_events(
+    State(server): State<Server>,
+    UrlPath(id): UrlPath<u64>,
+    Query(query): Query<EventsQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let job = server.job(id)?;
+    let changed = job.subscribe();
+    let lines = stream::unfold(
+        (job, changed, query.from),
+        |(job, mut changed, next)| async move {
+            loop {
+                changed.borrow_and_update();
+                let (events, following, running) = job.events_from(next);
+                if !events.is_empty() {
+                    let chunk: String = events.iter().map(|e| format!("{}\n", e)).collect();
+                    return Some((
+                        Ok::<_, Infallible>(Bytes::from(chunk)),
+                        (job, changed, following),
+                    ));
+                }
+                if !running || changed.changed().await.is_err() {
+                    return None;
+                }
+            }
+        },
+    );
+    Ok((
+        [(header::CONTENT_TYPE, "application/x-ndjson")],
+        Body::from_stream(lines),
+    )
+        .into_response())
+}
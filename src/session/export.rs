@@ -0,0 +1,629 @@
+use chrono::{DateTime, Local};
+use ollama::types::{MessageRole, ToolCall};
+use serde::Serialize;
+use serde_json::Value;
+use std::{error::Error, fmt::Write, str::FromStr};
+
+use super::{last_plan, load, Entry, Event};
+use crate::{
+    response::{FileStatus, Response, Task},
+    tool_handling::Decision,
+};
+
+/// Formats supported by `viktor export`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Markdown,
+    Html,
+    Json,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "md" | "markdown" => Ok(ExportFormat::Markdown),
+            "html" => Ok(ExportFormat::Html),
+            "json" => Ok(ExportFormat::Json),
+            other => Err(format!(
+                "unknown export format '{}' (expected md, html or json)",
+                other
+            )),
+        }
+    }
+}
+
+/// A session log reshaped for reading: tool outputs are attached to the
+/// assistant turn that requested them.
+#[derive(Serialize, Debug)]
+pub struct Transcript {
+    pub id: String,
+    pub model: String,
+    pub started: Option<DateTime<Local>>,
+    pub system_prompt: Option<String>,
+    pub turns: Vec<Turn>,
+    pub plan: Option<Response>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Turn {
+    User {
+        at: DateTime<Local>,
+        content: String,
+    },
+    Assistant {
+        at: DateTime<Local>,
+        content: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        thinking: Option<String>,
+        tool_calls: Vec<ToolUse>,
+    },
+    /// Session bookkeeping such as resumes and compactions.
+    Note { at: DateTime<Local>, text: String },
+}
+
+#[derive(Serialize, Debug)]
+pub struct ToolUse {
+    pub name: String,
+    pub arguments: Value,
+    pub output: Option<String>,
+    pub duration_ms: Option<u64>,
+}
+
+impl Transcript {
+    pub fn load(id: &str) -> Result<Self, Box<dyn Error>> {
+        let entries = load(id)?;
+        Ok(Self::from_entries(id, &entries))
+    }
+
+    fn from_entries(id: &str, entries: &[Entry]) -> Self {
+        let mut transcript = Transcript {
+            id: id.to_string(),
+            model: String::new(),
+            started: None,
+            system_prompt: None,
+            turns: Vec::new(),
+            plan: last_plan(entries),
+        };
+
+        for entry in entries {
+            let at = entry.at;
+            match &entry.event {
+                Event::Start { model, .. } => {
+                    transcript.model = model.clone();
+                    transcript.started = Some(at);
+                }
+                Event::Resume { model } => transcript.turns.push(Turn::Note {
+                    at,
+                    text: format!("Session resumed with {}", model),
+                }),
+                Event::Compacted { messages } => transcript.turns.push(Turn::Note {
+                    at,
+                    text: format!("Context compacted to {} messages", messages.len()),
+                }),
+                Event::Rewound { messages } => transcript.turns.push(Turn::Note {
+                    at,
+                    text: format!("Conversation rewound to {} messages", messages.len()),
+                }),
+                Event::Model { model } => {
+                    transcript.model = model.clone();
+                    transcript.turns.push(Turn::Note {
+                        at,
+                        text: format!("Switched model to {}", model),
+                    })
+                }
+                Event::Message { message, .. } => match message.role {
+                    MessageRole::System => {
+                        if transcript.system_prompt.is_none() {
+                            transcript.system_prompt = Some(message.content.clone());
+                        }
+                    }
+                    MessageRole::User => transcript.turns.push(Turn::User {
+                        at,
+                        content: message.content.clone(),
+                    }),
+                    MessageRole::Assistant => transcript.turns.push(Turn::Assistant {
+                        at,
+                        content: message.content.clone(),
+                        thinking: message.thinking.clone().filter(|t| !t.trim().is_empty()),
+                        tool_calls: message
+                            .tool_calls
+                            .iter()
+                            .flatten()
+                            .map(|c| ToolUse {
+                                name: c.function.name.clone(),
+                                arguments: c.function.arguments.clone(),
+                                output: None,
+                                duration_ms: None,
+                            })
+                            .collect(),
+                    }),
+                    // Outputs come from `ToolOutput`, which keeps the untruncated text.
+                    MessageRole::Tool => {}
+                },
+                Event::ToolOutput {
+                    call,
+                    output,
+                    duration_ms,
+                } => {
+                    if let Some(tool_use) = pending_use(&mut transcript.turns, call) {
+                        tool_use.output = Some(output.clone());
+                        tool_use.duration_ms = Some(*duration_ms);
+                    }
+                }
+                Event::Permission {
+                    call,
+                    decision,
+                    message,
+                } => {
+                    // Routine allowances would only add noise to the transcript.
+                    if *decision == Decision::AllowedByPolicy {
+                        continue;
+                    }
+                    let mut text = format!(
+                        "{} {}: {}",
+                        call.function.name, call.function.arguments, decision
+                    );
+                    if let Some(message) = message {
+                        text.push_str(&format!(" ({})", message));
+                    }
+                    transcript.turns.push(Turn::Note { at, text });
+                }
+                Event::Hook {
+                    hook,
+                    command,
+                    blocked,
+                    message,
+                } => {
+                    let mut text = format!(
+                        "{} hook `{}` {}",
+                        hook,
+                        command,
+                        if *blocked { "blocked" } else { "ran" }
+                    );
+                    if let Some(message) = message {
+                        text.push_str(&format!(": {}", message));
+                    }
+                    transcript.turns.push(Turn::Note { at, text });
+                }
+                Event::Plan { .. } | Event::Step { .. } => {}
+            }
+        }
+        transcript
+    }
+
+    pub fn render(&self, format: ExportFormat) -> Result<String, Box<dyn Error>> {
+        Ok(match format {
+            ExportFormat::Markdown => self.to_markdown(),
+            ExportFormat::Html => self.to_html(),
+            ExportFormat::Json => serde_json::to_string_pretty(self)?,
+        })
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "# Viktor session {}\n", self.id);
+        let _ = writeln!(out, "- **Model:** {}", self.model);
+        if let Some(started) = self.started {
+            let _ = writeln!(out, "- **Started:** {}", started.format("%Y-%m-%d %H:%M"));
+        }
+
+        if let Some(prompt) = &self.system_prompt {
+            let _ = writeln!(out, "\n## System prompt\n");
+            let _ = writeln!(
+                out,
+                "<details>\n<summary>Show system prompt</summary>\n\n{}\n\n</details>",
+                fence(prompt)
+            );
+        }
+
+        let _ = writeln!(out, "\n## Conversation");
+        for turn in &self.turns {
+            match turn {
+                Turn::User { at, content } => {
+                    let _ = writeln!(
+                        out,
+                        "\n### 👤 User · {}\n\n{}",
+                        at.format("%H:%M:%S"),
+                        content.trim()
+                    );
+                }
+                Turn::Assistant {
+                    at,
+                    content,
+                    thinking,
+                    tool_calls,
+                } => {
+                    let _ = writeln!(out, "\n### 🧠 Assistant · {}\n", at.format("%H:%M:%S"));
+                    if let Some(thinking) = thinking {
+                        let _ = writeln!(
+                            out,
+                            "<details>\n<summary>Thinking</summary>\n\n{}\n\n</details>\n",
+                            thinking.trim()
+                        );
+                    }
+                    if !content.trim().is_empty() {
+                        let _ = writeln!(out, "{}\n", content.trim());
+                    }
+                    for tool_use in tool_calls {
+                        let _ =
+                            writeln!(out, "**🔧 `{}`** `{}`\n", tool_use.name, tool_use.arguments);
+                        if let Some(output) = &tool_use.output {
+                            let _ = writeln!(
+                                out,
+                                "<details>\n<summary>Output ({} bytes{})</summary>\n\n{}\n\n</details>\n",
+                                output.len(),
+                                tool_use
+                                    .duration_ms
+                                    .map(|ms| format!(", {} ms", ms))
+                                    .unwrap_or_default(),
+                                fence(&pretty_output(output))
+                            );
+                        }
+                    }
+                }
+                Turn::Note { at, text } => {
+                    let _ = writeln!(out, "\n> _{} · {}_", text, at.format("%Y-%m-%d %H:%M"));
+                }
+            }
+        }
+
+        if let Some(plan) = &self.plan {
+            let _ = writeln!(out, "\n## Task breakdown");
+            for (stage, task) in plan.ordered_tasks() {
+                let _ = writeln!(
+                    out,
+                    "\n### {} · {} (stage {})\n",
+                    task.id(),
+                    task.objective(),
+                    stage
+                );
+                if !task.depends_on().is_empty() {
+                    let _ = writeln!(out, "**Depends on:** {}\n", task.depends_on().join(", "));
+                }
+                let _ = writeln!(out, "**Affected files:**\n");
+                for (path, note) in file_lines(task) {
+                    let _ = writeln!(out, "- `{}`{}", path, note);
+                }
+                let _ = writeln!(out, "\n{}", task.code().trim());
+            }
+        }
+        out
+    }
+
+    pub fn to_html(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Viktor session {id}</title>\n<style>\n{STYLE}</style>\n</head>\n<body>\n<h1>Viktor session {id}</h1>",
+            id = escape_html(&self.id)
+        );
+        let _ = writeln!(out, "<p><b>Model:</b> {}", escape_html(&self.model));
+        if let Some(started) = self.started {
+            let _ = writeln!(
+                out,
+                "<br><b>Started:</b> {}",
+                started.format("%Y-%m-%d %H:%M")
+            );
+        }
+        let _ = writeln!(out, "</p>");
+
+        if let Some(prompt) = &self.system_prompt {
+            let _ = writeln!(
+                out,
+                "<h2>System prompt</h2>\n<details><summary>Show system prompt</summary><pre>{}</pre></details>",
+                escape_html(prompt)
+            );
+        }
+
+        let _ = writeln!(out, "<h2>Conversation</h2>");
+        for turn in &self.turns {
+            match turn {
+                Turn::User { at, content } => {
+                    let _ = writeln!(
+                        out,
+                        "<div class=\"turn user\"><h3>👤 User · {}</h3><pre>{}</pre></div>",
+                        at.format("%H:%M:%S"),
+                        escape_html(content.trim())
+                    );
+                }
+                Turn::Assistant {
+                    at,
+                    content,
+                    thinking,
+                    tool_calls,
+                } => {
+                    let _ = writeln!(
+                        out,
+                        "<div class=\"turn assistant\"><h3>🧠 Assistant · {}</h3>",
+                        at.format("%H:%M:%S")
+                    );
+                    if let Some(thinking) = thinking {
+                        let _ = writeln!(
+                            out,
+                            "<details class=\"thinking\"><summary>Thinking</summary><pre>{}</pre></details>",
+                            escape_html(thinking.trim())
+                        );
+                    }
+                    if !content.trim().is_empty() {
+                        let _ = writeln!(out, "<pre>{}</pre>", escape_html(content.trim()));
+                    }
+                    for tool_use in tool_calls {
+                        let _ = writeln!(
+                            out,
+                            "<div class=\"tool\">🔧 <code>{}</code> <code>{}</code>",
+                            escape_html(&tool_use.name),
+                            escape_html(&tool_use.arguments.to_string())
+                        );
+                        if let Some(output) = &tool_use.output {
+                            let _ = writeln!(
+                                out,
+                                "<details><summary>Output ({} bytes{})</summary><pre>{}</pre></details>",
+                                output.len(),
+                                tool_use
+                                    .duration_ms
+                                    .map(|ms| format!(", {} ms", ms))
+                                    .unwrap_or_default(),
+                                escape_html(&pretty_output(output))
+                            );
+                        }
+                        let _ = writeln!(out, "</div>");
+                    }
+                    let _ = writeln!(out, "</div>");
+                }
+                Turn::Note { at, text } => {
+                    let _ = writeln!(
+                        out,
+                        "<p class=\"note\">{} · {}</p>",
+                        escape_html(text),
+                        at.format("%Y-%m-%d %H:%M")
+                    );
+                }
+            }
+        }
+
+        if let Some(plan) = &self.plan {
+            let _ = writeln!(out, "<h2>Task breakdown</h2>");
+            for (stage, task) in plan.ordered_tasks() {
+                let _ = writeln!(
+                    out,
+                    "<div class=\"task\"><h3>{} · {} <small>(stage {})</small></h3>",
+                    escape_html(task.id()),
+                    escape_html(task.objective()),
+                    stage
+                );
+                if !task.depends_on().is_empty() {
+                    let _ = writeln!(
+                        out,
+                        "<p><b>Depends on:</b> {}</p>",
+                        escape_html(&task.depends_on().join(", "))
+                    );
+                }
+                let _ = writeln!(out, "<ul>");
+                for (path, note) in file_lines(task) {
+                    let _ = writeln!(
+                        out,
+                        "<li><code>{}</code>{}</li>",
+                        escape_html(&path),
+                        escape_html(&note)
+                    );
+                }
+                let _ = writeln!(
+                    out,
+                    "</ul>\n<pre>{}</pre></div>",
+                    escape_html(task.code().trim())
+                );
+            }
+        }
+
+        out.push_str("</body>\n</html>\n");
+        out
+    }
+}
+
+/// The latest call still waiting for the output of `call`: one with the
+/// same arguments, or failing that (a pre_tool hook may have rewritten
+/// them) the first one with the same name.
+fn pending_use<'a>(turns: &'a mut [Turn], call: &ToolCall) -> Option<&'a mut ToolUse> {
+    let waiting = |u: &ToolUse, exact: bool| {
+        u.output.is_none()
+            && u.name == call.function.name
+            && (!exact || u.arguments == call.function.arguments)
+    };
+    let (turn, index) = [true, false].into_iter().find_map(|exact| {
+        turns
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(t, turn)| match turn {
+                Turn::Assistant { tool_calls, .. } => tool_calls
+                    .iter()
+                    .position(|u| waiting(u, exact))
+                    .map(|i| (t, i)),
+                _ => None,
+            })
+    })?;
+    match &mut turns[turn] {
+        Turn::Assistant { tool_calls, .. } => tool_calls.get_mut(index),
+        _ => None,
+    }
+}
+
+const STYLE: &str = "body { font-family: system-ui, sans-serif; max-width: 60rem; margin: 2rem auto; padding: 0 1rem; }
+pre { white-space: pre-wrap; background: #f6f8fa; padding: .5rem; border-radius: 4px; }
+.turn { border-left: 3px solid #ccc; padding-left: 1rem; margin: 1rem 0; }
+.user { border-color: #0969da; }
+.assistant { border-color: #8250df; }
+.tool { margin: .5rem 0; }
+.note { color: #666; font-style: italic; }
+.task { border: 1px solid #ddd; border-radius: 4px; padding: 0 1rem; margin: 1rem 0; }
+";
+
+/// Affected files of a task with their verification note, if any.
+fn file_lines(task: &Task) -> Vec<(String, String)> {
+    if task.file_checks().is_empty() {
+        return task
+            .affected_files()
+            .iter()
+            .map(|f| (f.clone(), String::new()))
+            .collect();
+    }
+    task.file_checks()
+        .iter()
+        .map(|check| {
+            let note = match &check.status {
+                FileStatus::Existing => String::new(),
+                FileStatus::New => " (new)".to_string(),
+                FileStatus::Invalid {
+                    suggestion: Some(s),
+                } => format!(" (not found, did you mean {}?)", s),
+                FileStatus::Invalid { suggestion: None } => " (not found)".to_string(),
+            };
+            (check.path.clone(), note)
+        })
+        .collect()
+}
+
+/// Tool outputs are JSON strings; pretty-print them when possible.
+fn pretty_output(output: &str) -> String {
+    serde_json::from_str::<Value>(output)
+        .ok()
+        .and_then(|v| serde_json::to_string_pretty(&v).ok())
+        .unwrap_or_else(|| output.to_string())
+}
+
+/// Wraps `text` in a code fence long enough not to clash with fences inside it.
+fn fence(text: &str) -> String {
+    let mut ticks = String::from("```");
+    while text.contains(&ticks) {
+        ticks.push('`');
+    }
+    format!("{}\n{}\n{}", ticks, text.trim_end(), ticks)
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ollama::types::{ChatMessage, FunctionRef};
+    use serde_json::json;
+
+    fn entry(event: Event) -> Entry {
+        Entry {
+            at: Local::now(),
+            event,
+        }
+    }
+
+    fn call(name: &str, arguments: Value) -> ToolCall {
+        ToolCall {
+            function: FunctionRef {
+                name: name.to_string(),
+                arguments,
+            },
+        }
+    }
+
+    fn output(call: &ToolCall, output: &str) -> Entry {
+        entry(Event::ToolOutput {
+            call: call.clone(),
+            output: output.to_string(),
+            duration_ms: 1,
+        })
+    }
+
+    fn tool_uses(transcript: &Transcript) -> Vec<(String, Option<String>)> {
+        transcript
+            .turns
+            .iter()
+            .flat_map(|t| match t {
+                Turn::Assistant { tool_calls, .. } => tool_calls.iter().collect(),
+                _ => Vec::new(),
+            })
+            .map(|u| (u.arguments.to_string(), u.output.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn outputs_are_matched_to_calls_by_arguments() {
+        let a = call("crawler.read_file_contents", json!({"path": "a.rs"}));
+        let b = call("crawler.read_file_contents", json!({"path": "b.rs"}));
+        let rewritten = call("crawler.read_file_contents", json!({"path": "c.rs"}));
+        let entries = [
+            entry(Event::Message {
+                message: ChatMessage {
+                    role: MessageRole::Assistant,
+                    content: String::new(),
+                    thinking: None,
+                    images: None,
+                    tool_calls: Some(vec![
+                        a.clone(),
+                        b.clone(),
+                        call("crawler.read_file_contents", json!({"path": "d.rs"})),
+                    ]),
+                },
+                duration_ms: None,
+            }),
+            output(&b, "B"),
+            output(&a, "A"),
+            // A hook changed the arguments; the name still places it.
+            output(&rewritten, "C"),
+        ];
+        let transcript = Transcript::from_entries("t", &entries);
+        assert_eq!(
+            tool_uses(&transcript),
+            [
+                (r#"{"path":"a.rs"}"#.to_string(), Some("A".to_string())),
+                (r#"{"path":"b.rs"}"#.to_string(), Some("B".to_string())),
+                (r#"{"path":"d.rs"}"#.to_string(), Some("C".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn routine_permissions_are_left_out() {
+        let permission = |decision| {
+            entry(Event::Permission {
+                call: call("shell.run", json!({"command": "ls"})),
+                decision,
+                message: None,
+            })
+        };
+        let entries = [
+            permission(Decision::AllowedByPolicy),
+            permission(Decision::DeniedByUser),
+        ];
+        let transcript = Transcript::from_entries("t", &entries);
+        assert_eq!(transcript.turns.len(), 1);
+        let Turn::Note { text, .. } = &transcript.turns[0] else {
+            panic!("expected a note");
+        };
+        assert_eq!(text, r#"shell.run {"command":"ls"}: denied by the user"#);
+    }
+
+    #[test]
+    fn fences_outgrow_the_backticks_they_wrap() {
+        assert_eq!(fence("plain\n"), "```\nplain\n```");
+        assert_eq!(
+            fence("```rust\nfn main() {}\n```"),
+            "````\n```rust\nfn main() {}\n```\n````"
+        );
+        assert_eq!(fence("a ```` b"), "`````\na ```` b\n`````");
+    }
+
+    #[test]
+    fn html_special_characters_are_escaped() {
+        assert_eq!(
+            escape_html(r#"<a href="x">&amp;</a>"#),
+            "&lt;a href=&quot;x&quot;&gt;&amp;amp;&lt;/a&gt;"
+        );
+    }
+}
@@ -0,0 +1,529 @@
+use chrono::{DateTime, Local};
+use ollama::{
+    types::{ChatMessage, ChatRequest, ToolCall, ToolDefinition},
+    OllamaClient,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    env,
+    error::Error,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+use tokio_util::sync::CancellationToken;
+use tools::{lsp::Lsp, mcp::Mcp, plugin::Plugins};
+
+use crate::{
+    config::settings::{Settings, ToolSettings},
+    context::{self, ContextBudget},
+    hooks::{HookPoint, Hooks},
+    redact::Redactor,
+    response::Response,
+    tool_handling::{self, Decision, Permissions},
+};
+
+mod export;
+mod view;
+
+pub use export::{ExportFormat, Transcript};
+pub use view::{last_plan, print_list, print_show};
+
+/// One line of `.viktor/sessions/<id>.jsonl`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Entry {
+    pub at: DateTime<Local>,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// First line of every session.
+    Start {
+        id: String,
+        model: String,
+        cwd: PathBuf,
+    },
+    /// The session was picked up again by `viktor resume`.
+    Resume { model: String },
+    /// A message appended to the conversation sent to the model.
+    Message {
+        message: ChatMessage,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        duration_ms: Option<u64>,
+    },
+    /// A tool call and the output it produced.
+    ToolOutput {
+        call: ToolCall,
+        output: String,
+        duration_ms: u64,
+    },
+    /// A research step began.
+    Step { step: usize, max: usize },
+    /// The final task breakdown.
+    Plan { plan: Value },
+    /// The conversation was compacted; `messages` replaces everything before it.
+    Compacted { messages: Vec<ChatMessage> },
+    /// The user rewound the conversation with `/undo` or `/reset`.
+    Rewound { messages: Vec<ChatMessage> },
+    /// The user switched models with `/model`.
+    Model { model: String },
+    /// The permission policy or the user decided on a tool call.
+    Permission {
+        call: ToolCall,
+        decision: Decision,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        message: Option<String>,
+    },
+    /// A hook blocked, changed or annotated something.
+    Hook {
+        hook: HookPoint,
+        command: String,
+        blocked: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        message: Option<String>,
+    },
+}
+
+/// Callback handed every entry a session logs.
+type Observer = dyn Fn(&Entry) + Send + Sync;
+
+/// A conversation with the model, mirrored to disk as it grows.
+pub struct Session {
+    pub id: String,
+    pub model: String,
+    pub messages: Vec<ChatMessage>,
+    pub context: ContextBudget,
+    pub tools: ToolSettings,
+    pub permissions: Permissions,
+    pub hooks: Hooks,
+    pub redactor: Redactor,
+    /// Language server behind the `lsp.*` tools, started on first use.
+    pub lsp: Lsp,
+    /// Tools declared in `.viktor/tools/*.toml`.
+    pub plugins: Plugins,
+    /// External MCP servers; empty until `tool_handling::connect_mcp` runs.
+    pub mcp: Mcp,
+    /// Repository the session works on.
+    pub root: PathBuf,
+    /// Whether someone can answer prompts; otherwise calls the permission
+    /// policy would ask about are refused and nothing waits on stdin.
+    pub interactive: bool,
+    /// Prompt size Ollama reported for the latest request.
+    pub last_prompt_tokens: Option<u32>,
+    file: File,
+    /// Sees every entry written to the log.
+    observer: Option<Box<Observer>>,
+}
+
+/// Directory holding all session logs for the current project.
+pub fn sessions_dir() -> Result<PathBuf, Box<dyn Error>> {
+    Ok(sessions_dir_of(&env::current_dir()?))
+}
+
+fn sessions_dir_of(root: &Path) -> PathBuf {
+    root.join(".viktor").join("sessions")
+}
+
+fn session_path(id: &str) -> Result<PathBuf, Box<dyn Error>> {
+    if !is_session_id(id) {
+        return Err(format!("'{}' is not a session id", id).into());
+    }
+    Ok(sessions_dir()?.join(format!("{}.jsonl", id)))
+}
+
+/// Whether `id` has the shape [`Session::create`] gives ids,
+/// `YYYYMMDD-HHMMSS` with an optional `-N`, so it cannot name a path
+/// outside the sessions directory.
+fn is_session_id(id: &str) -> bool {
+    let digits = |part: &str, len: Option<usize>| {
+        !part.is_empty()
+            && part.bytes().all(|b| b.is_ascii_digit())
+            && len.is_none_or(|len| part.len() == len)
+    };
+    let parts: Vec<&str> = id.split('-').collect();
+    match parts.as_slice() {
+        [date, time] => digits(date, Some(8)) && digits(time, Some(6)),
+        [date, time, n] => digits(date, Some(8)) && digits(time, Some(6)) && digits(n, None),
+        _ => false,
+    }
+}
+
+impl Session {
+    /// Starts a new session on the repository at `root` and records the
+    /// initial messages.
+    pub fn create(
+        root: &Path,
+        model: &str,
+        settings: &Settings,
+        messages: Vec<ChatMessage>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let dir = sessions_dir_of(root);
+        fs::create_dir_all(&dir)?;
+
+        // Ids are timestamps; a numeric suffix separates runs started in the same second.
+        let stamp = Local::now().format("%Y%m%d-%H%M%S").to_string();
+        let mut id = stamp.clone();
+        let mut n = 1;
+        let file = loop {
+            match OpenOptions::new()
+                .create_new(true)
+                .append(true)
+                .open(dir.join(format!("{}.jsonl", id)))
+            {
+                Ok(file) => break file,
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    n += 1;
+                    id = format!("{}-{}", stamp, n);
+                }
+                Err(e) => return Err(e.into()),
+            }
+        };
+
+        let redactor = Redactor::new(&settings.redaction)?;
+        let mut session = Session {
+            id: id.clone(),
+            model: model.to_string(),
+            messages: Vec::new(),
+            context: ContextBudget::from(&settings.context),
+            tools: settings.tools.clone(),
+            permissions: Permissions::new(root, &settings.permissions)?,
+            hooks: Hooks::new(&settings.hooks)?,
+            lsp: tool_handling::lsp(root, &settings.tools, redactor.sensitive_files()),
+            redactor,
+            plugins: tool_handling::plugins(root)?,
+            mcp: Mcp::default(),
+            root: root.to_path_buf(),
+            interactive: true,
+            last_prompt_tokens: None,
+            file,
+            observer: None,
+        };
+        session.log(Event::Start {
+            id,
+            model: model.to_string(),
+            cwd: root.to_path_buf(),
+        });
+        for message in messages {
+            session.push(message);
+        }
+        Ok(session)
+    }
+
+    /// Reopens a saved session, rebuilding the conversation from its log.
+    /// It works on the repository it was started in; if that directory is
+    /// gone, on the current one.
+    pub fn resume(id: &str, settings: &Settings) -> Result<Self, Box<dyn Error>> {
+        let entries = load(id)?;
+        let mut model = None;
+        let mut messages = Vec::new();
+        let recorded = entries.iter().find_map(|entry| match &entry.event {
+            Event::Start { cwd, .. } => Some(cwd.clone()),
+            _ => None,
+        });
+        let root = match recorded {
+            Some(cwd) if cwd.is_dir() => cwd,
+            Some(cwd) => {
+                let current = env::current_dir()?;
+                eprintln!(
+                    "⚠️ Session {} was started in {}, which no longer exists; using {}",
+                    id,
+                    cwd.display(),
+                    current.display()
+                );
+                current
+            }
+            None => env::current_dir()?,
+        };
+        let mut permissions = Permissions::new(&root, &settings.permissions)?;
+        for entry in entries {
+            match entry.event {
+                Event::Start { model: m, .. }
+                | Event::Resume { model: m }
+                | Event::Model { model: m } => model = Some(m),
+                Event::Message { message, .. } => messages.push(message),
+                Event::Compacted { messages: m } | Event::Rewound { messages: m } => messages = m,
+                Event::Permission {
+                    call,
+                    decision: Decision::AllowedForSession,
+                    ..
+                } => permissions.grant(&call),
+                _ => {}
+            }
+        }
+
+        let model = model.ok_or_else(|| format!("session '{}' has no start record", id))?;
+        let file = OpenOptions::new().append(true).open(session_path(id)?)?;
+        let redactor = Redactor::new(&settings.redaction)?;
+        let mut session = Session {
+            id: id.to_string(),
+            model: model.clone(),
+            messages,
+            context: ContextBudget::from(&settings.context),
+            tools: settings.tools.clone(),
+            permissions,
+            hooks: Hooks::new(&settings.hooks)?,
+            lsp: tool_handling::lsp(&root, &settings.tools, redactor.sensitive_files()),
+            redactor,
+            plugins: tool_handling::plugins(&root)?,
+            mcp: Mcp::default(),
+            root,
+            interactive: true,
+            last_prompt_tokens: None,
+            file,
+            observer: None,
+        };
+        session.log(Event::Resume { model });
+        Ok(session)
+    }
+
+    /// Sends the conversation to the model and appends its reply.
+    ///
+    /// The conversation is compacted first if it would not fit the context budget.
+    /// Fails with `OllamaError::Cancelled` once `cancel` fires.
+    pub async fn chat(
+        &mut self,
+        client: &OllamaClient,
+        tools: Option<Vec<ToolDefinition>>,
+        format: Option<Value>,
+        think: bool,
+        cancel: &CancellationToken,
+    ) -> Result<ChatMessage, Box<dyn Error>> {
+        let reserved = tools.as_deref().map_or(0, context::estimate_tools);
+        context::compact_if_needed(client, self, reserved, cancel).await?;
+
+        let chat_req = ChatRequest {
+            model: self.model.clone(),
+            messages: self.messages.clone(),
+            tools,
+            stream: false,
+            format,
+            think,
+            options: Some(self.context.options()),
+            keep_alive: None,
+        };
+
+        let started = Instant::now();
+        let res = client.chat_cancellable(&chat_req, cancel).await?;
+        self.last_prompt_tokens = res.prompt_eval_count;
+        self.push_timed(res.message.clone(), started.elapsed());
+        Ok(res.message)
+    }
+
+    /// Swaps in a compacted conversation.
+    pub fn replace_messages(&mut self, messages: Vec<ChatMessage>) {
+        self.log(Event::Compacted {
+            messages: messages.clone(),
+        });
+        self.messages = messages;
+    }
+
+    /// Drops messages from `len` onwards.
+    pub fn rewind(&mut self, len: usize) {
+        if len >= self.messages.len() {
+            return;
+        }
+        self.messages.truncate(len);
+        self.log(Event::Rewound {
+            messages: self.messages.clone(),
+        });
+    }
+
+    /// Uses `model` for all following requests.
+    pub fn set_model(&mut self, model: &str) {
+        self.model = model.to_string();
+        self.log(Event::Model {
+            model: model.to_string(),
+        });
+    }
+
+    /// Appends a message to the conversation and the log.
+    pub fn push(&mut self, message: ChatMessage) {
+        self.log(Event::Message {
+            message: message.clone(),
+            duration_ms: None,
+        });
+        self.messages.push(message);
+    }
+
+    /// Appends a model reply along with how long the request took.
+    fn push_timed(&mut self, message: ChatMessage, took: Duration) {
+        self.log(Event::Message {
+            message: message.clone(),
+            duration_ms: Some(took.as_millis() as u64),
+        });
+        self.messages.push(message);
+    }
+
+    pub fn record_tool_output(&mut self, call: &ToolCall, output: &str, took: Duration) {
+        self.log(Event::ToolOutput {
+            call: call.clone(),
+            output: output.to_string(),
+            duration_ms: took.as_millis() as u64,
+        });
+    }
+
+    pub fn record_permission(
+        &mut self,
+        call: &ToolCall,
+        decision: Decision,
+        message: Option<&str>,
+    ) {
+        self.log(Event::Permission {
+            call: call.clone(),
+            decision,
+            message: message.map(str::to_string),
+        });
+    }
+
+    pub fn record_hook(
+        &mut self,
+        hook: HookPoint,
+        command: &str,
+        blocked: bool,
+        message: Option<&str>,
+    ) {
+        self.log(Event::Hook {
+            hook,
+            command: command.to_string(),
+            blocked,
+            message: message.map(str::to_string),
+        });
+    }
+
+    pub fn record_step(&mut self, step: usize, max: usize) {
+        self.log(Event::Step { step, max });
+    }
+
+    pub fn record_plan(&mut self, plan: &Response) {
+        match serde_json::to_value(plan) {
+            Ok(plan) => self.log(Event::Plan { plan }),
+            Err(e) => eprintln!("⚠️ Unable to record plan in session: {}", e),
+        }
+    }
+
+    /// Calls `observer` with every following log entry.
+    pub fn observe(&mut self, observer: impl Fn(&Entry) + Send + Sync + 'static) {
+        self.observer = Some(Box::new(observer));
+    }
+
+    /// Writes one entry to the session file. Failures are reported but never
+    /// interrupt the conversation.
+    fn log(&mut self, event: Event) {
+        let entry = Entry {
+            at: Local::now(),
+            event,
+        };
+        if let Some(observer) = &self.observer {
+            observer(&entry);
+        }
+        let result = serde_json::to_string(&entry)
+            .map_err(|e| e.to_string())
+            .and_then(|line| writeln!(self.file, "{}", line).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            eprintln!("⚠️ Unable to write session log: {}", e);
+        }
+    }
+}
+
+/// Reads every entry of a saved session.
+pub fn load(id: &str) -> Result<Vec<Entry>, Box<dyn Error>> {
+    let path = session_path(id)?;
+    if !path.exists() {
+        return Err(format!("no session '{}' in {}", id, sessions_dir()?.display()).into());
+    }
+
+    let reader = BufReader::new(File::open(&path)?);
+    let mut entries = Vec::new();
+    for (n, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry = serde_json::from_str(&line)
+            .map_err(|e| format!("{}:{}: {}", path.display(), n + 1, e))?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// Ids of all saved sessions, oldest first.
+pub fn list_ids() -> Result<Vec<String>, Box<dyn Error>> {
+    let dir = sessions_dir()?;
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut ids = fs::read_dir(dir)?
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "jsonl"))
+        .filter_map(|p| p.file_stem().map(|s| s.to_string_lossy().into_owned()))
+        .collect::<Vec<_>>();
+    ids.sort_by(|a, b| id_order(a).cmp(&id_order(b)));
+    Ok(ids)
+}
+
+/// Sort key of a session id: its timestamp, then its numeric suffix, so
+/// `-10` follows `-9`. The first run of a second has no suffix.
+fn id_order(id: &str) -> (&str, u64) {
+    let (stamp, n) = match id.get(..15) {
+        Some(stamp) => (stamp, &id[15..]),
+        None => (id, ""),
+    };
+    (stamp, n.trim_start_matches('-').parse().unwrap_or(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{id_order, is_session_id};
+
+    #[test]
+    fn accepts_generated_ids() {
+        assert!(is_session_id("20261018-231500"));
+        assert!(is_session_id("20261018-231500-2"));
+    }
+
+    #[test]
+    fn rejects_paths_and_malformed_ids() {
+        for id in [
+            "",
+            "../../x",
+            "20261018-231500/../../x",
+            "/etc/passwd",
+            "20261018",
+            "2026101-231500",
+            "20261018-231500-",
+            "20261018-231500-2-3",
+            "20261018-23150a",
+        ] {
+            assert!(!is_session_id(id), "{id}");
+        }
+    }
+
+    #[test]
+    fn runs_of_one_second_sort_by_their_number() {
+        let mut ids = vec![
+            "20261018-231501",
+            "20261018-231500-10",
+            "20261018-231500-2",
+            "20261018-231500",
+            "20261018-231500-9",
+        ];
+        ids.sort_by(|a, b| id_order(a).cmp(&id_order(b)));
+        assert_eq!(
+            ids,
+            [
+                "20261018-231500",
+                "20261018-231500-2",
+                "20261018-231500-9",
+                "20261018-231500-10",
+                "20261018-231501",
+            ]
+        );
+    }
+}
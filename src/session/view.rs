@@ -0,0 +1,184 @@
+use ollama::types::MessageRole;
+use std::error::Error;
+
+use super::{list_ids, load, Entry, Event};
+use crate::{response::Response, tool_handling::Decision};
+
+/// Prints one line per saved session: id, model, start time, size and goal.
+pub fn print_list() -> Result<(), Box<dyn Error>> {
+    let ids = list_ids()?;
+    if ids.is_empty() {
+        println!("No saved sessions yet.");
+        return Ok(());
+    }
+
+    for id in ids {
+        let entries = match load(&id) {
+            Ok(entries) => entries,
+            Err(e) => {
+                println!("{:<20} ⚠️ unreadable: {}", id, e);
+                continue;
+            }
+        };
+        let summary = Summary::of(&entries);
+        println!(
+            "{:<20} {:<16} {}  {:>3} msg  {}  {}",
+            id,
+            summary.model,
+            summary.started,
+            summary.messages,
+            if summary.has_plan { "📋" } else { "  " },
+            summary.goal
+        );
+    }
+    Ok(())
+}
+
+/// Prints a saved session: header, conversation and the final plan.
+pub fn print_show(id: &str) -> Result<(), Box<dyn Error>> {
+    let entries = load(id)?;
+    let summary = Summary::of(&entries);
+
+    println!("=== Session {} ===", id);
+    println!("Model:    {}", summary.model);
+    println!("Started:  {}", summary.started);
+    if let (Some(first), Some(last)) = (entries.first(), entries.last()) {
+        let secs = (last.at - first.at).num_seconds();
+        println!("Duration: {}m {}s", secs / 60, secs % 60);
+    }
+    println!("Messages: {}", summary.messages);
+
+    for entry in &entries {
+        match &entry.event {
+            Event::Message { message, .. } => {
+                let label = match message.role {
+                    MessageRole::System => continue,
+                    MessageRole::User => "👤 User",
+                    MessageRole::Assistant => "🧠 Assistant",
+                    MessageRole::Tool => continue,
+                };
+                println!("\n[{}] {}:", entry.at.format("%H:%M:%S"), label);
+                if !message.content.trim().is_empty() {
+                    println!("{}", message.content.trim());
+                }
+                for call in message.tool_calls.iter().flatten() {
+                    println!("    🔧 {} {}", call.function.name, call.function.arguments);
+                }
+            }
+            Event::ToolOutput {
+                call,
+                output,
+                duration_ms,
+            } => {
+                println!(
+                    "    ↳ {} returned {} bytes in {} ms",
+                    call.function.name,
+                    output.len(),
+                    duration_ms
+                );
+            }
+            Event::Resume { .. } => {
+                println!("\n--- resumed at {} ---", entry.at.format("%Y-%m-%d %H:%M"));
+            }
+            Event::Compacted { messages } => {
+                println!("\n--- context compacted to {} messages ---", messages.len());
+            }
+            Event::Rewound { messages } => {
+                println!("\n--- rewound to {} messages ---", messages.len());
+            }
+            Event::Model { model } => {
+                println!("\n--- switched model to {} ---", model);
+            }
+            Event::Permission {
+                call,
+                decision,
+                message,
+            } => {
+                if *decision != Decision::AllowedByPolicy {
+                    println!(
+                        "    🔐 {} {}{}",
+                        call.function.name,
+                        decision,
+                        message
+                            .as_deref()
+                            .map(|m| format!(": {}", m))
+                            .unwrap_or_default()
+                    );
+                }
+            }
+            Event::Hook {
+                hook,
+                command,
+                blocked,
+                message,
+            } => {
+                println!(
+                    "    🪝 {} `{}`{}{}",
+                    hook,
+                    command,
+                    if *blocked { " blocked" } else { "" },
+                    message
+                        .as_deref()
+                        .map(|m| format!(": {}", m))
+                        .unwrap_or_default()
+                );
+            }
+            Event::Plan { .. } | Event::Start { .. } | Event::Step { .. } => {}
+        }
+    }
+
+    if let Some(plan) = last_plan(&entries) {
+        println!("\n{}", plan);
+    }
+    Ok(())
+}
+
+/// The most recent plan recorded in a session, if any.
+pub fn last_plan(entries: &[Entry]) -> Option<Response> {
+    entries.iter().rev().find_map(|e| match &e.event {
+        Event::Plan { plan } => serde_json::from_value(plan.clone()).ok(),
+        _ => None,
+    })
+}
+
+struct Summary {
+    model: String,
+    started: String,
+    messages: usize,
+    has_plan: bool,
+    goal: String,
+}
+
+impl Summary {
+    fn of(entries: &[Entry]) -> Self {
+        let mut summary = Summary {
+            model: String::from("?"),
+            started: String::from("?"),
+            messages: 0,
+            has_plan: false,
+            goal: String::new(),
+        };
+        for entry in entries {
+            match &entry.event {
+                Event::Start { model, .. } => {
+                    summary.model = model.clone();
+                    summary.started = entry.at.format("%Y-%m-%d %H:%M").to_string();
+                }
+                Event::Model { model } => summary.model = model.clone(),
+                Event::Message { message, .. } => {
+                    summary.messages += 1;
+                    if summary.goal.is_empty() && matches!(message.role, MessageRole::User) {
+                        summary.goal = message.content.lines().next().unwrap_or("").to_string();
+                        if summary.goal.chars().count() > 60 {
+                            summary.goal =
+                                summary.goal.chars().take(57).collect::<String>() + "...";
+                        }
+                    }
+                }
+                Event::Plan { .. } => summary.has_plan = true,
+                _ => {}
+            }
+        }
+        summary
+    }
+}
@@ -0,0 +1,360 @@
+use futures::{stream, StreamExt};
+use globset::GlobSet;
+use ollama::types::{ChatMessage, MessageRole, ToolCall, ToolDefinition};
+use serde_json::json;
+use std::{
+    error::Error,
+    path::Path,
+    time::{Duration, Instant},
+};
+use tokio_util::{sync::CancellationToken, task::AbortOnDropHandle};
+use tools::{
+    cargo::Cargo,
+    crawler::Crawler,
+    lsp::{Lsp, LspConfig},
+    mcp::{Mcp, McpServerConfig},
+    plugin::Plugins,
+    shell::{Shell, ShellConfig},
+    Tool,
+};
+
+use crate::{
+    config::settings::{McpSettings, ToolSettings},
+    hooks::annotate,
+    redact::describe,
+    session::Session,
+};
+
+mod permission;
+mod serve;
+mod truncate;
+
+use permission::authorize;
+pub use permission::{Decision, Permissions};
+pub use serve::Served;
+use truncate::cap_output;
+
+/// Definitions of every tool offered to the model.
+pub fn tool_defs(session: &Session) -> Vec<ToolDefinition> {
+    let (root, tools) = (&session.root, &session.tools);
+    let mut defs = crawler(root, tools, GlobSet::empty()).get_tool_defs();
+    defs.extend(Cargo::new(root).get_tool_defs());
+    defs.extend(shell(root, tools).get_tool_defs());
+    if tools.lsp.enabled && session.lsp.is_available() {
+        defs.extend(session.lsp.get_tool_defs());
+    }
+    defs.extend(session.plugins.get_tool_defs());
+    defs.extend(session.mcp.get_tool_defs());
+    defs
+}
+
+fn crawler(root: &Path, tools: &ToolSettings, sensitive: GlobSet) -> Crawler {
+    Crawler::new(root)
+        .allow_hidden(tools.crawler.allow_hidden)
+        .sensitive(sensitive)
+}
+
+fn shell(root: &Path, tools: &ToolSettings) -> Shell {
+    let settings = &tools.shell;
+    Shell::new(
+        root,
+        ShellConfig {
+            allow: settings.allow.clone(),
+            timeout: Duration::from_secs(settings.timeout_secs),
+            max_output: settings.max_output_chars,
+            pass_env: settings.pass_env.clone(),
+        },
+    )
+}
+
+/// A handle to the configured language server; nothing starts until a
+/// `lsp.*` tool is called. Files matching `sensitive` are never opened.
+pub fn lsp(root: &Path, tools: &ToolSettings, sensitive: GlobSet) -> Lsp {
+    let settings = &tools.lsp;
+    Lsp::new(
+        root,
+        LspConfig {
+            command: settings.command.clone(),
+            args: settings.args.clone(),
+            startup_timeout: Duration::from_secs(settings.startup_timeout_secs),
+            request_timeout: Duration::from_secs(settings.request_timeout_secs),
+        },
+    )
+    .sensitive(sensitive)
+}
+
+/// The tools declared in `.viktor/tools/*.toml`.
+pub fn plugins(root: &Path) -> Result<Plugins, Box<dyn Error>> {
+    let dir = root.join(".viktor").join("tools");
+    Plugins::load(root, &dir).map_err(|e| e.to_string().into())
+}
+
+/// Starts the configured MCP servers and reports which ones are usable.
+pub async fn connect_mcp(root: &Path, settings: &McpSettings) -> Mcp {
+    if settings.servers.is_empty() {
+        return Mcp::default();
+    }
+    let configs = settings
+        .servers
+        .iter()
+        .map(|(name, server)| McpServerConfig {
+            name: name.clone(),
+            command: server.command.clone(),
+            args: server.args.clone(),
+            env: server.env.clone(),
+            dir: root.to_path_buf(),
+            timeout: Duration::from_secs(server.timeout_secs),
+        })
+        .collect();
+    let (mcp, errors) = Mcp::connect(configs).await;
+    for (name, count) in mcp.servers() {
+        println!("🔗 MCP server '{}' connected ({} tools)", name, count);
+    }
+    for error in errors {
+        eprintln!("⚠️ {}", error);
+    }
+    mcp
+}
+
+/// Tools that only read the repository and can safely run side by side.
+fn is_read_only(call: &ToolCall) -> bool {
+    let name = call.function.name.as_str();
+    name.starts_with("crawler.") || name.starts_with("lsp.") || name == "cargo.metadata"
+}
+
+/// Routes a tool call to the tool owning its name prefix. The crawler
+/// refuses to read files matching `sensitive`.
+async fn dispatch(
+    call: ToolCall,
+    root: &Path,
+    tools: &ToolSettings,
+    sensitive: &GlobSet,
+    lsp: &Lsp,
+    plugins: &Plugins,
+    mcp: &Mcp,
+) -> String {
+    let name = &call.function.name;
+    let prefix: Vec<&str> = name.split('.').collect();
+    let prefix = prefix.first().expect("Bad tool call name format");
+
+    match *prefix {
+        "crawler" => {
+            crawler(root, tools, sensitive.clone())
+                .handle_tool_call(call)
+                .await
+        }
+        "cargo" => Cargo::new(root).handle_tool_call(call).await,
+        "shell" => shell(root, tools).handle_tool_call(call).await,
+        "lsp" if tools.lsp.enabled => lsp.handle_tool_call(call).await,
+        "plugin" => plugins.handle_tool_call(call).await,
+        "mcp" => mcp.handle_tool_call(call).await,
+        _ => {
+            eprintln!("Error: Unexpected tool call prefix: {}", prefix);
+            json!({"error": format!("Unexpected tool call prefix: {}", prefix)}).to_string()
+        }
+    }
+}
+
+/// Executes tool calls and appends their outputs to the session.
+///
+/// Consecutive read-only calls run concurrently, up to `tools.max_parallel`
+/// at a time; outputs are still appended in the order the model asked for
+/// them. Outputs over `tools.max_output_chars`, or over what is left of
+/// `tools.max_step_output_chars` for this reply, reach the model truncated;
+/// the session log keeps them whole. Every call passes the pre_tool hooks
+/// and the permission policy first; its output is redacted and then passed
+/// to the post_tool hooks. Stops as soon as `cancel` fires; callers check
+/// the token and rewind the unfinished step.
+pub async fn run_tool_calls(
+    session: &mut Session,
+    tool_calls: Vec<ToolCall>,
+    cancel: &CancellationToken,
+) {
+    let limit = session.tools.max_parallel.max(1);
+    let mut step_budget = session.tools.max_step_output_chars;
+    let mut pending = tool_calls.into_iter().peekable();
+
+    while let Some(call) = pending.next() {
+        let mut batch = vec![call];
+        if is_read_only(&batch[0]) {
+            while let Some(next) = pending.next_if(is_read_only) {
+                batch.push(next);
+            }
+        }
+        for call in &batch {
+            call.log();
+        }
+
+        // Hooks and permission prompts run one call at a time, before
+        // anything executes. Hooks go first so the policy sees the
+        // arguments that will actually be used.
+        let hooks = session.hooks.clone();
+        let mut verdicts = Vec::with_capacity(batch.len());
+        let mut notes = Vec::with_capacity(batch.len());
+        for call in &mut batch {
+            match hooks.pre_tool(session, call).await {
+                Ok(messages) => {
+                    verdicts.push(authorize(session, call, is_read_only(call)));
+                    notes.push(messages);
+                }
+                Err(refusal) => {
+                    verdicts.push(Err(refusal));
+                    notes.push(Vec::new());
+                }
+            }
+        }
+        let ran = verdicts.iter().map(Result::is_ok).collect::<Vec<_>>();
+        // Hooks and prompts take time; nothing starts once the step is over.
+        if cancel.is_cancelled() {
+            return;
+        }
+
+        let outputs = if is_read_only(&batch[0]) {
+            let sensitive = session.redactor.sensitive_files();
+            let outputs = stream::iter(batch.clone().into_iter().zip(verdicts))
+                .map(|(call, verdict)| {
+                    let root = session.root.clone();
+                    let tools = session.tools.clone();
+                    let sensitive = sensitive.clone();
+                    let lsp = session.lsp.clone();
+                    let plugins = session.plugins.clone();
+                    let mcp = session.mcp.clone();
+                    async move {
+                        match verdict {
+                            // Aborted when dropped, so cancelling the batch
+                            // stops the calls still running.
+                            Ok(()) => {
+                                AbortOnDropHandle::new(tokio::spawn(async move {
+                                    let started = Instant::now();
+                                    let output = dispatch(
+                                        call, &root, &tools, &sensitive, &lsp, &plugins, &mcp,
+                                    )
+                                    .await;
+                                    (output, started.elapsed())
+                                }))
+                                .await
+                            }
+                            Err(refusal) => Ok((refusal, Duration::ZERO)),
+                        }
+                    }
+                })
+                .buffered(limit)
+                .collect::<Vec<_>>();
+            tokio::select! {
+                outputs = outputs => outputs,
+                _ = cancel.cancelled() => return,
+            }
+        } else {
+            // Run in place rather than spawned, so cancelling drops the call
+            // and kills any process it started.
+            let (call, verdict) = batch
+                .iter()
+                .cloned()
+                .zip(verdicts)
+                .next()
+                .expect("batches are never empty");
+            let sensitive = session.redactor.sensitive_files();
+            let started = Instant::now();
+            let output = match verdict {
+                Ok(()) => tokio::select! {
+                    output = dispatch(
+                        call,
+                        &session.root,
+                        &session.tools,
+                        &sensitive,
+                        &session.lsp,
+                        &session.plugins,
+                        &session.mcp,
+                    ) => output,
+                    _ = cancel.cancelled() => return,
+                },
+                Err(refusal) => refusal,
+            };
+            vec![Ok((output, started.elapsed()))]
+        };
+
+        for (((call, joined), ran), notes) in batch.iter().zip(outputs).zip(ran).zip(notes) {
+            let (mut tool_output, elapsed) = joined.unwrap_or_else(|e| {
+                let error = json!({"error": format!("Tool call panicked: {}", e)});
+                (error.to_string(), Default::default())
+            });
+            if ran {
+                let (redacted, counts) = session.redactor.redact(&tool_output);
+                if !counts.is_empty() {
+                    println!(
+                        "🔒 Redacted {} from {} output",
+                        describe(&counts),
+                        call.function.name
+                    );
+                    tool_output = redacted;
+                }
+                tool_output = hooks.post_tool(session, call, tool_output).await;
+            }
+            let tool_output = annotate(tool_output, &notes);
+            session.record_tool_output(call, &tool_output, elapsed);
+
+            let name = &call.function.name;
+            let shown = cap_output(
+                &tool_output,
+                name,
+                session.tools.output_limit(name).min(step_budget),
+            );
+            step_budget = step_budget.saturating_sub(shown.len());
+            if shown.len() < tool_output.len() {
+                println!(
+                    "✂️ Truncated {} output from {} to {} characters",
+                    name,
+                    tool_output.len(),
+                    shown.len()
+                );
+            }
+
+            session.push(ChatMessage {
+                role: MessageRole::Tool,
+                content: shown,
+                thinking: None,
+                images: None,
+                tool_calls: None,
+            });
+        }
+    }
+}
+
+pub async fn handle_tool_calls(
+    session: &mut Session,
+    client: &ollama::OllamaClient,
+    cancel: &CancellationToken,
+) -> Result<(), Box<dyn Error>> {
+    const MAX_INTERACTIVE_TOOL_LOOPS: usize = 5;
+    let mut tool_loop_count = 0;
+
+    loop {
+        if tool_loop_count >= MAX_INTERACTIVE_TOOL_LOOPS {
+            println!("\n⚠️ Max tool call loops reached in interactive mode");
+            break;
+        }
+
+        let assistant_msg = session
+            .chat(client, Some(tool_defs(session)), None, true, cancel)
+            .await?;
+
+        if let Some(tool_calls) = assistant_msg.tool_calls {
+            if tool_calls.is_empty() {
+                println!("\n🧠 Assistant: {}", assistant_msg.content);
+                break;
+            }
+
+            tool_loop_count += 1;
+            println!("\n🔧 Executing {} tool call(s)...", tool_calls.len());
+            run_tool_calls(session, tool_calls, cancel).await;
+            if cancel.is_cancelled() {
+                break;
+            }
+        } else {
+            println!("\n🧠 Assistant: {}", assistant_msg.content);
+            break;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,550 @@
+use globset::{Glob, GlobBuilder, GlobMatcher};
+use ollama::types::ToolCall;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    error::Error,
+    fmt,
+    path::{Component, Path, PathBuf},
+};
+use tools::shell::Shell;
+
+use crate::{
+    config::settings::{Action, PermissionSettings},
+    redact::Redactor,
+    repl::ask,
+    session::Session,
+};
+
+/// How a tool call was let through or stopped. Recorded in the session log.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Decision {
+    AllowedByPolicy,
+    DeniedByPolicy,
+    AllowedOnce,
+    /// The user allowed it for the rest of the session.
+    AllowedForSession,
+    /// An earlier "always for this session" answer covered it.
+    AllowedByGrant,
+    DeniedByUser,
+}
+
+impl Decision {
+    pub fn is_allowed(self) -> bool {
+        !matches!(self, Decision::DeniedByPolicy | Decision::DeniedByUser)
+    }
+}
+
+impl fmt::Display for Decision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Decision::AllowedByPolicy => "allowed by policy",
+            Decision::DeniedByPolicy => "denied by policy",
+            Decision::AllowedOnce => "allowed once by the user",
+            Decision::AllowedForSession => "allowed for the session by the user",
+            Decision::AllowedByGrant => "allowed by an earlier session grant",
+            Decision::DeniedByUser => "denied by the user",
+        })
+    }
+}
+
+/// The permission rules of a session plus what the user allowed along the way.
+pub struct Permissions {
+    /// Canonical repository root; path arguments are matched relative to it.
+    root: PathBuf,
+    rules: Vec<Rule>,
+    default: Action,
+    /// Tool names, with the exact command for `shell.run`.
+    grants: Vec<(String, Option<String>)>,
+}
+
+struct Rule {
+    tool: GlobMatcher,
+    path: Option<GlobMatcher>,
+    command: Option<Vec<String>>,
+    action: Action,
+    message: Option<String>,
+}
+
+impl Permissions {
+    /// Compiles the configured rules; a bad glob is a configuration error.
+    pub fn new(root: &Path, settings: &PermissionSettings) -> Result<Self, Box<dyn Error>> {
+        let mut rules = Vec::with_capacity(settings.rules.len());
+        for rule in &settings.rules {
+            let path = match &rule.path {
+                Some(pattern) => Some(
+                    GlobBuilder::new(pattern)
+                        .literal_separator(true)
+                        .build()
+                        .map_err(|e| format!("permission rule path '{}': {}", pattern, e))?
+                        .compile_matcher(),
+                ),
+                None => None,
+            };
+            rules.push(Rule {
+                tool: Glob::new(&rule.tool)
+                    .map_err(|e| format!("permission rule tool '{}': {}", rule.tool, e))?
+                    .compile_matcher(),
+                path,
+                command: rule.command.as_deref().map(words),
+                action: rule.action,
+                message: rule.message.clone(),
+            });
+        }
+        Ok(Self {
+            root: root.canonicalize().unwrap_or_else(|_| root.to_path_buf()),
+            rules,
+            default: settings.default,
+            grants: Vec::new(),
+        })
+    }
+
+    /// Calls naming a path outside the root are denied. Otherwise the first
+    /// matching rule wins; without one, read-only tools are allowed and
+    /// everything else falls back to the configured default.
+    fn evaluate(&self, call: &ToolCall, read_only: bool) -> (Action, Option<String>) {
+        let name = call.function.name.as_str();
+        let paths = match self.path_arguments(call) {
+            Ok(paths) => paths,
+            Err(path) => {
+                return (
+                    Action::Deny,
+                    Some(format!("{} is outside the repository.", path)),
+                )
+            }
+        };
+        let command = Shell::command_of(call).map(words);
+
+        for rule in &self.rules {
+            if !rule.tool.is_match(name) {
+                continue;
+            }
+            if let Some(glob) = &rule.path {
+                if !paths.iter().any(|p| glob.is_match(p)) {
+                    continue;
+                }
+            }
+            if let Some(prefix) = &rule.command {
+                if !command.as_ref().is_some_and(|c| c.starts_with(prefix)) {
+                    continue;
+                }
+            }
+            return (rule.action, rule.message.clone());
+        }
+
+        if read_only {
+            (Action::Allow, None)
+        } else {
+            (self.default, None)
+        }
+    }
+
+    /// Path-like arguments as canonical, root-relative paths: `path`, the
+    /// entries of `paths`, and the `crawler.glob` `patterns` (exclusions
+    /// aside). `Err` carries the first argument that leaves the root.
+    pub fn path_arguments(&self, call: &ToolCall) -> Result<Vec<String>, String> {
+        let arguments = &call.function.arguments;
+        let strings = |key: &str| {
+            arguments
+                .get(key)
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        };
+        let single = arguments.get("path").and_then(Value::as_str);
+        let patterns = strings("patterns")
+            .into_iter()
+            .filter(|p| !p.starts_with('!'));
+        single
+            .map(str::to_string)
+            .into_iter()
+            .chain(strings("paths"))
+            .chain(patterns)
+            .map(|path| resolve(&self.root, &path).ok_or(path))
+            .collect()
+    }
+
+    /// Arguments of a `shell.run` command that may name files, as canonical
+    /// root-relative paths: operands, the values of `--option=value`, and the
+    /// path of a `rev:path` argument. Words leading outside the root are
+    /// skipped; the shell tool refuses those on its own.
+    fn command_paths(&self, call: &ToolCall) -> Vec<String> {
+        let argv = Shell::argv_of(call).unwrap_or_default();
+        argv.iter()
+            .skip(1)
+            .map(|word| {
+                word.rsplit_once(['=', ':'])
+                    .map_or(word.as_str(), |(_, v)| v)
+            })
+            .filter(|word| !word.is_empty() && !word.starts_with('-'))
+            .filter_map(|word| resolve(&self.root, word))
+            .collect()
+    }
+
+    fn is_granted(&self, call: &ToolCall) -> bool {
+        let command = Shell::command_of(call).map(str::to_string);
+        self.grants
+            .iter()
+            .any(|(tool, granted)| *tool == call.function.name && *granted == command)
+    }
+
+    /// Allows calls like `call` for the rest of the session: the same command
+    /// for `shell.run`, any arguments for other tools.
+    pub fn grant(&mut self, call: &ToolCall) {
+        if !self.is_granted(call) {
+            let command = Shell::command_of(call).map(str::to_string);
+            self.grants.push((call.function.name.clone(), command));
+        }
+    }
+}
+
+/// Applies the session's policy to `call`, asking the user when it says so.
+/// Sensitive files are denied before any rule is consulted.
+/// Every decision is logged; a refusal comes back as the tool output.
+pub fn authorize(session: &mut Session, call: &ToolCall, read_only: bool) -> Result<(), String> {
+    let (action, message) = decide(&session.permissions, &session.redactor, call, read_only);
+    let (decision, message) = match action {
+        Action::Allow => (Decision::AllowedByPolicy, None),
+        Action::Deny => (Decision::DeniedByPolicy, message),
+        Action::Ask if session.permissions.is_granted(call) => (Decision::AllowedByGrant, None),
+        Action::Ask if !session.interactive => (
+            Decision::DeniedByPolicy,
+            Some("Nobody is available to approve this call.".to_string()),
+        ),
+        Action::Ask => ask_user(call),
+    };
+
+    if decision == Decision::AllowedForSession {
+        session.permissions.grant(call);
+    }
+    session.record_permission(call, decision, message.as_deref());
+    if decision.is_allowed() {
+        return Ok(());
+    }
+
+    let reason = match decision {
+        Decision::DeniedByUser => "Denied by the user",
+        _ => "Denied by the permission policy",
+    };
+    println!("🚫 {} {}", reason, call.function.name);
+    Err(refusal(reason, message))
+}
+
+/// Applies the policy to `call` when nobody can be asked, as in
+/// `viktor mcp-serve`: calls it would ask about are refused.
+pub fn check(
+    permissions: &Permissions,
+    redactor: &Redactor,
+    call: &ToolCall,
+    read_only: bool,
+) -> Result<(), String> {
+    match decide(permissions, redactor, call, read_only) {
+        (Action::Allow, _) => Ok(()),
+        (Action::Ask, _) if permissions.is_granted(call) => Ok(()),
+        (_, message) => Err(refusal("Denied by the permission policy", message)),
+    }
+}
+
+/// The verdict on `call`; sensitive files, named as a path argument or on a
+/// shell command line, are denied before any rule is consulted.
+fn decide(
+    permissions: &Permissions,
+    redactor: &Redactor,
+    call: &ToolCall,
+    read_only: bool,
+) -> (Action, Option<String>) {
+    let mut paths = permissions.path_arguments(call).unwrap_or_default();
+    paths.extend(permissions.command_paths(call));
+    match redactor.sensitive_path(&paths) {
+        Some(path) => (
+            Action::Deny,
+            Some(format!(
+                "{} is a sensitive file; its contents are never sent to the model.",
+                path
+            )),
+        ),
+        None => permissions.evaluate(call, read_only),
+    }
+}
+
+fn refusal(reason: &str, message: Option<String>) -> String {
+    match message {
+        Some(message) => json!({"error": reason, "message": message}),
+        None => json!({"error": reason}),
+    }
+    .to_string()
+}
+
+fn ask_user(call: &ToolCall) -> (Decision, Option<String>) {
+    let subject = match Shell::command_of(call) {
+        Some(command) => format!("run `{}`", command),
+        None => format!("call it with {}", call.function.arguments),
+    };
+    println!("\n🔐 {} wants to {}", call.function.name, subject);
+
+    loop {
+        let answer = ask("Allow? [o]nce / [a]lways this session / [d]eny: ");
+        match answer.as_deref().map(str::to_lowercase).as_deref() {
+            Ok("o" | "once") => return (Decision::AllowedOnce, None),
+            Ok("a" | "always") => return (Decision::AllowedForSession, None),
+            Ok("d" | "deny") => {
+                let message = ask("Message for the model (optional): ")
+                    .ok()
+                    .filter(|m| !m.is_empty());
+                return (Decision::DeniedByUser, message);
+            }
+            // Closed stdin: nobody is there to approve.
+            Err(_) | Ok("") => return (Decision::DeniedByUser, None),
+            Ok(_) => continue,
+        }
+    }
+}
+
+/// `path` relative to `root` with symlinks and `..` resolved, `/`-separated,
+/// or `None` when it leads outside `root`. Components that do not exist
+/// (new files, glob wildcards) are taken as written.
+fn resolve(root: &Path, path: &str) -> Option<String> {
+    let mut current = root.to_path_buf();
+    for component in Path::new(path).components() {
+        match component {
+            Component::Prefix(_) | Component::RootDir => {
+                current = PathBuf::from(component.as_os_str());
+            }
+            Component::CurDir => {}
+            Component::ParentDir => {
+                current.pop();
+            }
+            Component::Normal(name) => {
+                current.push(name);
+                if let Ok(canonical) = current.canonicalize() {
+                    current = canonical;
+                }
+            }
+        }
+    }
+    let rel = current.strip_prefix(root).ok()?;
+    let parts: Vec<_> = rel.iter().map(|part| part.to_string_lossy()).collect();
+    Some(if parts.is_empty() {
+        ".".to_string()
+    } else {
+        parts.join("/")
+    })
+}
+
+fn words(command: &str) -> Vec<String> {
+    command.split_whitespace().map(str::to_string).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::settings::PermissionRule;
+    use ollama::types::FunctionRef;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn call(name: &str, arguments: Value) -> ToolCall {
+        ToolCall {
+            function: FunctionRef {
+                name: name.to_string(),
+                arguments,
+            },
+        }
+    }
+
+    fn rule(
+        tool: &str,
+        path: Option<&str>,
+        command: Option<&str>,
+        action: Action,
+    ) -> PermissionRule {
+        PermissionRule {
+            tool: tool.to_string(),
+            path: path.map(str::to_string),
+            command: command.map(str::to_string),
+            action,
+            message: None,
+        }
+    }
+
+    /// A repository with `src/main.rs` and `secret/key.txt`, and a policy
+    /// denying everything under `secret/`.
+    fn setup(mut rules: Vec<PermissionRule>) -> (TempDir, Permissions) {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("src")).unwrap();
+        fs::create_dir_all(dir.path().join("secret")).unwrap();
+        fs::write(dir.path().join("src/main.rs"), "fn main() {}").unwrap();
+        fs::write(dir.path().join("secret/key.txt"), "hunter2").unwrap();
+        rules.push(rule("crawler.*", Some("secret/**"), None, Action::Deny));
+        let settings = PermissionSettings {
+            default: Action::Ask,
+            rules,
+        };
+        let permissions = Permissions::new(dir.path(), &settings).unwrap();
+        (dir, permissions)
+    }
+
+    fn read(path: &str) -> ToolCall {
+        call("crawler.read_file_contents", json!({ "paths": [path] }))
+    }
+
+    #[test]
+    fn path_rules_match_plain_paths() {
+        let (_dir, permissions) = setup(Vec::new());
+        assert_eq!(
+            permissions.evaluate(&read("secret/key.txt"), true).0,
+            Action::Deny
+        );
+        assert_eq!(
+            permissions.evaluate(&read("./secret/key.txt"), true).0,
+            Action::Deny
+        );
+        assert_eq!(
+            permissions.evaluate(&read("src/main.rs"), true).0,
+            Action::Allow
+        );
+    }
+
+    #[test]
+    fn parent_components_are_resolved_before_matching() {
+        let (_dir, permissions) = setup(Vec::new());
+        assert_eq!(
+            permissions.evaluate(&read("src/../secret/key.txt"), true).0,
+            Action::Deny
+        );
+        assert_eq!(
+            permissions.evaluate(&read("secret/../src/main.rs"), true).0,
+            Action::Allow
+        );
+    }
+
+    #[test]
+    fn absolute_paths_inside_the_root_are_matched() {
+        let (dir, permissions) = setup(Vec::new());
+        let root = dir.path().canonicalize().unwrap();
+        let absolute = root.join("secret/key.txt");
+        assert_eq!(
+            permissions
+                .evaluate(&read(absolute.to_str().unwrap()), true)
+                .0,
+            Action::Deny
+        );
+    }
+
+    #[test]
+    fn paths_outside_the_root_are_denied() {
+        let (_dir, permissions) = setup(Vec::new());
+        for path in ["..", "../other/file", "src/../../other", "/etc/passwd"] {
+            let (action, message) = permissions.evaluate(&read(path), true);
+            assert_eq!(action, Action::Deny, "{path}");
+            assert!(
+                message.unwrap().contains("outside the repository"),
+                "{path}"
+            );
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_are_matched_by_their_target() {
+        let (dir, permissions) = setup(Vec::new());
+        std::os::unix::fs::symlink(
+            dir.path().join("secret/key.txt"),
+            dir.path().join("notes.txt"),
+        )
+        .unwrap();
+        assert_eq!(
+            permissions.evaluate(&read("notes.txt"), true).0,
+            Action::Deny
+        );
+    }
+
+    #[test]
+    fn glob_patterns_are_matched_against_path_rules() {
+        let (_dir, permissions) = setup(Vec::new());
+        let glob = |patterns: Value| call("crawler.glob", json!({ "patterns": patterns }));
+        assert_eq!(
+            permissions.evaluate(&glob(json!(["secret/*.txt"])), true).0,
+            Action::Deny
+        );
+        assert_eq!(
+            permissions
+                .evaluate(&glob(json!(["src/**/*.rs", "secret/**"])), true)
+                .0,
+            Action::Deny
+        );
+        assert_eq!(
+            permissions.evaluate(&glob(json!(["../**"])), true).0,
+            Action::Deny
+        );
+        assert_eq!(
+            permissions
+                .evaluate(&glob(json!(["src/**", "!secret/**"])), true)
+                .0,
+            Action::Allow
+        );
+    }
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let (_dir, permissions) = setup(vec![
+            rule("crawler.*", Some("secret/key.txt"), None, Action::Allow),
+            rule("shell.run", None, Some("cargo test"), Action::Allow),
+            rule("shell.run", None, Some("cargo"), Action::Deny),
+        ]);
+        assert_eq!(
+            permissions.evaluate(&read("secret/key.txt"), true).0,
+            Action::Allow
+        );
+
+        let run = |command: &str| call("shell.run", json!({ "command": command }));
+        assert_eq!(
+            permissions.evaluate(&run("cargo test -p x"), false).0,
+            Action::Allow
+        );
+        assert_eq!(
+            permissions.evaluate(&run("cargo check"), false).0,
+            Action::Deny
+        );
+        assert_eq!(permissions.evaluate(&run("ls"), false).0, Action::Ask);
+    }
+
+    #[test]
+    fn sensitive_files_on_a_command_line_are_denied() {
+        let (dir, permissions) = setup(Vec::new());
+        fs::write(dir.path().join(".env"), "TOKEN=x").unwrap();
+        let redactor = Redactor::new(&Default::default()).unwrap();
+        let run = |command: &str| call("shell.run", json!({ "command": command }));
+        for command in [
+            "git diff --no-index /dev/null .env",
+            "cat src/../.env",
+            "git show HEAD:.env",
+            "grep --file=.env src",
+        ] {
+            assert_eq!(
+                decide(&permissions, &redactor, &run(command), false).0,
+                Action::Deny,
+                "{command}"
+            );
+        }
+        assert_eq!(
+            decide(&permissions, &redactor, &run("git diff src/main.rs"), false).0,
+            Action::Ask
+        );
+    }
+
+    #[test]
+    fn unmatched_calls_fall_back_by_kind() {
+        let (_dir, permissions) = setup(Vec::new());
+        assert_eq!(
+            permissions.evaluate(&read("src/main.rs"), true).0,
+            Action::Allow
+        );
+        let plugin = call("plugin.lint", json!({ "path": "src/main.rs" }));
+        assert_eq!(permissions.evaluate(&plugin, false).0, Action::Ask);
+    }
+}
@@ -0,0 +1,76 @@
+use globset::GlobSet;
+use ollama::types::{ToolCall, ToolDefinition};
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+};
+use tools::{cargo::Cargo, lsp::Lsp, mcp::Mcp, plugin::Plugins, Tool};
+
+use super::{
+    crawler, dispatch, is_read_only, lsp,
+    permission::{check, Permissions},
+    truncate::cap_output,
+};
+use crate::{
+    config::settings::{Settings, ToolSettings},
+    redact::Redactor,
+};
+
+/// The tools `viktor mcp-serve` offers to other agent hosts: the read-only
+/// ones, under the project's permission rules and redaction. There is nobody
+/// to ask, so calls the policy would ask about are refused; hooks do not run.
+pub struct Served {
+    root: PathBuf,
+    tools: ToolSettings,
+    permissions: Permissions,
+    redactor: Redactor,
+    lsp: Lsp,
+}
+
+impl Served {
+    pub fn new(root: &Path, settings: &Settings) -> Result<Self, Box<dyn Error>> {
+        let redactor = Redactor::new(&settings.redaction)?;
+        Ok(Served {
+            root: root.to_path_buf(),
+            tools: settings.tools.clone(),
+            permissions: Permissions::new(root, &settings.permissions)?,
+            lsp: lsp(root, &settings.tools, redactor.sensitive_files()),
+            redactor,
+        })
+    }
+}
+
+impl Tool for Served {
+    fn get_tool_defs(&self) -> Vec<ToolDefinition> {
+        let mut defs = crawler(&self.root, &self.tools, GlobSet::empty()).get_tool_defs();
+        defs.extend(Cargo::new(&self.root).get_tool_defs());
+        if self.tools.lsp.enabled && self.lsp.is_available() {
+            defs.extend(self.lsp.get_tool_defs());
+        }
+        defs
+    }
+
+    async fn handle_tool_call(&self, call: ToolCall) -> String {
+        let name = call.function.name.clone();
+        if let Err(refusal) = check(
+            &self.permissions,
+            &self.redactor,
+            &call,
+            is_read_only(&call),
+        ) {
+            return refusal;
+        }
+        let output = dispatch(
+            call,
+            &self.root,
+            &self.tools,
+            &self.redactor.sensitive_files(),
+            &self.lsp,
+            &Plugins::default(),
+            &Mcp::default(),
+        )
+        .await;
+        let (output, _) = self.redactor.redact(&output);
+        cap_output(&output, &name, self.tools.output_limit(&name))
+    }
+}
@@ -0,0 +1,282 @@
+use serde_json::Value;
+
+/// Room left for the marker that replaces dropped content.
+const MARKER_RESERVE: usize = 160;
+
+/// Bounds the number of shrinking passes over one output.
+const MAX_PASSES: usize = 64;
+
+/// The only tool whose cut strings are file lines that can be read on from.
+const READ_TOOL: &str = "crawler.read_file_contents";
+
+/// Shrinks the output of the `tool` call to about `limit` characters.
+///
+/// JSON outputs stay valid JSON: the longest string is cut at a line
+/// boundary, or trailing entries of the largest array are dropped, until the
+/// output fits. Every cut leaves a marker saying how much was dropped and how
+/// to fetch the rest.
+pub fn cap_output(output: &str, tool: &str, limit: usize) -> String {
+    if output.len() <= limit {
+        return output.to_string();
+    }
+    let Ok(mut value) = serde_json::from_str::<Value>(output) else {
+        return cut_text(output, limit.saturating_sub(MARKER_RESERVE), None);
+    };
+
+    for _ in 0..MAX_PASSES {
+        let size = value.to_string().len();
+        if size <= limit {
+            return value.to_string();
+        }
+        if !shrink(&mut value, tool, size - limit) {
+            break;
+        }
+    }
+    cut_text(
+        &value.to_string(),
+        limit.saturating_sub(MARKER_RESERVE),
+        None,
+    )
+}
+
+/// Takes roughly `excess` characters out of `value`. Returns `false` when
+/// nothing is left to cut.
+fn shrink(value: &mut Value, tool: &str, excess: usize) -> bool {
+    let mut strings = Vec::new();
+    let mut arrays = Vec::new();
+    collect(value, String::new(), &mut strings, &mut arrays);
+    let longest = strings.into_iter().max_by_key(|(_, size)| *size);
+    let largest = arrays.into_iter().max_by_key(|(_, size)| *size);
+
+    // Cutting one long string loses less than dropping whole entries.
+    if let Some((pointer, size)) = &longest {
+        if *size > excess + MARKER_RESERVE {
+            let first_line = first_line(value, tool, pointer);
+            let Some(Value::String(s)) = value.pointer_mut(pointer) else {
+                return false;
+            };
+            let keep = s.len() * (size - excess - MARKER_RESERVE) / size;
+            *s = cut_text(s, keep, first_line);
+            return true;
+        }
+    }
+    if let Some((pointer, _)) = largest {
+        if let Some(Value::Array(entries)) = value.pointer_mut(&pointer) {
+            drop_entries(entries, excess + MARKER_RESERVE);
+            return true;
+        }
+    }
+    match longest {
+        Some((pointer, size)) if size > MARKER_RESERVE => {
+            let first_line = first_line(value, tool, &pointer);
+            if let Some(Value::String(s)) = value.pointer_mut(&pointer) {
+                *s = cut_text(s, 0, first_line);
+            }
+            true
+        }
+        _ => false,
+    }
+}
+
+/// File line the string at `pointer` starts on, if it is the `content` of
+/// a file read: the `start_line` next to it for ranged reads, otherwise 1.
+fn first_line(value: &Value, tool: &str, pointer: &str) -> Option<usize> {
+    let (parent, key) = pointer.rsplit_once('/')?;
+    if tool != READ_TOOL || key != "content" {
+        return None;
+    }
+    let line = value
+        .pointer(parent)
+        .and_then(|p| p.get("start_line"))
+        .and_then(Value::as_u64)
+        .map_or(1, |line| line.max(1) as usize);
+    Some(line)
+}
+
+/// Records the JSON pointer and serialized size of every string, and of
+/// every array with more than one entry.
+fn collect(
+    value: &Value,
+    pointer: String,
+    strings: &mut Vec<(String, usize)>,
+    arrays: &mut Vec<(String, usize)>,
+) {
+    match value {
+        Value::String(s) => strings.push((pointer, Value::from(s.as_str()).to_string().len())),
+        Value::Array(entries) => {
+            if entries.len() > 1 {
+                arrays.push((pointer.clone(), value.to_string().len()));
+            }
+            for (i, entry) in entries.iter().enumerate() {
+                collect(entry, format!("{}/{}", pointer, i), strings, arrays);
+            }
+        }
+        Value::Object(fields) => {
+            for (key, field) in fields {
+                let key = key.replace('~', "~0").replace('/', "~1");
+                collect(field, format!("{}/{}", pointer, key), strings, arrays);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Drops trailing entries worth at least `amount` characters and appends a
+/// marker entry in their place.
+fn drop_entries(entries: &mut Vec<Value>, amount: usize) {
+    let total = entries.len();
+    let mut removed = 0;
+    while entries.len() > 1 && removed < amount {
+        if let Some(entry) = entries.pop() {
+            // The entry and its separating comma.
+            removed += entry.to_string().len() + 1;
+        }
+    }
+    let dropped = total - entries.len();
+    entries.push(Value::from(format!(
+        "[truncated: {} more entries dropped. Narrow the request, e.g. a subdirectory or a smaller depth.]",
+        dropped
+    )));
+}
+
+/// Keeps about `keep` characters of `text`, ending on a complete line where
+/// possible, followed by a truncation marker. `first_line` is the file line
+/// `text` starts on, if it holds file lines, so the marker can say where to
+/// read on.
+fn cut_text(text: &str, keep: usize, first_line: Option<usize>) -> String {
+    let mut end = keep.min(text.len());
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    let whole_lines = text[..end].rfind('\n').map(|i| i + 1);
+    let end = whole_lines.unwrap_or(end);
+    let kept = &text[..end];
+    let dropped = text.len() - end;
+
+    let marker = match whole_lines.and(first_line) {
+        Some(first_line) => {
+            let last = first_line + kept.lines().count() - 1;
+            format!(
+                "[truncated after line {}: {} of {} characters dropped. Read further with start_line={} or narrow the request.]",
+                last,
+                dropped,
+                text.len(),
+                last + 1
+            )
+        }
+        None => format!(
+            "[truncated: {} of {} characters dropped. Narrow the request to see the rest.]",
+            dropped,
+            text.len()
+        ),
+    };
+    if kept.is_empty() {
+        marker
+    } else {
+        format!("{}\n{}", kept.trim_end_matches('\n'), marker)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn numbered(lines: usize) -> String {
+        (1..=lines).map(|n| format!("line {}\n", n)).collect()
+    }
+
+    #[test]
+    fn short_outputs_are_unchanged() {
+        assert_eq!(cap_output("hello", "shell.run", 10), "hello");
+    }
+
+    #[test]
+    fn cut_text_ends_on_a_line_and_points_past_it() {
+        let cut = cut_text(&numbered(10), 20, Some(1));
+        assert!(cut.starts_with("line 1\nline 2\n"), "{cut}");
+        assert!(cut.contains("truncated after line 2:"), "{cut}");
+        assert!(cut.contains("start_line=3 "), "{cut}");
+    }
+
+    #[test]
+    fn cut_text_counts_from_the_first_line_of_a_range() {
+        let cut = cut_text(&numbered(10), 20, Some(200));
+        assert!(cut.contains("truncated after line 201:"), "{cut}");
+        assert!(cut.contains("start_line=202 "), "{cut}");
+    }
+
+    #[test]
+    fn cut_text_without_a_line_break_gives_no_line_hint() {
+        let cut = cut_text(&"x".repeat(100), 10, Some(1));
+        assert!(
+            cut.starts_with("xxxxxxxxxx\n[truncated: 90 of 100"),
+            "{cut}"
+        );
+        assert!(!cut.contains("start_line"), "{cut}");
+    }
+
+    #[test]
+    fn cut_text_respects_char_boundaries() {
+        let cut = cut_text("ééééé\nééééé", 3, Some(1));
+        assert!(cut.starts_with("é\n[truncated: "), "{cut}");
+    }
+
+    #[test]
+    fn json_stays_valid_and_hints_from_the_range_start() {
+        let output = json!({"results": [{
+            "path": "src/main.rs",
+            "start_line": 200,
+            "end_line": 1199,
+            "content": numbered(1000),
+        }]})
+        .to_string();
+        let capped = cap_output(&output, READ_TOOL, 2000);
+        assert!(capped.len() <= 2000, "{}", capped.len());
+        let value: Value = serde_json::from_str(&capped).unwrap();
+        let content = value["results"][0]["content"].as_str().unwrap();
+        assert!(content.starts_with("line 1\n"));
+        let kept = content.lines().filter(|l| l.starts_with("line ")).count();
+        let hint = format!("start_line={} ", 200 + kept);
+        assert!(content.contains(&hint), "{content}");
+    }
+
+    #[test]
+    fn long_arrays_lose_trailing_entries() {
+        let entries: Vec<String> = (0..500).map(|n| format!("src/file_{}.rs", n)).collect();
+        let output = json!({"results": entries}).to_string();
+        let capped = cap_output(&output, "crawler.tree", 1000);
+        assert!(capped.len() <= 1000, "{}", capped.len());
+        let value: Value = serde_json::from_str(&capped).unwrap();
+        let results = value["results"].as_array().unwrap();
+        assert_eq!(results[0], "src/file_0.rs");
+        let marker = results.last().unwrap().as_str().unwrap();
+        assert!(marker.starts_with("[truncated:"), "{marker}");
+    }
+
+    #[test]
+    fn plain_text_is_cut_under_the_limit() {
+        let capped = cap_output(&numbered(1000), "shell.run", 500);
+        assert!(capped.len() <= 500, "{}", capped.len());
+        assert!(
+            capped.ends_with("Narrow the request to see the rest.]"),
+            "{capped}"
+        );
+    }
+
+    #[test]
+    fn only_file_contents_get_a_line_hint() {
+        let lines = numbered(1000);
+        let output = json!({"results": [{"path": "src/main.rs", "content": lines}]}).to_string();
+        let capped = cap_output(&output, "crawler.search", 2000);
+        assert!(!capped.contains("start_line"), "{capped}");
+
+        let output = json!({"stdout": lines, "exit_code": 0}).to_string();
+        let capped = cap_output(&output, READ_TOOL, 2000);
+        assert!(!capped.contains("start_line"), "{capped}");
+        assert!(
+            capped.contains("Narrow the request to see the rest."),
+            "{capped}"
+        );
+    }
+}
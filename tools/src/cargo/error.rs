@@ -0,0 +1,16 @@
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// Reasons the Cargo workspace could not be read.
+#[derive(Debug, Error)]
+pub enum CargoError {
+    /// There is no `Cargo.toml` at the repository root.
+    #[error("No Cargo.toml found at '{0}'.")]
+    NoManifest(PathBuf),
+    /// A manifest could not be read.
+    #[error("Unable to read '{0}': {1}")]
+    Read(PathBuf, std::io::Error),
+    /// A manifest is not valid TOML.
+    #[error("Unable to parse '{0}': {1}")]
+    Parse(PathBuf, toml::de::Error),
+}
@@ -0,0 +1,149 @@
+//! `cargo.metadata`: the crates of a Cargo workspace and how they depend on
+//! each other, read straight from the manifests so it works offline and
+//! without resolving the registry.
+
+pub mod error;
+mod workspace;
+
+use crate::{crawler::PathPolicy, Tool};
+use ollama::types::{FunctionDefinition, ToolCall, ToolDefinition};
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use tokio::task;
+
+pub use self::error::CargoError;
+pub use self::workspace::{Dependency, Package, Target, Workspace};
+
+/// Reads the Cargo workspace at a repository root.
+pub struct Cargo {
+    root_path: PathBuf,
+}
+
+impl Cargo {
+    pub fn new<P: AsRef<Path>>(root_path: P) -> Self {
+        let raw = root_path.as_ref().to_path_buf();
+        Cargo {
+            root_path: raw.canonicalize().unwrap_or(raw),
+        }
+    }
+
+    pub async fn workspace(&self) -> Result<Workspace, CargoError> {
+        let root = self.root_path.clone();
+        let policy = PathPolicy::new(&root, false);
+        task::spawn_blocking(move || Workspace::load(&root, &policy))
+            .await
+            .unwrap_or_else(|_| Err(CargoError::NoManifest(self.root_path.clone())))
+    }
+}
+
+impl Tool for Cargo {
+    fn get_tool_defs(&self) -> Vec<ToolDefinition> {
+        vec![ToolDefinition {
+            type_: "function".into(),
+            function: FunctionDefinition {
+                name: "cargo.metadata".into(),
+                description: "Summarizes the Cargo workspace: local packages with their \
+directories, targets (lib, binaries, tests, ...) and features, the dependency graph between \
+local crates, and each package's external dependencies."
+                    .into(),
+                parameters: json!({
+                    "type": "object",
+                    "properties": {
+                        "package": {
+                            "type": "string",
+                            "description": "Only show this package (default: all)"
+                        }
+                    }
+                }),
+            },
+        }]
+    }
+
+    async fn handle_tool_call(&self, call: ToolCall) -> String {
+        let only = call
+            .function
+            .arguments
+            .get("package")
+            .and_then(Value::as_str);
+        let workspace = match self.workspace().await {
+            Ok(workspace) => workspace,
+            Err(e) => return json!({"error": e.to_string()}).to_string(),
+        };
+        if let Some(name) = only {
+            if !workspace.packages.iter().any(|p| p.name == name) {
+                let names = workspace
+                    .packages
+                    .iter()
+                    .map(|p| p.name.as_str())
+                    .collect::<Vec<_>>();
+                return json!({
+                    "error": format!("No local package named '{}'", name),
+                    "packages": names,
+                })
+                .to_string();
+            }
+        }
+
+        let packages = workspace
+            .packages
+            .iter()
+            .filter(|p| only.is_none_or(|name| p.name == name))
+            .map(describe_package)
+            .collect::<Vec<_>>();
+        json!({
+            "workspace_members": workspace.members,
+            "local_dependency_graph": workspace.graph(),
+            "packages": packages,
+        })
+        .to_string()
+    }
+}
+
+fn describe_package(package: &Package) -> Value {
+    let targets = package
+        .targets
+        .iter()
+        .map(|t| format!("{} {} ({})", t.kind, t.name, t.path))
+        .collect::<Vec<_>>();
+    let local = package
+        .dependencies
+        .iter()
+        .filter(|d| d.is_local())
+        .map(|d| {
+            json!({
+                "name": d.name,
+                "path": d.path,
+                "kind": d.kind,
+                "optional": d.optional,
+            })
+        })
+        .collect::<Vec<_>>();
+    let external = package
+        .dependencies
+        .iter()
+        .filter(|d| !d.is_local())
+        .map(|d| {
+            let mut line = format!("{} {}", d.name, d.version.as_deref().unwrap_or("*"));
+            if d.kind != "normal" {
+                line.push_str(&format!(" [{}]", d.kind));
+            }
+            if d.optional {
+                line.push_str(" [optional]");
+            }
+            if let Some(target) = &d.target {
+                line.push_str(&format!(" [{}]", target));
+            }
+            line
+        })
+        .collect::<Vec<_>>();
+    json!({
+        "name": package.name,
+        "version": package.version,
+        "edition": package.edition,
+        "dir": package.dir,
+        "targets": targets,
+        "features": package.features,
+        "local_dependencies": local,
+        "external_dependencies": external,
+    })
+}
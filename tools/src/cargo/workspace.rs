@@ -0,0 +1,508 @@
+use super::error::CargoError;
+use crate::crawler::PathPolicy;
+use globset::GlobBuilder;
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fs,
+    path::{Path, PathBuf},
+};
+use toml::{Table, Value};
+
+const MANIFEST: &str = "Cargo.toml";
+
+/// How deep workspace member globs such as `crates/*` are expanded.
+const MAX_MEMBER_DEPTH: usize = 4;
+
+/// The local crates of a repository, read from their manifests.
+#[derive(Debug, Clone)]
+pub struct Workspace {
+    /// Names of the `[workspace] members`; empty without a workspace.
+    pub members: Vec<String>,
+    /// Every local package: the root package, workspace members and the
+    /// crates they reach through path dependencies.
+    pub packages: Vec<Package>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Package {
+    pub name: String,
+    pub version: Option<String>,
+    pub edition: Option<String>,
+    /// Directory, root-relative (`.` for the root).
+    pub dir: String,
+    pub features: BTreeMap<String, Vec<String>>,
+    pub targets: Vec<Target>,
+    pub dependencies: Vec<Dependency>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Target {
+    /// `lib`, `bin`, `example`, `test` or `bench`.
+    pub kind: &'static str,
+    pub name: String,
+    /// Source file, root-relative.
+    pub path: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Dependency {
+    /// Name of the depended-on package (not the rename, if any).
+    pub name: String,
+    /// `normal`, `dev` or `build`.
+    pub kind: &'static str,
+    pub version: Option<String>,
+    /// Directory of a path dependency, root-relative.
+    pub path: Option<String>,
+    pub optional: bool,
+    /// `cfg(...)` or triple of a target-specific dependency.
+    pub target: Option<String>,
+}
+
+impl Dependency {
+    pub fn is_local(&self) -> bool {
+        self.path.is_some()
+    }
+}
+
+impl Workspace {
+    /// Reads the manifest at `root_path` and every local crate reachable
+    /// from it. Manifests `policy` hides are treated as missing.
+    pub fn load(root_path: &Path, policy: &PathPolicy) -> Result<Self, CargoError> {
+        let root_manifest = root_path.join(MANIFEST);
+        let Some(root_table) = read_manifest(policy, &root_manifest)? else {
+            return Err(CargoError::NoManifest(root_path.to_path_buf()));
+        };
+        let workspace = root_table.get("workspace").and_then(Value::as_table);
+        let inherited = workspace
+            .and_then(|w| w.get("dependencies"))
+            .and_then(Value::as_table)
+            .cloned()
+            .unwrap_or_default();
+
+        let member_dirs = workspace.map_or_else(Vec::new, |w| expand_members(root_path, policy, w));
+        let mut queue: VecDeque<PathBuf> = VecDeque::new();
+        queue.push_back(root_path.to_path_buf());
+        queue.extend(member_dirs.iter().cloned());
+
+        let mut seen = Vec::new();
+        let mut packages = Vec::new();
+        while let Some(dir) = queue.pop_front() {
+            let dir = dir.canonicalize().unwrap_or(dir);
+            if seen.contains(&dir) || !dir.starts_with(root_path) {
+                continue;
+            }
+            seen.push(dir.clone());
+            let Some(table) = read_manifest(policy, &dir.join(MANIFEST))? else {
+                continue;
+            };
+            let Some(package) = read_package(root_path, &dir, &table, &inherited) else {
+                continue;
+            };
+            for dependency in &package.dependencies {
+                if let Some(path) = &dependency.path {
+                    queue.push_back(root_path.join(path));
+                }
+            }
+            packages.push(package);
+        }
+
+        let members = member_dirs
+            .iter()
+            .filter_map(|dir| {
+                let dir = relative(root_path, &dir.canonicalize().ok()?);
+                packages
+                    .iter()
+                    .find(|p| p.dir == dir)
+                    .map(|p| p.name.clone())
+            })
+            .collect();
+        Ok(Workspace { members, packages })
+    }
+
+    /// Local dependency edges: package name to the local packages it uses,
+    /// with the kind for dev and build dependencies.
+    pub fn graph(&self) -> BTreeMap<String, Vec<String>> {
+        self.packages
+            .iter()
+            .map(|package| {
+                let edges = package
+                    .dependencies
+                    .iter()
+                    .filter(|d| d.is_local())
+                    .map(|d| match d.kind {
+                        "normal" => d.name.clone(),
+                        kind => format!("{} ({})", d.name, kind),
+                    })
+                    .collect();
+                (package.name.clone(), edges)
+            })
+            .collect()
+    }
+}
+
+/// The manifest at `path`, or `None` if it is missing or hidden by `policy`.
+fn read_manifest(policy: &PathPolicy, path: &Path) -> Result<Option<Table>, CargoError> {
+    match path.canonicalize() {
+        Ok(canonical) if canonical.is_file() && policy.allows(&canonical) => {}
+        _ => return Ok(None),
+    }
+    let text = fs::read_to_string(path).map_err(|e| CargoError::Read(path.to_path_buf(), e))?;
+    text.parse::<Table>()
+        .map(Some)
+        .map_err(|e| CargoError::Parse(path.to_path_buf(), e))
+}
+
+/// Directories named by `[workspace] members`, minus `exclude`.
+fn expand_members(root_path: &Path, policy: &PathPolicy, workspace: &Table) -> Vec<PathBuf> {
+    let strings = |key: &str| {
+        workspace
+            .get(key)
+            .and_then(Value::as_array)
+            .map(|a| a.iter().filter_map(Value::as_str).collect::<Vec<_>>())
+            .unwrap_or_default()
+    };
+    let excluded = strings("exclude");
+    let mut dirs = Vec::new();
+    for pattern in strings("members") {
+        let pattern = pattern.trim_end_matches('/');
+        if !pattern.contains(['*', '?', '[']) {
+            dirs.push(root_path.join(pattern));
+            continue;
+        }
+        let Ok(glob) = GlobBuilder::new(pattern).literal_separator(true).build() else {
+            continue;
+        };
+        let matcher = glob.compile_matcher();
+        let found = policy
+            .walker(root_path)
+            .max_depth(Some(MAX_MEMBER_DEPTH))
+            .build()
+            .flatten()
+            .filter(|e| e.depth() > 0 && e.file_type().is_some_and(|t| t.is_dir()))
+            .filter(|e| matcher.is_match(relative(root_path, e.path())))
+            .filter(|e| e.path().join(MANIFEST).is_file())
+            .map(|e| e.into_path());
+        dirs.extend(found);
+    }
+    dirs.retain(|dir| !excluded.contains(&relative(root_path, dir).as_str()));
+    dirs.sort();
+    dirs
+}
+
+fn read_package(root_path: &Path, dir: &Path, table: &Table, inherited: &Table) -> Option<Package> {
+    let package = table.get("package")?.as_table()?;
+    let name = package.get("name")?.as_str()?.to_string();
+    let field = |key: &str| match package.get(key) {
+        Some(Value::String(s)) => Some(s.clone()),
+        Some(Value::Table(t)) if t.get("workspace").and_then(Value::as_bool) == Some(true) => {
+            Some("workspace".to_string())
+        }
+        _ => None,
+    };
+
+    let features = table
+        .get("features")
+        .and_then(Value::as_table)
+        .map(|features| {
+            features
+                .iter()
+                .map(|(name, enables)| {
+                    let enables = enables
+                        .as_array()
+                        .map(|a| {
+                            a.iter()
+                                .filter_map(Value::as_str)
+                                .map(String::from)
+                                .collect()
+                        })
+                        .unwrap_or_default();
+                    (name.clone(), enables)
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut dependencies = Vec::new();
+    let mut add = |deps: Option<&Value>, kind: &'static str, target: Option<&str>| {
+        let Some(deps) = deps.and_then(Value::as_table) else {
+            return;
+        };
+        for (key, spec) in deps {
+            dependencies.push(read_dependency(
+                root_path, dir, key, spec, kind, target, inherited,
+            ));
+        }
+    };
+    for (section, kind) in [
+        ("dependencies", "normal"),
+        ("dev-dependencies", "dev"),
+        ("build-dependencies", "build"),
+    ] {
+        add(table.get(section), kind, None);
+        if let Some(targets) = table.get("target").and_then(Value::as_table) {
+            for (cfg, target) in targets {
+                add(target.get(section), kind, Some(cfg));
+            }
+        }
+    }
+
+    Some(Package {
+        targets: read_targets(root_path, dir, &name, table),
+        name,
+        version: field("version"),
+        edition: field("edition"),
+        dir: relative(root_path, dir),
+        features,
+        dependencies,
+    })
+}
+
+fn read_dependency(
+    root_path: &Path,
+    dir: &Path,
+    key: &str,
+    spec: &Value,
+    kind: &'static str,
+    target: Option<&str>,
+    inherited: &Table,
+) -> Dependency {
+    let mut spec = spec.clone();
+    // `foo = { workspace = true }` takes its source from `[workspace.dependencies]`,
+    // whose paths are relative to the root.
+    let mut base = dir;
+    if spec.get("workspace").and_then(Value::as_bool) == Some(true) {
+        if let Some(shared) = inherited.get(key) {
+            let optional = spec.get("optional").cloned();
+            spec = shared.clone();
+            if let (Some(optional), Some(table)) = (optional, spec.as_table_mut()) {
+                table.insert("optional".to_string(), optional);
+            }
+            base = root_path;
+        }
+    }
+
+    let get = |field: &str| spec.get(field).and_then(Value::as_str).map(String::from);
+    let version = match &spec {
+        Value::String(version) => Some(version.clone()),
+        _ => get("version"),
+    };
+    let path = get("path").map(|path| {
+        let full = base.join(path);
+        relative(root_path, &full.canonicalize().unwrap_or(full))
+    });
+    Dependency {
+        name: get("package").unwrap_or_else(|| key.to_string()),
+        kind,
+        version,
+        path,
+        optional: spec.get("optional").and_then(Value::as_bool) == Some(true),
+        target: target.map(String::from),
+    }
+}
+
+/// Declared targets plus the ones Cargo discovers from the usual layout.
+fn read_targets(root_path: &Path, dir: &Path, package: &str, table: &Table) -> Vec<Target> {
+    let mut targets = Vec::new();
+    let mut push = |kind: &'static str, name: String, path: PathBuf| {
+        if !targets
+            .iter()
+            .any(|t: &Target| t.kind == kind && t.name == name)
+        {
+            targets.push(Target {
+                kind,
+                name,
+                path: relative(root_path, &path),
+            });
+        }
+    };
+
+    let lib = table.get("lib").and_then(Value::as_table);
+    let lib_path = lib
+        .and_then(|l| l.get("path"))
+        .and_then(Value::as_str)
+        .map_or_else(|| dir.join("src/lib.rs"), |p| dir.join(p));
+    if lib.is_some() || lib_path.is_file() {
+        let name = lib
+            .and_then(|l| l.get("name"))
+            .and_then(Value::as_str)
+            .map_or_else(|| package.replace('-', "_"), String::from);
+        push("lib", name, lib_path);
+    }
+
+    for (key, kind, folder) in [
+        ("bin", "bin", "src/bin"),
+        ("example", "example", "examples"),
+        ("test", "test", "tests"),
+        ("bench", "bench", "benches"),
+    ] {
+        let declared = table.get(key).and_then(Value::as_array);
+        for entry in declared.into_iter().flatten().filter_map(Value::as_table) {
+            let Some(name) = entry.get("name").and_then(Value::as_str) else {
+                continue;
+            };
+            let path = entry.get("path").and_then(Value::as_str).map_or_else(
+                || dir.join(folder).join(format!("{}.rs", name)),
+                |p| dir.join(p),
+            );
+            push(kind, name.to_string(), path);
+        }
+        if kind == "bin" && dir.join("src/main.rs").is_file() {
+            push("bin", package.to_string(), dir.join("src/main.rs"));
+        }
+        for (name, path) in discover(&dir.join(folder)) {
+            push(kind, name, path);
+        }
+    }
+    targets
+}
+
+/// `folder/*.rs` and `folder/*/main.rs`, as Cargo's auto-discovery finds them.
+fn discover(folder: &Path) -> Vec<(String, PathBuf)> {
+    let Ok(entries) = fs::read_dir(folder) else {
+        return Vec::new();
+    };
+    let mut found: Vec<_> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            if path.is_dir() {
+                let main = path.join("main.rs");
+                let name = entry.file_name().to_str()?.to_string();
+                return main.is_file().then_some((name, main));
+            }
+            let name = path.file_stem()?.to_str()?.to_string();
+            (path.extension()? == "rs").then_some((name, path))
+        })
+        .collect();
+    found.sort();
+    found
+}
+
+/// Root-relative path with `/` separators; `.` for the root itself.
+fn relative(root_path: &Path, path: &Path) -> String {
+    match path.strip_prefix(root_path) {
+        Ok(rel) if rel.as_os_str().is_empty() => ".".to_string(),
+        Ok(rel) => rel.to_string_lossy().replace('\\', "/"),
+        Err(_) => path.to_string_lossy().into_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write(root: &Path, rel: &str, content: &str) {
+        let path = root.join(rel);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    /// A workspace with `crates/*` members, one of them excluded, and an
+    /// app reaching a crate outside the members through a path dependency.
+    fn fixture() -> (TempDir, PathBuf) {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        write(
+            &root,
+            "Cargo.toml",
+            r#"
+[workspace]
+members = ["crates/*"]
+exclude = ["crates/scratch"]
+
+[workspace.package]
+version = "0.3.0"
+
+[workspace.dependencies]
+serde = "1.0"
+core = { path = "crates/core" }
+"#,
+        );
+        write(
+            &root,
+            "crates/app/Cargo.toml",
+            r#"
+[package]
+name = "app"
+version.workspace = true
+edition = "2021"
+
+[dependencies]
+core = { workspace = true }
+serde = { workspace = true }
+util = { path = "../../vendor/util" }
+
+[dev-dependencies]
+testkit = { path = "../testkit", package = "test-kit" }
+"#,
+        );
+        write(&root, "crates/app/src/main.rs", "");
+        write(
+            &root,
+            "crates/core/Cargo.toml",
+            "[package]\nname = \"core\"\nversion = \"0.1.0\"\n",
+        );
+        write(&root, "crates/core/src/lib.rs", "");
+        write(
+            &root,
+            "crates/testkit/Cargo.toml",
+            "[package]\nname = \"test-kit\"\n",
+        );
+        write(
+            &root,
+            "crates/scratch/Cargo.toml",
+            "[package]\nname = \"scratch\"\n",
+        );
+        write(
+            &root,
+            "vendor/util/Cargo.toml",
+            "[package]\nname = \"util\"\n",
+        );
+        (dir, root)
+    }
+
+    fn load(root: &Path) -> Workspace {
+        Workspace::load(root, &PathPolicy::new(root, false)).unwrap()
+    }
+
+    #[test]
+    fn glob_members_are_expanded_minus_excluded_ones() {
+        let (_dir, root) = fixture();
+        let workspace = load(&root);
+        assert_eq!(workspace.members, ["app", "core", "test-kit"]);
+        let mut names: Vec<_> = workspace.packages.iter().map(|p| p.name.as_str()).collect();
+        names.sort();
+        // `util` is no member but is reached through a path dependency.
+        assert_eq!(names, ["app", "core", "test-kit", "util"]);
+    }
+
+    #[test]
+    fn inherited_fields_and_dependencies_are_resolved() {
+        let (_dir, root) = fixture();
+        let workspace = load(&root);
+        let app = workspace.packages.iter().find(|p| p.name == "app").unwrap();
+        assert_eq!(app.version.as_deref(), Some("workspace"));
+        assert_eq!(app.dir, "crates/app");
+        let dependency = |name: &str| app.dependencies.iter().find(|d| d.name == name).unwrap();
+        assert_eq!(dependency("serde").version.as_deref(), Some("1.0"));
+        assert!(!dependency("serde").is_local());
+        // Paths under `[workspace.dependencies]` are relative to the root.
+        assert_eq!(dependency("core").path.as_deref(), Some("crates/core"));
+        assert_eq!(
+            dependency("test-kit").path.as_deref(),
+            Some("crates/testkit")
+        );
+        assert_eq!(dependency("test-kit").kind, "dev");
+        assert_eq!(app.targets[0].path, "crates/app/src/main.rs");
+    }
+
+    #[test]
+    fn the_graph_holds_local_edges_only() {
+        let (_dir, root) = fixture();
+        let graph = load(&root).graph();
+        assert_eq!(graph["app"], ["core", "util", "test-kit (dev)"]);
+        assert!(graph["core"].is_empty());
+        assert!(!graph.contains_key("scratch"));
+    }
+}
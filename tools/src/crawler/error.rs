@@ -24,6 +24,12 @@ pub enum CrawlerError {
     /// An error occurred during path canonicalization.
     #[error("Path canonicalization error: {0}")]
     Canonicalization(std::io::Error),
+    /// A `crawler.glob` pattern did not compile.
+    #[error("Invalid glob pattern '{0}': {1}")]
+    InvalidPattern(String, String),
+    /// A `modified_within` age was not like `30m`, `12h`, `1d` or `2w`.
+    #[error("Invalid age '{0}'; use a number with s, m, h, d or w, e.g. \"1d\".")]
+    InvalidAge(String),
     /// Any other unforeseen error.
     #[error("An unexpected error occurred: {0}")]
     Other(String),
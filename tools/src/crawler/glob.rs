@@ -0,0 +1,176 @@
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use super::{
+    error::CrawlerError,
+    metadata::{FileInfo, SortBy},
+    policy::PathPolicy,
+};
+
+/// Which files `crawler.glob` returns and in what order.
+#[derive(Debug, Clone)]
+pub struct GlobQuery {
+    include: GlobSet,
+    exclude: GlobSet,
+    pub sort: SortBy,
+    pub limit: usize,
+    /// Only files modified at most this long ago.
+    pub modified_within: Option<Duration>,
+}
+
+/// A matching file with its metadata.
+#[derive(Debug, Clone)]
+pub struct GlobMatch {
+    /// Root-relative, with `/` separators.
+    pub path: String,
+    pub info: FileInfo,
+}
+
+impl GlobQuery {
+    /// Compiles `patterns`, matched against root-relative paths. A leading
+    /// `!` turns a pattern into an exclusion; with only exclusions, every
+    /// file not excluded matches.
+    pub fn new(patterns: &[&str], sort: SortBy, limit: usize) -> Result<Self, CrawlerError> {
+        let mut include = GlobSetBuilder::new();
+        let mut exclude = GlobSetBuilder::new();
+        let mut has_include = false;
+        for &pattern in patterns {
+            let (set, pattern) = match pattern.strip_prefix('!') {
+                Some(negated) => (&mut exclude, negated),
+                None => {
+                    has_include = true;
+                    (&mut include, pattern)
+                }
+            };
+            let glob = GlobBuilder::new(pattern.trim_start_matches("./"))
+                .literal_separator(true)
+                .build()
+                .map_err(|e| CrawlerError::InvalidPattern(pattern.to_string(), e.to_string()))?;
+            set.add(glob);
+        }
+        if !has_include {
+            include.add(GlobBuilder::new("**").build().expect("valid glob"));
+        }
+        let build = |set: GlobSetBuilder| {
+            set.build()
+                .map_err(|e| CrawlerError::InvalidPattern(patterns.join(" "), e.to_string()))
+        };
+        Ok(GlobQuery {
+            include: build(include)?,
+            exclude: build(exclude)?,
+            sort,
+            limit,
+            modified_within: None,
+        })
+    }
+
+    pub fn modified_within(mut self, age: Option<Duration>) -> Self {
+        self.modified_within = age;
+        self
+    }
+
+    fn is_match(&self, rel: &str) -> bool {
+        self.include.is_match(rel) && !self.exclude.is_match(rel)
+    }
+}
+
+/// Files under `root_path` allowed by `policy` and matching `query`, sorted
+/// and cut to `query.limit`, plus how many matched in total.
+pub fn find(policy: &PathPolicy, root_path: &Path, query: &GlobQuery) -> (Vec<GlobMatch>, usize) {
+    let cutoff = query
+        .modified_within
+        .and_then(|age| SystemTime::now().checked_sub(age));
+
+    // Stat everything first; lines are only counted for what is returned.
+    let mut found: Vec<(String, PathBuf, u64, Option<SystemTime>)> = policy
+        .walker(root_path)
+        .build()
+        .flatten()
+        .filter(|e| e.file_type().is_some_and(|ft| ft.is_file()))
+        .filter_map(|e| {
+            let rel = e.path().strip_prefix(root_path).ok()?;
+            let rel = rel.to_str()?.replace('\\', "/");
+            if !query.is_match(&rel) {
+                return None;
+            }
+            let meta = e.metadata().ok()?;
+            let modified = meta.modified().ok();
+            if let Some(cutoff) = cutoff {
+                if modified.is_none_or(|m| m < cutoff) {
+                    return None;
+                }
+            }
+            Some((rel, e.into_path(), meta.len(), modified))
+        })
+        .collect();
+
+    match query.sort {
+        SortBy::Name => found.sort_by(|a, b| a.0.cmp(&b.0)),
+        SortBy::Size => found.sort_by(|a, b| b.2.cmp(&a.2).then_with(|| a.0.cmp(&b.0))),
+        SortBy::Modified => found.sort_by(|a, b| b.3.cmp(&a.3).then_with(|| a.0.cmp(&b.0))),
+    }
+
+    let total = found.len();
+    let matches = found
+        .into_iter()
+        .take(query.limit)
+        .filter_map(|(path, full, _, _)| {
+            Some(GlobMatch {
+                path,
+                info: FileInfo::read(&full)?,
+            })
+        })
+        .collect();
+    (matches, total)
+}
+
+/// Parses an age like `30m`, `12h`, `1d` or `2w`; a bare number is seconds.
+pub fn parse_age(age: &str) -> Result<Duration, CrawlerError> {
+    let age = age.trim();
+    let split = age.find(|c: char| !c.is_ascii_digit()).unwrap_or(age.len());
+    let (number, unit) = age.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| CrawlerError::InvalidAge(age.to_string()))?;
+    let seconds = match unit.trim() {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(CrawlerError::InvalidAge(age.to_string())),
+    };
+    number
+        .checked_mul(seconds)
+        .map(Duration::from_secs)
+        .ok_or_else(|| CrawlerError::InvalidAge(age.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ages_are_parsed_with_their_unit() {
+        assert_eq!(parse_age("30").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_age("30m").unwrap(), Duration::from_secs(30 * 60));
+        assert_eq!(
+            parse_age(" 2w ").unwrap(),
+            Duration::from_secs(2 * 7 * 86_400)
+        );
+        assert!(parse_age("1y").is_err());
+        assert!(parse_age("h").is_err());
+    }
+
+    #[test]
+    fn overflowing_ages_are_rejected() {
+        assert!(matches!(
+            parse_age(&format!("{}w", u64::MAX / 2)),
+            Err(CrawlerError::InvalidAge(_))
+        ));
+        assert!(parse_age(&format!("{}s", u64::MAX)).is_ok());
+    }
+}
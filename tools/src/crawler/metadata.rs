@@ -0,0 +1,137 @@
+use std::{fs, path::Path, time::SystemTime};
+
+/// Files larger than this are not read to count their lines.
+const MAX_LINE_COUNT_BYTES: u64 = 1024 * 1024;
+
+/// How much of a file is checked for NUL bytes to tell binary from text.
+const BINARY_SNIFF_BYTES: usize = 8000;
+
+/// Order of crawler results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortBy {
+    /// Alphabetically; in trees, directories come first.
+    Name,
+    /// Largest first; in trees, directories come first.
+    Size,
+    /// Most recently modified first.
+    Modified,
+}
+
+impl SortBy {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "name" | "path" => Some(SortBy::Name),
+            "size" => Some(SortBy::Size),
+            "mtime" | "modified" => Some(SortBy::Modified),
+            _ => None,
+        }
+    }
+}
+
+/// What the crawler reports about a file besides its path.
+#[derive(Debug, Clone)]
+pub struct FileInfo {
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+    /// `None` for binary files and files too large to count.
+    pub lines: Option<usize>,
+    pub binary: bool,
+    pub language: Option<&'static str>,
+}
+
+impl FileInfo {
+    /// Stats and, if small enough, reads `path`. `None` if it is not a
+    /// readable file.
+    pub fn read(path: &Path) -> Option<Self> {
+        let meta = fs::metadata(path).ok()?;
+        if !meta.is_file() {
+            return None;
+        }
+        let size = meta.len();
+        let (lines, binary) = if size <= MAX_LINE_COUNT_BYTES {
+            match fs::read(path) {
+                Ok(bytes) if is_binary(&bytes) => (None, true),
+                Ok(bytes) => (Some(count_lines(&bytes)), false),
+                Err(_) => (None, false),
+            }
+        } else {
+            (None, false)
+        };
+        Some(FileInfo {
+            size,
+            modified: meta.modified().ok(),
+            lines,
+            binary,
+            language: language(path),
+        })
+    }
+
+    /// Compact summary, e.g. `1.2 KB, 40 lines, Rust`.
+    pub fn describe(&self) -> String {
+        let mut parts = vec![human_size(self.size)];
+        match self.lines {
+            Some(1) => parts.push("1 line".to_string()),
+            Some(lines) => parts.push(format!("{} lines", lines)),
+            None if self.binary => parts.push("binary".to_string()),
+            None => {}
+        }
+        parts.extend(self.language.map(str::to_string));
+        parts.join(", ")
+    }
+}
+
+fn is_binary(bytes: &[u8]) -> bool {
+    bytes[..bytes.len().min(BINARY_SNIFF_BYTES)].contains(&0)
+}
+
+fn count_lines(bytes: &[u8]) -> usize {
+    let newlines = bytes.iter().filter(|&&b| b == b'\n').count();
+    newlines + usize::from(bytes.last().is_some_and(|&b| b != b'\n'))
+}
+
+/// `512 B`, `1.2 KB`, `3.4 MB`.
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["KB", "MB", "GB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// Language of a file, guessed from its name.
+pub fn language(path: &Path) -> Option<&'static str> {
+    let name = path.file_name()?.to_str()?;
+    match name {
+        "Cargo.lock" => return Some("TOML"),
+        "Dockerfile" => return Some("Dockerfile"),
+        "Makefile" => return Some("Makefile"),
+        _ => {}
+    }
+    let language = match path.extension()?.to_str()? {
+        "rs" => "Rust",
+        "toml" => "TOML",
+        "md" => "Markdown",
+        "json" => "JSON",
+        "yml" | "yaml" => "YAML",
+        "py" => "Python",
+        "js" | "mjs" | "cjs" => "JavaScript",
+        "ts" | "tsx" => "TypeScript",
+        "go" => "Go",
+        "c" | "h" => "C",
+        "cc" | "cpp" | "hpp" => "C++",
+        "java" => "Java",
+        "sh" | "bash" => "Shell",
+        "html" => "HTML",
+        "css" => "CSS",
+        "sql" => "SQL",
+        "proto" => "Protobuf",
+        _ => return None,
+    };
+    Some(language)
+}
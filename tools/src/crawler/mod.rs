@@ -3,18 +3,54 @@
 //! and file content reading.
 
 pub mod error;
+mod glob;
+mod metadata;
+mod policy;
 mod tool;
+mod tree;
 
 use crate::Tool;
+use chrono::{DateTime, Local, SecondsFormat};
 use ollama::types::{FunctionDefinition, ToolCall, ToolDefinition};
 use serde_json::{json, Value};
-use std::env;
 
 pub use self::error::CrawlerError;
+pub use self::glob::{GlobMatch, GlobQuery};
+pub use self::metadata::{FileInfo, SortBy};
+pub use self::policy::{PathPolicy, ALWAYS_EXCLUDED, IGNORE_FILE};
 pub use self::tool::Crawler;
+pub use self::tree::TreeOptions;
+
+/// Error for paths the crawler may not see.
+const NOT_VISIBLE: &str = "Missing, outside the repository, or excluded by .gitignore, \
+.viktorignore or the hidden-file rules";
+
+/// Error for files whose contents must never reach the model.
+const SENSITIVE: &str = "Sensitive file; its contents are never sent to the model";
+
+/// Defaults of `crawler.tree`.
+const TREE_DEPTH: usize = 2;
+const TREE_MAX_ENTRIES: usize = 50;
+
+/// Default result limit of `crawler.glob`.
+const GLOB_LIMIT: usize = 100;
+
+impl Crawler {
+    /// Adds the `include_hidden` parameter to a tool schema when the
+    /// configuration lets the model ask for hidden files.
+    fn with_hidden_option(&self, mut parameters: Value) -> Value {
+        if self.hidden_allowed() {
+            parameters["properties"]["include_hidden"] = json!({
+                "type": "boolean",
+                "description": "Also consider hidden files and directories (default: false)"
+            });
+        }
+        parameters
+    }
+}
 
 impl Tool for Crawler {
-    fn get_tool_defs() -> Vec<ToolDefinition> {
+    fn get_tool_defs(&self) -> Vec<ToolDefinition> {
         let fuzzy_search_tool = ToolDefinition {
             type_: "function".into(),
             function: FunctionDefinition {
@@ -22,7 +58,7 @@ impl Tool for Crawler {
                 description: "Recursively searches the codebase for files/directories \
 whose paths fuzzy-match any of the provided query strings."
                     .into(),
-                parameters: json!({
+                parameters: self.with_hidden_option(json!({
                     "type": "object",
                     "properties": {
                         "queries": {
@@ -32,7 +68,7 @@ whose paths fuzzy-match any of the provided query strings."
                         }
                     },
                     "required": ["queries"]
-                }),
+                })),
             },
         };
 
@@ -40,18 +76,28 @@ whose paths fuzzy-match any of the provided query strings."
             type_: "function".into(),
             function: FunctionDefinition {
                 name: "crawler.read_file_contents".into(),
-                description: "Reads the textual content of specified file paths.".into(),
-                parameters: json!({
+                description: "Reads the textual content of specified file paths, optionally only a range of lines.".into(),
+                parameters: self.with_hidden_option(json!({
                     "type": "object",
                     "properties": {
                         "paths": {
                             "type": "array",
                             "items": { "type": "string" },
                             "description": "List of file paths to read"
+                        },
+                        "start_line": {
+                            "type": "integer",
+                            "description": "First line to read, counting from 1 (default: 1)",
+                            "minimum": 1
+                        },
+                        "end_line": {
+                            "type": "integer",
+                            "description": "Last line to read, inclusive (default: end of file)",
+                            "minimum": 1
                         }
                     },
                     "required": ["paths"]
-                }),
+                })),
             },
         };
 
@@ -60,7 +106,7 @@ whose paths fuzzy-match any of the provided query strings."
             function: FunctionDefinition {
                 name: "crawler.list_directory_contents".into(),
                 description: "Recursively lists files and directories up to a given depth.".into(),
-                parameters: json!({
+                parameters: self.with_hidden_option(json!({
                     "type": "object",
                     "properties": {
                         "path": {
@@ -75,14 +121,92 @@ whose paths fuzzy-match any of the provided query strings."
                         }
                     },
                     "required": ["path"]
-                }),
+                })),
+            },
+        };
+
+        let tree_tool = ToolDefinition {
+            type_: "function".into(),
+            function: FunctionDefinition {
+                name: "crawler.tree".into(),
+                description: "Shows a directory as a compact indented tree with root-relative \
+names, file sizes, line counts and languages. Directories beyond the depth show how many entries they hold. \
+Prefer this over list_directory_contents to get an overview."
+                    .into(),
+                parameters: self.with_hidden_option(json!({
+                    "type": "object",
+                    "properties": {
+                        "path": {
+                            "type": "string",
+                            "description": "Directory to show, relative to the repository root (default: \".\")"
+                        },
+                        "depth": {
+                            "type": "integer",
+                            "description": format!("How many levels below the first to expand (default: {})", TREE_DEPTH),
+                            "minimum": 0
+                        },
+                        "sort": {
+                            "type": "string",
+                            "enum": ["name", "size", "mtime"],
+                            "description": "Order within each directory (default: name)"
+                        },
+                        "max_entries": {
+                            "type": "integer",
+                            "description": format!("Entries shown per directory (default: {})", TREE_MAX_ENTRIES),
+                            "minimum": 1
+                        }
+                    }
+                })),
+            },
+        };
+
+        let glob_tool = ToolDefinition {
+            type_: "function".into(),
+            function: FunctionDefinition {
+                name: "crawler.glob".into(),
+                description: "Finds files whose root-relative paths match glob patterns and returns \
+them with size, modification time and line count. Use it for exact structural queries such as \
+\"**/Cargo.toml\" or recently changed files; use fuzzy_search_paths when you only know part of a name."
+                    .into(),
+                parameters: self.with_hidden_option(json!({
+                    "type": "object",
+                    "properties": {
+                        "patterns": {
+                            "type": "array",
+                            "items": { "type": "string" },
+                            "description": "Glob patterns such as \"src/**/*.rs\"; prefix with ! to exclude, \
+e.g. \"!**/tests/**\". * does not cross directories, ** does."
+                        },
+                        "sort": {
+                            "type": "string",
+                            "enum": ["path", "size", "mtime"],
+                            "description": "Order of the results: path, largest first or newest first (default: path)"
+                        },
+                        "limit": {
+                            "type": "integer",
+                            "description": format!("Maximum number of results (default: {})", GLOB_LIMIT),
+                            "minimum": 1
+                        },
+                        "modified_within": {
+                            "type": "string",
+                            "description": "Only files modified within this long, e.g. \"30m\", \"12h\", \"1d\", \"2w\""
+                        }
+                    },
+                    "required": ["patterns"]
+                })),
             },
         };
 
-        vec![fuzzy_search_tool, read_file_tool, list_dir_tool]
+        vec![
+            fuzzy_search_tool,
+            read_file_tool,
+            list_dir_tool,
+            tree_tool,
+            glob_tool,
+        ]
     }
 
-    async fn handle_tool_call(call: ToolCall) -> String {
+    async fn handle_tool_call(&self, call: ToolCall) -> String {
         let args: Value = call.function.arguments;
         let name = call
             .function
@@ -90,8 +214,11 @@ whose paths fuzzy-match any of the provided query strings."
             .strip_prefix("crawler.")
             .unwrap_or(&call.function.name);
 
-        let cwd = env::current_dir().unwrap_or_else(|_| ".".into());
-        let crawler = Crawler::new(&cwd).await;
+        let include_hidden = args
+            .get("include_hidden")
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+        let crawler = self.including_hidden(include_hidden);
 
         match name {
             "fuzzy_search_paths" => {
@@ -101,7 +228,7 @@ whose paths fuzzy-match any of the provided query strings."
                     .map(|arr| arr.iter().filter_map(Value::as_str).collect::<Vec<_>>())
                     .unwrap_or_default();
 
-                let results = crawler.fuzzy_search_paths(&queries);
+                let results = crawler.fuzzy_search_paths(&queries).await;
                 let entries = results
                     .into_iter()
                     .map(|(score, path)| {
@@ -122,12 +249,49 @@ whose paths fuzzy-match any of the provided query strings."
                     .map(|arr| arr.iter().filter_map(Value::as_str).collect::<Vec<_>>())
                     .unwrap_or_default();
 
+                let start = args.get("start_line").and_then(Value::as_u64);
+                let end = args.get("end_line").and_then(Value::as_u64);
+
                 let mut results = Vec::with_capacity(paths.len());
                 for &p in &paths {
+                    if !crawler.allows(p) {
+                        results.push(json!({
+                            "path": p,
+                            "error": NOT_VISIBLE
+                        }));
+                        continue;
+                    }
+                    if crawler.is_sensitive(p) {
+                        results.push(json!({
+                            "path": p,
+                            "error": SENSITIVE
+                        }));
+                        continue;
+                    }
                     let content = crawler.read_file_contents(p).await;
+                    if start.is_none() && end.is_none() {
+                        results.push(json!({
+                            "path": p,
+                            "content": content
+                        }));
+                        continue;
+                    }
+
+                    let total = content.lines().count();
+                    let first = start.unwrap_or(1).max(1) as usize;
+                    let last = end.map_or(total, |e| (e as usize).min(total));
+                    let lines = content
+                        .lines()
+                        .skip(first - 1)
+                        .take((last + 1).saturating_sub(first))
+                        .collect::<Vec<_>>()
+                        .join("\n");
                     results.push(json!({
                         "path": p,
-                        "content": content
+                        "start_line": first,
+                        "end_line": last,
+                        "total_lines": total,
+                        "content": lines
                     }));
                 }
 
@@ -138,6 +302,13 @@ whose paths fuzzy-match any of the provided query strings."
                 let path = args.get("path").and_then(Value::as_str).unwrap_or(".");
                 let depth = args.get("depth").and_then(Value::as_u64).unwrap_or(0) as usize;
 
+                if !crawler.allows(path) {
+                    return json!({
+                        "path": path,
+                        "error": NOT_VISIBLE
+                    })
+                    .to_string();
+                }
                 let entries = crawler.list_directory_contents(path, depth).await;
                 let paths = entries
                     .into_iter()
@@ -147,6 +318,83 @@ whose paths fuzzy-match any of the provided query strings."
                 json!({ "entries": paths }).to_string()
             }
 
+            "tree" => {
+                let path = args.get("path").and_then(Value::as_str).unwrap_or(".");
+                let sort = args.get("sort").and_then(Value::as_str).unwrap_or("name");
+                let Some(sort) = SortBy::parse(sort) else {
+                    return json!({"error": format!("Unknown sort '{}': use name, size or mtime", sort)})
+                        .to_string();
+                };
+                let options = TreeOptions {
+                    depth: args
+                        .get("depth")
+                        .and_then(Value::as_u64)
+                        .map_or(TREE_DEPTH, |d| d as usize),
+                    sort,
+                    max_entries: args
+                        .get("max_entries")
+                        .and_then(Value::as_u64)
+                        .map_or(TREE_MAX_ENTRIES, |n| (n as usize).max(1)),
+                };
+
+                match crawler.tree(path, options).await {
+                    Some(tree) => json!({ "tree": tree }).to_string(),
+                    None => json!({
+                        "path": path,
+                        "error": NOT_VISIBLE
+                    })
+                    .to_string(),
+                }
+            }
+
+            "glob" => {
+                let patterns = args
+                    .get("patterns")
+                    .and_then(Value::as_array)
+                    .map(|arr| arr.iter().filter_map(Value::as_str).collect::<Vec<_>>())
+                    .unwrap_or_default();
+                let sort = args.get("sort").and_then(Value::as_str).unwrap_or("path");
+                let Some(sort) = SortBy::parse(sort) else {
+                    return json!({"error": format!("Unknown sort '{}': use path, size or mtime", sort)})
+                        .to_string();
+                };
+                let limit = args
+                    .get("limit")
+                    .and_then(Value::as_u64)
+                    .map_or(GLOB_LIMIT, |n| (n as usize).max(1));
+                let age = match args.get("modified_within").and_then(Value::as_str) {
+                    Some(age) => match glob::parse_age(age) {
+                        Ok(age) => Some(age),
+                        Err(e) => return json!({"error": e.to_string()}).to_string(),
+                    },
+                    None => None,
+                };
+                let query = match GlobQuery::new(&patterns, sort, limit) {
+                    Ok(query) => query.modified_within(age),
+                    Err(e) => return json!({"error": e.to_string()}).to_string(),
+                };
+
+                let (matches, total) = crawler.glob(query).await;
+                let results = matches
+                    .into_iter()
+                    .map(|m| {
+                        json!({
+                            "path": m.path,
+                            "size": m.info.size,
+                            "modified": m.info.modified.map(|t| DateTime::<Local>::from(t).to_rfc3339_opts(SecondsFormat::Secs, false)),
+                            "lines": m.info.lines,
+                        })
+                    })
+                    .collect::<Vec<_>>();
+
+                json!({
+                    "truncated": total > results.len(),
+                    "total": total,
+                    "results": results,
+                })
+                .to_string()
+            }
+
             other => {
                 eprintln!("⚠️ Unknown tool called: {}", other);
                 json!({}).to_string()
@@ -0,0 +1,221 @@
+use globset::GlobSet;
+use ignore::{
+    gitignore::{Gitignore, GitignoreBuilder},
+    Match, WalkBuilder,
+};
+use std::{
+    ffi::OsStr,
+    path::{Component, Path, PathBuf},
+};
+
+/// Directories that are never walked or read, whatever the ignore files say.
+pub const ALWAYS_EXCLUDED: &[&str] = &[".git", "target", "node_modules"];
+
+/// Project-specific ignore file, in gitignore syntax. It takes precedence
+/// over `.ignore` and `.gitignore` files.
+pub const IGNORE_FILE: &str = ".viktorignore";
+
+/// Which paths under the root the crawler may see: everything except paths
+/// ignored by `.viktorignore`, `.ignore`, `.gitignore`, `.git/info/exclude`
+/// or the global git excludes, the always-excluded directories and, unless
+/// `include_hidden` is set, dotfiles.
+///
+/// Every tool that reads repository files goes through it, so a file hidden
+/// from listings cannot be read by naming it either. Sensitive files may be
+/// listed but never read.
+#[derive(Debug, Clone)]
+pub struct PathPolicy {
+    root_path: PathBuf,
+    include_hidden: bool,
+    sensitive: GlobSet,
+}
+
+impl PathPolicy {
+    pub fn new<P: AsRef<Path>>(root_path: P, include_hidden: bool) -> Self {
+        PathPolicy {
+            root_path: root_path.as_ref().to_path_buf(),
+            include_hidden,
+            sensitive: GlobSet::empty(),
+        }
+    }
+
+    /// Root-relative globs of files whose contents are never read.
+    pub fn sensitive(mut self, sensitive: GlobSet) -> Self {
+        self.sensitive = sensitive;
+        self
+    }
+
+    /// The same policy, seeing hidden files or not.
+    pub fn including_hidden(&self, include_hidden: bool) -> Self {
+        PathPolicy {
+            include_hidden,
+            ..self.clone()
+        }
+    }
+
+    /// A walker over `dir` that only yields allowed paths.
+    pub fn walker(&self, dir: &Path) -> WalkBuilder {
+        let mut builder = WalkBuilder::new(dir);
+        builder
+            .git_ignore(true)
+            .git_exclude(true)
+            .git_global(true)
+            .require_git(false)
+            .hidden(!self.include_hidden)
+            .add_custom_ignore_filename(IGNORE_FILE)
+            .filter_entry(|entry| !is_excluded(entry.file_name()));
+        builder
+    }
+
+    /// Whether `path`, an absolute (canonical) path, may be read or listed.
+    /// Applies the same rules as [`PathPolicy::walker`] without walking.
+    pub fn allows(&self, path: &Path) -> bool {
+        let Ok(rel) = path.strip_prefix(&self.root_path) else {
+            return false;
+        };
+        for component in rel.components() {
+            let Component::Normal(name) = component else {
+                return false;
+            };
+            if is_excluded(name) || (!self.include_hidden && is_hidden(name)) {
+                return false;
+            }
+        }
+        !self.is_ignored(path)
+    }
+
+    /// Whether `path`, an absolute (canonical) path, is a sensitive file.
+    pub fn is_sensitive(&self, path: &Path) -> bool {
+        path.strip_prefix(&self.root_path)
+            .is_ok_and(|rel| self.sensitive.is_match(rel))
+    }
+
+    /// Whether the contents of `path`, an absolute (canonical) path, may be
+    /// read: it is allowed and not sensitive.
+    pub fn readable(&self, path: &Path) -> bool {
+        self.allows(path) && !self.is_sensitive(path)
+    }
+
+    /// Consults the sources [`PathPolicy::walker`] reads, in its order of
+    /// precedence: `.viktorignore`, `.ignore` and `.gitignore` files, each
+    /// kind from the innermost directory outwards (including the root's
+    /// parents), then the repository's `.git/info/exclude`, then the global
+    /// excludes. The first one with an opinion decides.
+    fn is_ignored(&self, path: &Path) -> bool {
+        let is_dir = path.is_dir();
+        for name in [IGNORE_FILE, ".ignore", ".gitignore"] {
+            for dir in path.ancestors().skip(1) {
+                let file = dir.join(name);
+                if !file.is_file() {
+                    continue;
+                }
+                let (matcher, _) = Gitignore::new(&file);
+                match matcher.matched_path_or_any_parents(path, is_dir) {
+                    Match::None => continue,
+                    decision => return decision.is_ignore(),
+                }
+            }
+        }
+
+        let repository = self
+            .root_path
+            .ancestors()
+            .find(|dir| dir.join(".git").is_dir());
+        if let Some(repository) = repository {
+            let mut exclude = GitignoreBuilder::new(repository);
+            exclude.add(repository.join(".git/info/exclude"));
+            if let Ok(matcher) = exclude.build() {
+                match matcher.matched_path_or_any_parents(path, is_dir) {
+                    Match::None => {}
+                    decision => return decision.is_ignore(),
+                }
+            }
+        }
+
+        let (global, _) = Gitignore::global();
+        global.matched_path_or_any_parents(path, is_dir).is_ignore()
+    }
+}
+
+fn is_excluded(name: &OsStr) -> bool {
+    ALWAYS_EXCLUDED.iter().any(|excluded| name == *excluded)
+}
+
+fn is_hidden(name: &OsStr) -> bool {
+    name.to_str().is_some_and(|name| name.starts_with('.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    /// Whether the walker lists `rel` and whether `allows` lets it be read.
+    fn seen(root: &Path, rel: &str) -> (bool, bool) {
+        let policy = PathPolicy::new(root, false);
+        let listed = policy
+            .walker(root)
+            .build()
+            .flatten()
+            .any(|e| e.path() == root.join(rel));
+        (listed, policy.allows(&root.join(rel)))
+    }
+
+    #[test]
+    fn direct_reads_follow_the_same_sources_as_listings() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        fs::create_dir_all(root.join(".git/info")).unwrap();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join(".git/info/exclude"), "excluded.txt\n").unwrap();
+        fs::write(root.join(".ignore"), "*.log\n").unwrap();
+        fs::write(root.join(".gitignore"), "build/\n!keep.log\n").unwrap();
+        fs::write(root.join(IGNORE_FILE), "src/generated.rs\n").unwrap();
+        for file in [
+            "excluded.txt",
+            "debug.log",
+            "keep.log",
+            "src/generated.rs",
+            "src/main.rs",
+        ] {
+            fs::write(root.join(file), "").unwrap();
+        }
+
+        for rel in ["excluded.txt", "debug.log", "keep.log", "src/generated.rs"] {
+            assert_eq!(seen(&root, rel), (false, false), "{rel}");
+        }
+        assert_eq!(seen(&root, "src/main.rs"), (true, true));
+    }
+
+    #[test]
+    fn hidden_and_always_excluded_paths_are_refused() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        fs::create_dir_all(root.join("target")).unwrap();
+        fs::write(root.join("target/out"), "").unwrap();
+        fs::write(root.join(".env"), "").unwrap();
+
+        assert_eq!(seen(&root, "target/out"), (false, false));
+        assert_eq!(seen(&root, ".env"), (false, false));
+        assert!(PathPolicy::new(&root, true).allows(&root.join(".env")));
+        assert!(!PathPolicy::new(&root, true).allows(Path::new("/etc/passwd")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn sensitive_files_are_refused_through_symlinks() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        fs::write(root.join("server.key"), "").unwrap();
+        std::os::unix::fs::symlink(root.join("server.key"), root.join("notes.txt")).unwrap();
+        let mut sensitive = globset::GlobSetBuilder::new();
+        sensitive.add(globset::Glob::new("**/*.key").unwrap());
+        let policy = PathPolicy::new(&root, false).sensitive(sensitive.build().unwrap());
+
+        let notes = root.join("notes.txt").canonicalize().unwrap();
+        assert!(policy.allows(&notes));
+        assert!(!policy.readable(&notes));
+        assert!(!policy.including_hidden(true).readable(&notes));
+    }
+}
@@ -1,84 +1,129 @@
 use fuzzy_matcher::skim::SkimMatcherV2;
 use fuzzy_matcher::FuzzyMatcher;
-use ignore::WalkBuilder;
+use globset::GlobSet;
 use std::{
     collections::HashMap,
     path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
 };
 use tokio::{fs, task};
 
+use super::{
+    glob::{self, GlobMatch, GlobQuery},
+    policy::PathPolicy,
+    tree::{self, TreeOptions},
+};
+
 pub struct Crawler {
     root_path: PathBuf,
-    matcher: SkimMatcherV2,
+    policy: PathPolicy,
+    allow_hidden: bool,
 }
 
 impl Crawler {
     /// Creates a new `Crawler`. Always succeeds:
     /// if `canonicalize` fails, we just keep the raw path.
-    pub async fn new<P: AsRef<Path>>(root_path: P) -> Self {
+    pub fn new<P: AsRef<Path>>(root_path: P) -> Self {
         let raw = root_path.as_ref().to_path_buf();
         let canonical = raw.clone().canonicalize().unwrap_or(raw.clone());
         Crawler {
+            policy: PathPolicy::new(&canonical, false),
             root_path: canonical,
-            matcher: SkimMatcherV2::default(),
+            allow_hidden: false,
         }
     }
 
-    /// Fuzzy searches under `root_path`. On any walker error,
-    /// it just skips entries, so this always returns a Vec.
-    pub fn fuzzy_search_paths(&self, queries: &[&str]) -> Vec<(i64, PathBuf)> {
-        let mut best: HashMap<PathBuf, i64> = HashMap::new();
-        let walker = WalkBuilder::new(&self.root_path)
-            .git_ignore(true)
-            .git_exclude(true)
-            .git_global(true)
-            .hidden(true)
-            .build();
-
-        for res in walker {
-            let entry = match res {
-                Ok(e) => e,
-                Err(_) => continue,
-            };
-            if !entry
-                .file_type()
-                .map_or(false, |ft| ft.is_file() || ft.is_dir())
-            {
-                continue;
-            }
-            let path = entry.path();
-            let rel = match path
-                .strip_prefix(&self.root_path)
-                .ok()
-                .and_then(|p| p.to_str())
-            {
-                Some(s) => s,
-                None => continue,
-            };
-
-            let mut best_score = 0;
-            for &q in queries {
-                if let Some(score) = self.matcher.fuzzy_match(rel, q) {
-                    best_score = best_score.max(score);
-                }
-            }
-            if best_score > 0 {
-                best.entry(path.to_path_buf())
-                    .and_modify(|e| *e = (*e).max(best_score))
-                    .or_insert(best_score);
-            }
+    /// Lets tool calls ask for hidden files with `include_hidden`.
+    pub fn allow_hidden(mut self, allow: bool) -> Self {
+        self.allow_hidden = allow;
+        self
+    }
+
+    /// Files matching `sensitive` (root-relative globs) may be listed but
+    /// are never read.
+    pub fn sensitive(mut self, sensitive: GlobSet) -> Self {
+        self.policy = self.policy.sensitive(sensitive);
+        self
+    }
+
+    pub fn hidden_allowed(&self) -> bool {
+        self.allow_hidden
+    }
+
+    /// A crawler over the same root that also sees hidden files, if
+    /// `include_hidden` is requested and allowed.
+    pub fn including_hidden(&self, include_hidden: bool) -> Crawler {
+        Crawler {
+            root_path: self.root_path.clone(),
+            policy: self
+                .policy
+                .including_hidden(include_hidden && self.allow_hidden),
+            allow_hidden: self.allow_hidden,
+        }
+    }
+
+    /// Whether the path policy lets the crawler see `rel`, which must also
+    /// resolve to somewhere under the root.
+    pub fn allows<P: AsRef<Path>>(&self, rel: P) -> bool {
+        let full = self.root_path.join(rel.as_ref());
+        match full.canonicalize() {
+            Ok(canon) => self.policy.allows(&canon),
+            Err(_) => false,
         }
+    }
 
-        let mut out: Vec<_> = best.into_iter().map(|(p, s)| (s, p)).collect();
-        out.sort_by(|a, b| b.0.cmp(&a.0));
+    /// Whether `rel` resolves to a sensitive file, following symlinks.
+    pub fn is_sensitive<P: AsRef<Path>>(&self, rel: P) -> bool {
+        self.root_path
+            .join(rel.as_ref())
+            .canonicalize()
+            .is_ok_and(|canon| self.policy.is_sensitive(&canon))
+    }
+
+    /// Fuzzy searches under `root_path` on a blocking thread. On any walker
+    /// error, it just skips entries, so this always returns a Vec. Dropping
+    /// the future stops the walk at the next entry.
+    pub async fn fuzzy_search_paths(&self, queries: &[&str]) -> Vec<(i64, PathBuf)> {
+        let root = self.root_path.clone();
+        let policy = self.policy.clone();
+        let queries: Vec<String> = queries.iter().map(|q| q.to_string()).collect();
+        let stop = StopOnDrop::default();
+        let stopped = stop.0.clone();
+        task::spawn_blocking(move || fuzzy_search(&policy, &root, &queries, &stopped))
+            .await
+            .unwrap_or_default()
+    }
+
+    /// Root-relative paths of every file under `root_path` allowed by the
+    /// path policy.
+    pub fn list_files(&self) -> Vec<PathBuf> {
+        let walker = self.policy.walker(&self.root_path).build();
+
+        let mut out: Vec<PathBuf> = walker
+            .flatten()
+            .filter(|e| e.file_type().is_some_and(|ft| ft.is_file()))
+            .filter_map(|e| {
+                e.path()
+                    .strip_prefix(&self.root_path)
+                    .ok()
+                    .map(Path::to_path_buf)
+            })
+            .collect();
+        out.sort();
         out
     }
 
-    /// Reads a file’s contents. Any failure => empty String.
+    /// Reads a file’s contents. Any failure, including a path the policy
+    /// excludes or a sensitive file => empty String. The check is made on
+    /// the canonical path, so symlinks cannot reach around it.
     pub async fn read_file_contents<P: AsRef<Path>>(&self, rel: P) -> String {
-        let full = self.root_path.join(rel.as_ref());
-        let canon = full.clone().canonicalize().unwrap_or(full.clone());
-        if !canon.starts_with(&self.root_path) || !full.is_file() {
+        let Ok(full) = self.root_path.join(rel.as_ref()).canonicalize() else {
+            return String::new();
+        };
+        if !self.policy.readable(&full) || !full.is_file() {
             return String::new();
         }
         fs::read_to_string(&full).await.unwrap_or_default()
@@ -92,23 +137,17 @@ impl Crawler {
     ) -> Vec<PathBuf> {
         // 1) Resolve and check boundaries
         let full = self.root_path.join(rel.as_ref());
-        let canon = full.clone().canonicalize().unwrap_or(full.clone());
-        if !canon.starts_with(&self.root_path) || !full.is_dir() {
+        if !self.allows(rel.as_ref()) || !full.is_dir() {
             return Vec::new();
         }
 
         // 2) Spawn a blocking task for the WalkBuilder
         let root = full.clone();
+        let policy = self.policy.clone();
         let max_depth = depth + 1; // 0 => only direct children
         task::spawn_blocking(move || {
             let mut out = Vec::new();
-            let walker = WalkBuilder::new(&root)
-                .git_ignore(true)
-                .git_exclude(true)
-                .git_global(true)
-                .hidden(true)
-                .max_depth(Some(max_depth))
-                .build();
+            let walker = policy.walker(&root).max_depth(Some(max_depth)).build();
 
             for res in walker.flatten() {
                 let entry = res;
@@ -118,15 +157,97 @@ impl Crawler {
                     out.push(path.to_path_buf());
                 }
             }
-            dbg!(&out);
             out
         })
         .await
         .unwrap_or_default()
     }
 
+    /// Renders `rel` as an indented tree with file metadata. `None` if it
+    /// is not a directory the policy allows.
+    pub async fn tree<P: AsRef<Path>>(&self, rel: P, options: TreeOptions) -> Option<String> {
+        let full = self.root_path.join(rel.as_ref());
+        if !self.allows(rel.as_ref()) || !full.is_dir() {
+            return None;
+        }
+        let dir = full.canonicalize().ok()?;
+        let root = self.root_path.clone();
+        let policy = self.policy.clone();
+        task::spawn_blocking(move || tree::render(&policy, &root, &dir, &options))
+            .await
+            .ok()
+    }
+
+    /// Files matching `query`, with metadata, and how many matched before
+    /// the limit.
+    pub async fn glob(&self, query: GlobQuery) -> (Vec<GlobMatch>, usize) {
+        let root = self.root_path.clone();
+        let policy = self.policy.clone();
+        task::spawn_blocking(move || glob::find(&policy, &root, &query))
+            .await
+            .unwrap_or_default()
+    }
+
     /// Expose the (canonical) root path.
     pub fn root_path(&self) -> &std::path::Path {
         &self.root_path
     }
 }
+
+/// Raises its flag when dropped, so a blocking task can notice that the
+/// future waiting for it was cancelled.
+#[derive(Default)]
+struct StopOnDrop(Arc<AtomicBool>);
+
+impl Drop for StopOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+fn fuzzy_search(
+    policy: &PathPolicy,
+    root: &Path,
+    queries: &[String],
+    stopped: &AtomicBool,
+) -> Vec<(i64, PathBuf)> {
+    let matcher = SkimMatcherV2::default();
+    let mut best: HashMap<PathBuf, i64> = HashMap::new();
+
+    for res in policy.walker(root).build() {
+        if stopped.load(Ordering::Relaxed) {
+            return Vec::new();
+        }
+        let entry = match res {
+            Ok(e) => e,
+            Err(_) => continue,
+        };
+        if !entry
+            .file_type()
+            .map_or(false, |ft| ft.is_file() || ft.is_dir())
+        {
+            continue;
+        }
+        let path = entry.path();
+        let rel = match path.strip_prefix(root).ok().and_then(|p| p.to_str()) {
+            Some(s) => s,
+            None => continue,
+        };
+
+        let mut best_score = 0;
+        for q in queries {
+            if let Some(score) = matcher.fuzzy_match(rel, q) {
+                best_score = best_score.max(score);
+            }
+        }
+        if best_score > 0 {
+            best.entry(path.to_path_buf())
+                .and_modify(|e| *e = (*e).max(best_score))
+                .or_insert(best_score);
+        }
+    }
+
+    let mut out: Vec<_> = best.into_iter().map(|(p, s)| (s, p)).collect();
+    out.sort_by(|a, b| b.0.cmp(&a.0));
+    out
+}
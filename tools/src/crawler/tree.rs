@@ -0,0 +1,204 @@
+use std::{cmp::Reverse, fmt::Write, fs, path::Path, path::PathBuf, time::SystemTime};
+
+use super::{
+    metadata::{human_size, FileInfo, SortBy},
+    policy::PathPolicy,
+};
+
+#[derive(Debug, Clone)]
+pub struct TreeOptions {
+    /// How many levels below the first to expand (0 = direct children only).
+    pub depth: usize,
+    pub sort: SortBy,
+    /// Entries shown per directory; the rest are only counted.
+    pub max_entries: usize,
+}
+
+/// What sorting needs, from metadata alone; files are only read once
+/// they are known to be printed.
+struct Entry {
+    path: PathBuf,
+    name: String,
+    is_dir: bool,
+    /// 0 for directories.
+    size: u64,
+    modified: Option<SystemTime>,
+}
+
+/// Renders `dir` as an indented tree, one entry per line. Files carry
+/// their size, line count and language; directories beyond `depth` carry
+/// how many entries they hold.
+pub fn render(policy: &PathPolicy, root_path: &Path, dir: &Path, options: &TreeOptions) -> String {
+    let label = match dir.strip_prefix(root_path) {
+        Ok(rel) if !rel.as_os_str().is_empty() => rel.display().to_string(),
+        _ => ".".to_string(),
+    };
+    let mut out = format!("{}/\n", label);
+    render_dir(policy, dir, options, 0, &mut out);
+    out
+}
+
+fn render_dir(
+    policy: &PathPolicy,
+    dir: &Path,
+    options: &TreeOptions,
+    level: usize,
+    out: &mut String,
+) {
+    let mut entries = children(policy, dir);
+    sort(&mut entries, options.sort);
+    let indent = "  ".repeat(level + 1);
+
+    for entry in entries.iter().take(options.max_entries) {
+        match entry.is_dir {
+            false => {
+                let description = match FileInfo::read(&entry.path) {
+                    Some(info) => info.describe(),
+                    None => human_size(entry.size),
+                };
+                let _ = writeln!(out, "{}{}  {}", indent, entry.name, description);
+            }
+            true if level < options.depth => {
+                let _ = writeln!(out, "{}{}/", indent, entry.name);
+                render_dir(policy, &entry.path, options, level + 1, out);
+            }
+            true => {
+                let count = count_children(policy, &entry.path);
+                let noun = if count == 1 { "entry" } else { "entries" };
+                let _ = writeln!(out, "{}{}/  ({} {})", indent, entry.name, count, noun);
+            }
+        }
+    }
+    if entries.len() > options.max_entries {
+        let _ = writeln!(
+            out,
+            "{}… {} more entries",
+            indent,
+            entries.len() - options.max_entries
+        );
+    }
+}
+
+/// Direct children of `dir` the policy allows. Files that are not regular
+/// files once symlinks are followed are left out.
+fn children(policy: &PathPolicy, dir: &Path) -> Vec<Entry> {
+    policy
+        .walker(dir)
+        .max_depth(Some(1))
+        .build()
+        .flatten()
+        .filter(|e| e.depth() == 1)
+        .filter_map(|e| {
+            let is_dir = e.file_type()?.is_dir();
+            let meta = if is_dir {
+                e.metadata().ok()
+            } else {
+                Some(fs::metadata(e.path()).ok().filter(|m| m.is_file())?)
+            };
+            Some(Entry {
+                name: e.file_name().to_string_lossy().into_owned(),
+                path: e.into_path(),
+                is_dir,
+                size: meta.as_ref().filter(|_| !is_dir).map_or(0, |m| m.len()),
+                modified: meta.and_then(|m| m.modified().ok()),
+            })
+        })
+        .collect()
+}
+
+/// How many direct children of `dir` the policy allows, without statting
+/// or reading any of them.
+fn count_children(policy: &PathPolicy, dir: &Path) -> usize {
+    policy
+        .walker(dir)
+        .max_depth(Some(1))
+        .build()
+        .flatten()
+        .filter(|e| e.depth() == 1)
+        .count()
+}
+
+fn sort(entries: &mut [Entry], by: SortBy) {
+    match by {
+        SortBy::Name => entries.sort_by(|a, b| (!a.is_dir, &a.name).cmp(&(!b.is_dir, &b.name))),
+        SortBy::Size => {
+            entries.sort_by(|a, b| (!a.is_dir, b.size, &a.name).cmp(&(!b.is_dir, a.size, &b.name)))
+        }
+        SortBy::Modified => entries.sort_by_key(|e| Reverse(e.modified)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn tree(root: &Path, sort: SortBy, depth: usize, max_entries: usize) -> String {
+        let options = TreeOptions {
+            depth,
+            sort,
+            max_entries,
+        };
+        render(&PathPolicy::new(root, false), root, root, &options)
+    }
+
+    fn fixture() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("src/bin")).unwrap();
+        fs::create_dir_all(root.join("docs")).unwrap();
+        fs::write(root.join("small.txt"), "a\n").unwrap();
+        fs::write(root.join("big.txt"), "b\n".repeat(100)).unwrap();
+        fs::write(root.join("src/lib.rs"), "").unwrap();
+        fs::write(root.join("src/main.rs"), "fn main() {}\n").unwrap();
+        fs::write(root.join("src/bin/tool.rs"), "").unwrap();
+        fs::write(root.join("docs/guide.md"), "").unwrap();
+        dir
+    }
+
+    #[test]
+    fn directories_come_first_then_files_by_name_or_size() {
+        let dir = fixture();
+        let by_name = tree(dir.path(), SortBy::Name, 0, 10);
+        assert_eq!(
+            by_name,
+            "./\n  docs/  (1 entry)\n  src/  (3 entries)\n  \
+             big.txt  200 B, 100 lines\n  small.txt  2 B, 1 line\n"
+        );
+        let by_size = tree(dir.path(), SortBy::Size, 0, 10);
+        let names: Vec<_> = by_size
+            .lines()
+            .skip(1)
+            .map(|l| l.split_whitespace().next().unwrap())
+            .collect();
+        assert_eq!(names, ["docs/", "src/", "big.txt", "small.txt"]);
+    }
+
+    #[test]
+    fn entries_past_the_limit_are_only_counted() {
+        let dir = fixture();
+        let out = tree(dir.path(), SortBy::Name, 0, 3);
+        assert!(out.contains("  big.txt  200 B"));
+        assert!(!out.contains("small.txt"));
+        assert!(out.ends_with("  … 1 more entries\n"));
+    }
+
+    #[test]
+    fn expanded_directories_are_indented_and_collapsed_ones_counted() {
+        let dir = fixture();
+        let out = tree(dir.path(), SortBy::Name, 1, 10);
+        assert_eq!(
+            out,
+            "./
+  docs/
+    guide.md  0 B, 0 lines, Markdown
+  src/
+    bin/  (1 entry)
+    lib.rs  0 B, 0 lines, Rust
+    main.rs  13 B, 1 line, Rust
+  big.txt  200 B, 100 lines
+  small.txt  2 B, 1 line
+"
+        );
+    }
+}
@@ -1,8 +1,13 @@
 use ollama::types::{ToolCall, ToolDefinition};
 
+pub mod cargo;
 pub mod crawler;
+pub mod lsp;
+pub mod mcp;
+pub mod plugin;
+pub mod shell;
 
 pub trait Tool {
-    fn get_tool_defs() -> Vec<ToolDefinition>;
-    async fn handle_tool_call(call: ToolCall) -> String;
+    fn get_tool_defs(&self) -> Vec<ToolDefinition>;
+    async fn handle_tool_call(&self, call: ToolCall) -> String;
 }
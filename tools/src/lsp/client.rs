@@ -0,0 +1,302 @@
+use serde_json::{json, Value};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    process::Stdio,
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    process::{Child, ChildStdin, ChildStdout, Command},
+    sync::mpsc,
+    time::{self, Instant},
+};
+use url::Url;
+
+use super::error::LspError;
+
+/// JSON-RPC errors meaning "ask again later", e.g. while the server indexes.
+const RETRYABLE_CODES: &[i64] = &[-32801, -32802];
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// How long the server may stay silent after `initialized` before we stop
+/// waiting for it to report that it finished loading the workspace.
+const STATUS_GRACE: Duration = Duration::from_secs(2);
+
+/// A running language server spoken to over stdio.
+pub struct Server {
+    _child: Child,
+    stdin: ChildStdin,
+    incoming: mpsc::UnboundedReceiver<Value>,
+    next_id: i64,
+    opened: HashSet<PathBuf>,
+    /// Whether the server reported `experimental/serverStatus`; then it is
+    /// only ready once it says it is quiescent.
+    reports_status: bool,
+    quiescent: bool,
+}
+
+impl Server {
+    /// Spawns `command`, runs the initialize handshake and waits (up to
+    /// `timeout`) for the workspace to load.
+    pub async fn start(
+        root_path: &Path,
+        command: &str,
+        args: &[String],
+        timeout: Duration,
+    ) -> Result<Self, LspError> {
+        let mut child = Command::new(command)
+            .args(args)
+            .current_dir(root_path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()?;
+        let stdin = child.stdin.take().ok_or(LspError::Exited)?;
+        let stdout = child.stdout.take().ok_or(LspError::Exited)?;
+        let (sender, incoming) = mpsc::unbounded_channel();
+        tokio::spawn(read_messages(stdout, sender));
+
+        let mut server = Server {
+            _child: child,
+            stdin,
+            incoming,
+            next_id: 1,
+            opened: HashSet::new(),
+            reports_status: false,
+            quiescent: false,
+        };
+        let root_uri = uri(root_path);
+        let name = root_path
+            .file_name()
+            .map_or("root".into(), |n| n.to_string_lossy());
+        server
+            .request(
+                "initialize",
+                json!({
+                    "processId": std::process::id(),
+                    "rootUri": root_uri,
+                    "workspaceFolders": [{"uri": root_uri, "name": name}],
+                    "capabilities": {
+                        "textDocument": {
+                            "definition": {"linkSupport": true},
+                            "references": {},
+                            "hover": {"contentFormat": ["markdown", "plaintext"]},
+                        },
+                        "workspace": {"symbol": {}, "workspaceFolders": true},
+                        "window": {"workDoneProgress": true},
+                        "experimental": {"serverStatusNotification": true},
+                    },
+                }),
+                timeout,
+            )
+            .await?;
+        server.notify("initialized", json!({})).await?;
+        server.wait_until_ready(timeout).await;
+        Ok(server)
+    }
+
+    /// Opens `path` in the server once, so requests about it are answered
+    /// from the file as it is on disk now.
+    pub async fn open(&mut self, path: &Path) -> Result<(), LspError> {
+        if self.opened.contains(path) {
+            return Ok(());
+        }
+        let text = tokio::fs::read_to_string(path).await?;
+        self.notify(
+            "textDocument/didOpen",
+            json!({
+                "textDocument": {
+                    "uri": uri(path),
+                    "languageId": language_id(path),
+                    "version": 1,
+                    "text": text,
+                }
+            }),
+        )
+        .await?;
+        self.opened.insert(path.to_path_buf());
+        Ok(())
+    }
+
+    /// Sends a request and waits for its result, retrying while the server
+    /// says its state is still changing.
+    pub async fn request(
+        &mut self,
+        method: &str,
+        params: Value,
+        timeout: Duration,
+    ) -> Result<Value, LspError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            match self.request_once(method, params.clone(), deadline).await {
+                Err(LspError::Server { code, .. })
+                    if RETRYABLE_CODES.contains(&code)
+                        && Instant::now() + RETRY_DELAY < deadline =>
+                {
+                    time::sleep(RETRY_DELAY).await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn request_once(
+        &mut self,
+        method: &str,
+        params: Value,
+        deadline: Instant,
+    ) -> Result<Value, LspError> {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.send(json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params}))
+            .await?;
+
+        loop {
+            let message = match time::timeout_at(deadline, self.incoming.recv()).await {
+                Ok(Some(message)) => message,
+                Ok(None) => return Err(LspError::Exited),
+                Err(_) => return Err(LspError::Timeout(method.to_string())),
+            };
+            if message.get("method").is_none() && message.get("id") == Some(&json!(id)) {
+                if let Some(error) = message.get("error") {
+                    return Err(LspError::Server {
+                        code: error.get("code").and_then(Value::as_i64).unwrap_or(0),
+                        message: error
+                            .get("message")
+                            .and_then(Value::as_str)
+                            .unwrap_or_default()
+                            .to_string(),
+                    });
+                }
+                return Ok(message.get("result").cloned().unwrap_or(Value::Null));
+            }
+            self.handle(message).await?;
+        }
+    }
+
+    async fn notify(&mut self, method: &str, params: Value) -> Result<(), LspError> {
+        self.send(json!({"jsonrpc": "2.0", "method": method, "params": params}))
+            .await
+    }
+
+    /// Deals with a message that is not the awaited response: server
+    /// requests get an empty answer, status notifications are tracked.
+    async fn handle(&mut self, message: Value) -> Result<(), LspError> {
+        let method = message.get("method").and_then(Value::as_str);
+        if method == Some("experimental/serverStatus") {
+            self.reports_status = true;
+            self.quiescent = message
+                .pointer("/params/quiescent")
+                .and_then(Value::as_bool)
+                .unwrap_or(false);
+        }
+        if let (Some(method), Some(id)) = (method, message.get("id")) {
+            // `workspace/configuration` expects one entry per requested item.
+            let result = match method {
+                "workspace/configuration" => {
+                    let items = message
+                        .pointer("/params/items")
+                        .and_then(Value::as_array)
+                        .map_or(0, Vec::len);
+                    Value::Array(vec![Value::Null; items])
+                }
+                _ => Value::Null,
+            };
+            self.send(json!({"jsonrpc": "2.0", "id": id, "result": result}))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Waits for a server that reports its status to become quiescent.
+    /// Servers that stay silent are assumed ready after a short grace.
+    async fn wait_until_ready(&mut self, timeout: Duration) {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if self.reports_status && self.quiescent {
+                return;
+            }
+            let wait = if self.reports_status {
+                deadline
+            } else {
+                deadline.min(Instant::now() + STATUS_GRACE)
+            };
+            match time::timeout_at(wait, self.incoming.recv()).await {
+                Ok(Some(message)) => {
+                    if self.handle(message).await.is_err() {
+                        return;
+                    }
+                }
+                Ok(None) | Err(_) => return,
+            }
+        }
+    }
+
+    async fn send(&mut self, message: Value) -> Result<(), LspError> {
+        let body = message.to_string();
+        let frame = format!("Content-Length: {}\r\n\r\n{}", body.len(), body);
+        self.stdin
+            .write_all(frame.as_bytes())
+            .await
+            .map_err(|_| LspError::Exited)?;
+        self.stdin.flush().await.map_err(|_| LspError::Exited)
+    }
+}
+
+/// Reads framed messages until the server closes stdout.
+async fn read_messages(stdout: ChildStdout, sender: mpsc::UnboundedSender<Value>) {
+    let mut reader = BufReader::new(stdout);
+    loop {
+        let mut length = None;
+        loop {
+            let mut header = String::new();
+            match reader.read_line(&mut header).await {
+                Ok(0) | Err(_) => return,
+                Ok(_) => {}
+            }
+            let header = header.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if let Some(value) = header.strip_prefix("Content-Length:") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+        let Some(length) = length else {
+            continue;
+        };
+        let mut body = vec![0; length];
+        if reader.read_exact(&mut body).await.is_err() {
+            return;
+        }
+        if let Ok(message) = serde_json::from_slice(&body) {
+            if sender.send(message).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+pub fn uri(path: &Path) -> String {
+    Url::from_file_path(path).map_or_else(|_| path.display().to_string(), |u| u.to_string())
+}
+
+pub fn path_of(uri: &str) -> Option<PathBuf> {
+    Url::parse(uri).ok()?.to_file_path().ok()
+}
+
+fn language_id(path: &Path) -> &'static str {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("rs") => "rust",
+        Some("toml") => "toml",
+        Some("py") => "python",
+        Some("js") => "javascript",
+        Some("ts") => "typescript",
+        Some("go") => "go",
+        Some("c" | "h") => "c",
+        Some("cc" | "cpp" | "hpp") => "cpp",
+        _ => "plaintext",
+    }
+}
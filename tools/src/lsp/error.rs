@@ -0,0 +1,32 @@
+use thiserror::Error;
+
+/// Reasons a language server request fails.
+#[derive(Debug, Error)]
+pub enum LspError {
+    /// The configured server command is not on PATH.
+    #[error("Language server '{0}' is not installed.")]
+    NotInstalled(String),
+    /// The server quit during the initialize handshake.
+    #[error("Language server '{0}' exited while starting; check that it works from a terminal.")]
+    StartFailed(String),
+    /// The server process could not be started or written to.
+    #[error("Language server I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    /// The server did not answer in time.
+    #[error("Language server did not answer '{0}' in time.")]
+    Timeout(String),
+    /// The server answered with an error.
+    #[error("Language server error {code}: {message}")]
+    Server { code: i64, message: String },
+    /// The server exited; it is restarted on the next call.
+    #[error("Language server exited.")]
+    Exited,
+    /// A path argument does not name a file the crawler may see.
+    #[error(
+        "'{0}' is not a file under the repository root, or it is excluded by the ignore rules."
+    )]
+    NotAFile(String),
+    /// The line does not exist or does not contain the symbol.
+    #[error("{0}")]
+    Position(String),
+}
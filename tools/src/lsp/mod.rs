@@ -0,0 +1,535 @@
+//! `lsp.*`: go-to-definition, references, hover and workspace symbols from
+//! a language server (e.g. rust-analyzer) started lazily for the repository
+//! root and kept for the rest of the session.
+
+mod client;
+pub mod error;
+
+use crate::{crawler::PathPolicy, Tool};
+use globset::GlobSet;
+use ollama::types::{FunctionDefinition, ToolCall, ToolDefinition};
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    env, fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::sync::{Mutex, MutexGuard};
+
+use self::client::{path_of, uri, Server};
+pub use self::error::LspError;
+
+/// Default number of references and symbols returned.
+const DEFAULT_LIMIT: usize = 50;
+
+/// Which language server to run and how patiently.
+#[derive(Debug, Clone)]
+pub struct LspConfig {
+    pub command: String,
+    pub args: Vec<String>,
+    /// How long the server may take to start and load the workspace.
+    pub startup_timeout: Duration,
+    pub request_timeout: Duration,
+}
+
+/// Handle to the session's language server. Clones share the server, which
+/// is started on first use and restarted if it exits.
+#[derive(Clone)]
+pub struct Lsp {
+    root_path: PathBuf,
+    /// Files whose lines may be read; others are located without a snippet.
+    policy: PathPolicy,
+    config: LspConfig,
+    server: Arc<Mutex<Option<Server>>>,
+}
+
+/// A position in a file as the model names it, resolved for the server.
+struct Position {
+    file: PathBuf,
+    line: u32,
+    character: u32,
+}
+
+impl Lsp {
+    pub fn new<P: AsRef<Path>>(root_path: P, config: LspConfig) -> Self {
+        let raw = root_path.as_ref().to_path_buf();
+        let root_path = raw.canonicalize().unwrap_or(raw);
+        Lsp {
+            policy: PathPolicy::new(&root_path, false),
+            root_path,
+            config,
+            server: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Files matching `sensitive` (root-relative globs) are neither queried
+    /// nor quoted.
+    pub fn sensitive(mut self, sensitive: GlobSet) -> Self {
+        self.policy = self.policy.sensitive(sensitive);
+        self
+    }
+
+    /// Whether the server command can be found, i.e. the tools are usable.
+    pub fn is_available(&self) -> bool {
+        find_executable(&self.config.command).is_some()
+    }
+
+    async fn server(&self) -> Result<MutexGuard<'_, Option<Server>>, LspError> {
+        let mut guard = self.server.lock().await;
+        if guard.is_none() {
+            let command = find_executable(&self.config.command)
+                .ok_or_else(|| LspError::NotInstalled(self.config.command.clone()))?;
+            // stderr, so it cannot corrupt a protocol spoken on stdout.
+            eprintln!("🔌 Starting language server `{}`…", self.config.command);
+            let server = Server::start(
+                &self.root_path,
+                &command.to_string_lossy(),
+                &self.config.args,
+                self.config.startup_timeout,
+            )
+            .await
+            .map_err(|e| match e {
+                LspError::Exited => LspError::StartFailed(self.config.command.clone()),
+                other => other,
+            })?;
+            *guard = Some(server);
+        }
+        Ok(guard)
+    }
+
+    async fn call(&self, call: &ToolCall) -> Result<Value, LspError> {
+        let args = &call.function.arguments;
+        let name = call
+            .function
+            .name
+            .strip_prefix("lsp.")
+            .unwrap_or(&call.function.name);
+        let limit = args
+            .get("limit")
+            .and_then(Value::as_u64)
+            .map_or(DEFAULT_LIMIT, |n| (n as usize).max(1));
+        let timeout = self.config.request_timeout;
+
+        let mut guard = self.server().await?;
+        let server = guard.as_mut().ok_or(LspError::Exited)?;
+        let result = match name {
+            "definition" | "references" | "hover" => {
+                let position = self.position(args)?;
+                server.open(&position.file).await?;
+                let mut params = json!({
+                    "textDocument": {"uri": uri(&position.file)},
+                    "position": {"line": position.line, "character": position.character},
+                });
+                match name {
+                    "definition" => server
+                        .request("textDocument/definition", params, timeout)
+                        .await
+                        .map(
+                            |result| json!({"definitions": self.locations(&result, usize::MAX).0}),
+                        ),
+                    "references" => {
+                        let declaration = args
+                            .get("include_declaration")
+                            .and_then(Value::as_bool)
+                            .unwrap_or(true);
+                        params["context"] = json!({"includeDeclaration": declaration});
+                        server
+                            .request("textDocument/references", params, timeout)
+                            .await
+                            .map(|result| {
+                                let (references, total) = self.locations(&result, limit);
+                                json!({
+                                    "truncated": total > references.len(),
+                                    "total": total,
+                                    "references": references,
+                                })
+                            })
+                    }
+                    _ => server
+                        .request("textDocument/hover", params, timeout)
+                        .await
+                        .map(|result| json!({"hover": hover_text(&result)})),
+                }
+            }
+            "workspace_symbols" => {
+                let query = args.get("query").and_then(Value::as_str).unwrap_or("");
+                server
+                    .request("workspace/symbol", json!({"query": query}), timeout)
+                    .await
+                    .map(|result| self.symbols(&result, limit))
+            }
+            other => Ok(json!({"error": format!("Unknown tool: lsp.{}", other)})),
+        };
+        if matches!(result, Err(LspError::Exited)) {
+            *guard = None;
+        }
+        result
+    }
+
+    /// Resolves `path`, 1-based `line` and either `symbol` (its first
+    /// occurrence on the line) or 1-based `character`.
+    fn position(&self, args: &Value) -> Result<Position, LspError> {
+        let rel = args.get("path").and_then(Value::as_str).unwrap_or("");
+        let file = self.root_path.join(rel);
+        let file = match file.canonicalize() {
+            Ok(file) if file.is_file() && self.policy.readable(&file) => file,
+            _ => return Err(LspError::NotAFile(rel.to_string())),
+        };
+        let text = fs::read_to_string(&file)?;
+        let number = args.get("line").and_then(Value::as_u64).unwrap_or(1).max(1) as usize;
+        let Some(line) = text.lines().nth(number - 1) else {
+            return Err(LspError::Position(format!(
+                "{} has {} lines; there is no line {}.",
+                rel,
+                text.lines().count(),
+                number
+            )));
+        };
+
+        let column = match (
+            args.get("symbol").and_then(Value::as_str),
+            args.get("character").and_then(Value::as_u64),
+        ) {
+            (Some(symbol), _) => find_symbol(line, symbol).ok_or_else(|| {
+                LspError::Position(format!(
+                    "`{}` does not occur on line {} of {}: {}",
+                    symbol,
+                    number,
+                    rel,
+                    line.trim()
+                ))
+            })?,
+            (None, Some(character)) => line
+                .char_indices()
+                .nth(character.saturating_sub(1) as usize)
+                .map_or(line.len(), |(i, _)| i),
+            (None, None) => line.len() - line.trim_start().len(),
+        };
+        Ok(Position {
+            file,
+            line: (number - 1) as u32,
+            character: line[..column].encode_utf16().count() as u32,
+        })
+    }
+
+    /// Normalizes `Location`, `Location[]` and `LocationLink[]` results into
+    /// root-relative locations with the source line, sorted by path and line.
+    fn locations(&self, result: &Value, limit: usize) -> (Vec<Value>, usize) {
+        let items = match result {
+            Value::Array(items) => items.clone(),
+            Value::Null => Vec::new(),
+            single => vec![single.clone()],
+        };
+        let mut found = items
+            .iter()
+            .filter_map(|item| {
+                let uri = item
+                    .get("uri")
+                    .or_else(|| item.get("targetUri"))?
+                    .as_str()?;
+                let range = item
+                    .get("range")
+                    .or_else(|| item.get("targetSelectionRange"))?;
+                Some((path_of(uri)?, range_start(range)))
+            })
+            .collect::<Vec<_>>();
+        // Locations in the repository first, then e.g. the standard library.
+        found.sort_by_key(|(path, start)| {
+            (!path.starts_with(&self.root_path), path.clone(), *start)
+        });
+        found.dedup();
+
+        let total = found.len();
+        let mut files = HashMap::new();
+        let locations = found
+            .into_iter()
+            .take(limit)
+            .map(|(path, (line, character))| self.location(&mut files, &path, line, character))
+            .collect();
+        (locations, total)
+    }
+
+    fn symbols(&self, result: &Value, limit: usize) -> Value {
+        let items = result.as_array().cloned().unwrap_or_default();
+        let mut files = HashMap::new();
+        let symbols = items
+            .iter()
+            .take(limit)
+            .filter_map(|item| {
+                let location = item.get("location")?;
+                let path = path_of(location.get("uri")?.as_str()?)?;
+                let (line, character) = location.get("range").map_or((0, 0), range_start);
+                let mut symbol = self.location(&mut files, &path, line, character);
+                symbol["name"] = item.get("name")?.clone();
+                symbol["kind"] = json!(symbol_kind(
+                    item.get("kind").and_then(Value::as_u64).unwrap_or(0)
+                ));
+                if let Some(container) = item.get("containerName") {
+                    symbol["container"] = container.clone();
+                }
+                Some(symbol)
+            })
+            .collect::<Vec<_>>();
+        json!({
+            "truncated": items.len() > symbols.len(),
+            "total": items.len(),
+            "symbols": symbols,
+        })
+    }
+
+    /// A location as shown to the model: root-relative path (absolute for
+    /// files elsewhere, e.g. the standard library), 1-based line and the
+    /// line's text if the path policy lets the file be read.
+    fn location(
+        &self,
+        files: &mut HashMap<PathBuf, Option<String>>,
+        path: &Path,
+        line: u64,
+        character: u64,
+    ) -> Value {
+        let text = files.entry(path.to_path_buf()).or_insert_with(|| {
+            let readable = path
+                .canonicalize()
+                .is_ok_and(|canonical| self.policy.readable(&canonical));
+            readable.then(|| fs::read_to_string(path).ok()).flatten()
+        });
+        let snippet = text
+            .as_deref()
+            .and_then(|t| t.lines().nth(line as usize))
+            .map(str::trim);
+        let shown = path
+            .strip_prefix(&self.root_path)
+            .unwrap_or(path)
+            .to_string_lossy();
+        json!({
+            "path": shown,
+            "line": line + 1,
+            "character": character + 1,
+            "snippet": snippet,
+        })
+    }
+}
+
+impl Tool for Lsp {
+    fn get_tool_defs(&self) -> Vec<ToolDefinition> {
+        let position = |extra: Value| {
+            let mut properties = json!({
+                "path": {
+                    "type": "string",
+                    "description": "File path, relative to the repository root"
+                },
+                "line": {
+                    "type": "integer",
+                    "description": "Line number, counting from 1",
+                    "minimum": 1
+                },
+                "symbol": {
+                    "type": "string",
+                    "description": "Identifier on that line to ask about (its first occurrence)"
+                },
+                "character": {
+                    "type": "integer",
+                    "description": "Column counting from 1, if no symbol is given",
+                    "minimum": 1
+                }
+            });
+            if let (Some(properties), Value::Object(extra)) = (properties.as_object_mut(), extra) {
+                properties.extend(extra);
+            }
+            json!({
+                "type": "object",
+                "properties": properties,
+                "required": ["path", "line"]
+            })
+        };
+        let def = |name: &str, description: &str, parameters: Value| ToolDefinition {
+            type_: "function".into(),
+            function: FunctionDefinition {
+                name: name.into(),
+                description: description.into(),
+                parameters,
+            },
+        };
+        let limit = json!({
+            "type": "integer",
+            "description": format!("Maximum number of results (default: {})", DEFAULT_LIMIT),
+            "minimum": 1
+        });
+
+        vec![
+            def(
+                "lsp.definition",
+                "Finds where the symbol at a position is defined, using the language server.",
+                position(json!({})),
+            ),
+            def(
+                "lsp.references",
+                "Finds every reference to the symbol at a position, using the language server. \
+More reliable than text search for renamed imports, methods and trait implementations.",
+                position(json!({
+                    "include_declaration": {
+                        "type": "boolean",
+                        "description": "Also list the declaration itself (default: true)"
+                    },
+                    "limit": limit,
+                })),
+            ),
+            def(
+                "lsp.hover",
+                "Shows the type and documentation of the symbol at a position.",
+                position(json!({})),
+            ),
+            def(
+                "lsp.workspace_symbols",
+                "Searches the workspace for functions, types, modules and other symbols by name.",
+                json!({
+                    "type": "object",
+                    "properties": {
+                        "query": {
+                            "type": "string",
+                            "description": "Symbol name or part of it"
+                        },
+                        "limit": limit,
+                    },
+                    "required": ["query"]
+                }),
+            ),
+        ]
+    }
+
+    async fn handle_tool_call(&self, call: ToolCall) -> String {
+        match self.call(&call).await {
+            Ok(result) => result.to_string(),
+            Err(e) => json!({"error": e.to_string()}).to_string(),
+        }
+    }
+}
+
+/// Byte offset of the first whole-word occurrence of `symbol` in `line`.
+fn find_symbol(line: &str, symbol: &str) -> Option<usize> {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    line.match_indices(symbol).map(|(i, _)| i).find(|&i| {
+        let before = line[..i].chars().next_back();
+        let after = line[i + symbol.len()..].chars().next();
+        !before.is_some_and(is_ident) && !after.is_some_and(is_ident)
+    })
+}
+
+fn range_start(range: &Value) -> (u64, u64) {
+    let field = |name: &str| {
+        range
+            .pointer(&format!("/start/{}", name))
+            .and_then(Value::as_u64)
+            .unwrap_or(0)
+    };
+    (field("line"), field("character"))
+}
+
+/// Plain text of a hover result, whatever shape the server used.
+fn hover_text(result: &Value) -> Option<String> {
+    let contents = result.get("contents")?;
+    let text = |value: &Value| match value {
+        Value::String(s) => Some(s.clone()),
+        other => other.get("value")?.as_str().map(String::from),
+    };
+    match contents {
+        Value::Array(parts) => Some(
+            parts
+                .iter()
+                .filter_map(text)
+                .collect::<Vec<_>>()
+                .join("\n\n"),
+        ),
+        single => text(single),
+    }
+}
+
+fn symbol_kind(kind: u64) -> &'static str {
+    const KINDS: &[&str] = &[
+        "file",
+        "module",
+        "namespace",
+        "package",
+        "class",
+        "method",
+        "property",
+        "field",
+        "constructor",
+        "enum",
+        "interface",
+        "function",
+        "variable",
+        "constant",
+        "string",
+        "number",
+        "boolean",
+        "array",
+        "object",
+        "key",
+        "null",
+        "enum_member",
+        "struct",
+        "event",
+        "operator",
+        "type_parameter",
+    ];
+    kind.checked_sub(1)
+        .and_then(|i| KINDS.get(i as usize))
+        .copied()
+        .unwrap_or("symbol")
+}
+
+/// `command` itself if it is a path, otherwise its location on PATH.
+fn find_executable(command: &str) -> Option<PathBuf> {
+    let path = Path::new(command);
+    if path.components().count() > 1 {
+        return path.is_file().then(|| path.to_path_buf());
+    }
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(command))
+        .find(|candidate| candidate.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_symbol_matches_whole_words_only() {
+        assert_eq!(find_symbol("let greeting = greet();", "greet"), Some(15));
+        assert_eq!(find_symbol("greeter.greet_all()", "greet"), None);
+        assert_eq!(find_symbol("self.greet", "greet"), Some(5));
+    }
+
+    #[test]
+    fn find_symbol_offsets_convert_to_utf16_columns() {
+        let line = "let s = \"😀é\"; greet();";
+        let offset = find_symbol(line, "greet").unwrap();
+        assert_eq!(offset, 18);
+        assert_eq!(line[..offset].encode_utf16().count(), 15);
+        // An identifier right after a non-ASCII letter is not a whole word.
+        assert_eq!(find_symbol("égreet()", "greet"), None);
+    }
+
+    #[test]
+    fn hover_text_reads_every_contents_shape() {
+        let markup = json!({"contents": {"kind": "markdown", "value": "fn greet()"}});
+        assert_eq!(hover_text(&markup).as_deref(), Some("fn greet()"));
+
+        let plain = json!({"contents": "fn greet()"});
+        assert_eq!(hover_text(&plain).as_deref(), Some("fn greet()"));
+
+        let marked = json!({"contents": [
+            {"language": "rust", "value": "fn greet()"},
+            "Says hello.",
+        ]});
+        assert_eq!(
+            hover_text(&marked).as_deref(),
+            Some("fn greet()\n\nSays hello.")
+        );
+
+        assert_eq!(hover_text(&Value::Null), None);
+    }
+}
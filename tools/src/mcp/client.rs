@@ -0,0 +1,162 @@
+use serde_json::{json, Value};
+use std::process::Stdio;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    process::{Child, ChildStdin, ChildStdout, Command},
+    sync::mpsc,
+    time::{self, Instant},
+};
+
+use super::{error::McpError, McpServerConfig};
+
+/// Protocol revision we speak; servers answer with the one they picked.
+pub const PROTOCOL_VERSION: &str = "2025-06-18";
+
+/// A running MCP server spoken to over stdio, one JSON-RPC message per line.
+pub struct Client {
+    name: String,
+    _child: Child,
+    stdin: ChildStdin,
+    incoming: mpsc::UnboundedReceiver<Value>,
+    next_id: i64,
+}
+
+impl Client {
+    /// Spawns the server and runs the initialize handshake.
+    pub async fn start(config: &McpServerConfig) -> Result<Self, McpError> {
+        let mut child = Command::new(&config.command)
+            .args(&config.args)
+            .envs(&config.env)
+            .current_dir(&config.dir)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| McpError::Spawn(config.name.clone(), e))?;
+        let exited = || McpError::Exited(config.name.clone());
+        let stdin = child.stdin.take().ok_or_else(exited)?;
+        let stdout = child.stdout.take().ok_or_else(exited)?;
+        let (sender, incoming) = mpsc::unbounded_channel();
+        tokio::spawn(read_messages(stdout, sender));
+
+        let mut client = Client {
+            name: config.name.clone(),
+            _child: child,
+            stdin,
+            incoming,
+            next_id: 1,
+        };
+        client
+            .request(
+                "initialize",
+                json!({
+                    "protocolVersion": PROTOCOL_VERSION,
+                    "capabilities": {},
+                    "clientInfo": {"name": "viktor", "version": env!("CARGO_PKG_VERSION")},
+                }),
+                config.timeout,
+            )
+            .await?;
+        client
+            .send(json!({"jsonrpc": "2.0", "method": "notifications/initialized"}))
+            .await?;
+        Ok(client)
+    }
+
+    /// Every tool the server offers, following pagination.
+    pub async fn list_tools(&mut self, timeout: time::Duration) -> Result<Vec<Value>, McpError> {
+        let mut tools = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = match &cursor {
+                Some(cursor) => json!({"cursor": cursor}),
+                None => json!({}),
+            };
+            let page = self.request("tools/list", params, timeout).await?;
+            tools.extend(
+                page.get("tools")
+                    .and_then(Value::as_array)
+                    .cloned()
+                    .unwrap_or_default(),
+            );
+            cursor = page
+                .get("nextCursor")
+                .and_then(Value::as_str)
+                .map(String::from);
+            if cursor.is_none() {
+                return Ok(tools);
+            }
+        }
+    }
+
+    pub async fn request(
+        &mut self,
+        method: &str,
+        params: Value,
+        timeout: time::Duration,
+    ) -> Result<Value, McpError> {
+        let deadline = Instant::now() + timeout;
+        let id = self.next_id;
+        self.next_id += 1;
+        self.send(json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params}))
+            .await?;
+
+        loop {
+            let message = match time::timeout_at(deadline, self.incoming.recv()).await {
+                Ok(Some(message)) => message,
+                Ok(None) => return Err(McpError::Exited(self.name.clone())),
+                Err(_) => return Err(McpError::Timeout(self.name.clone(), method.to_string())),
+            };
+            if message.get("method").is_none() && message.get("id") == Some(&json!(id)) {
+                if let Some(error) = message.get("error") {
+                    return Err(McpError::Server {
+                        server: self.name.clone(),
+                        code: error.get("code").and_then(Value::as_i64).unwrap_or(0),
+                        message: error
+                            .get("message")
+                            .and_then(Value::as_str)
+                            .unwrap_or_default()
+                            .to_string(),
+                    });
+                }
+                return Ok(message.get("result").cloned().unwrap_or(Value::Null));
+            }
+            // Requests from the server (sampling, roots, ...) are not
+            // supported; answer so it does not wait. Notifications are dropped.
+            if let (Some(_), Some(id)) = (message.get("method"), message.get("id")) {
+                self.send(json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": {"code": -32601, "message": "Method not supported by viktor"},
+                }))
+                .await?;
+            }
+        }
+    }
+
+    async fn send(&mut self, message: Value) -> Result<(), McpError> {
+        let mut line = message.to_string();
+        line.push('\n');
+        let exited = |_| McpError::Exited(self.name.clone());
+        self.stdin
+            .write_all(line.as_bytes())
+            .await
+            .map_err(exited)?;
+        self.stdin.flush().await.map_err(exited)
+    }
+}
+
+/// Reads one message per line until the server closes stdout. Lines that
+/// are not JSON (stray logging) are skipped.
+async fn read_messages(stdout: ChildStdout, sender: mpsc::UnboundedSender<Value>) {
+    let mut lines = BufReader::new(stdout).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let Ok(message) = serde_json::from_str::<Value>(&line) else {
+            continue;
+        };
+        if sender.send(message).is_err() {
+            return;
+        }
+    }
+}
@@ -0,0 +1,25 @@
+use thiserror::Error;
+
+/// Reasons an MCP server could not be reached or a call failed.
+#[derive(Debug, Error)]
+pub enum McpError {
+    /// The server process could not be started.
+    #[error("Unable to start MCP server '{0}': {1}")]
+    Spawn(String, std::io::Error),
+    /// The server did not answer in time.
+    #[error("MCP server '{0}' did not answer '{1}' in time.")]
+    Timeout(String, String),
+    /// The server answered with a JSON-RPC error.
+    #[error("MCP server '{server}' error {code}: {message}")]
+    Server {
+        server: String,
+        code: i64,
+        message: String,
+    },
+    /// The server closed its stdout; it is restarted on the next call.
+    #[error("MCP server '{0}' exited.")]
+    Exited(String),
+    /// The tool name does not belong to any connected server.
+    #[error("Unknown MCP tool '{0}'.")]
+    UnknownTool(String),
+}
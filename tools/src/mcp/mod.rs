@@ -0,0 +1,210 @@
+//! `mcp.<server>.<tool>`: tools offered by external Model Context Protocol
+//! servers, started over stdio when the session begins. Each server's tool
+//! list is fetched once; calls are forwarded as `tools/call`.
+//!
+//! [`serve`] is the other direction: it offers any [`Tool`] to MCP hosts.
+
+mod client;
+pub mod error;
+mod server;
+
+use crate::Tool;
+use ollama::types::{FunctionDefinition, ToolCall, ToolDefinition};
+use serde_json::{json, Value};
+use std::{collections::HashMap, path::PathBuf, sync::Arc, time::Duration};
+use tokio::sync::Mutex;
+
+use self::client::Client;
+pub use self::error::McpError;
+pub use self::server::serve;
+
+/// How to start one MCP server.
+#[derive(Debug, Clone)]
+pub struct McpServerConfig {
+    /// Key in the configuration; becomes the middle part of tool names.
+    pub name: String,
+    pub command: String,
+    pub args: Vec<String>,
+    pub env: HashMap<String, String>,
+    /// Working directory of the server process.
+    pub dir: PathBuf,
+    /// Limit for the handshake and for each call.
+    pub timeout: Duration,
+}
+
+/// A server whose tools were listed, and its process if it is running.
+struct Server {
+    config: McpServerConfig,
+    tools: Vec<Value>,
+    client: Mutex<Option<Client>>,
+}
+
+/// The session's MCP servers. Clones share the connections; a server that
+/// exits is restarted on the next call.
+#[derive(Clone, Default)]
+pub struct Mcp {
+    servers: Arc<Vec<Server>>,
+}
+
+impl Mcp {
+    /// Starts every server and lists its tools. Servers that fail are left
+    /// out and their errors returned next to the handle.
+    pub async fn connect(configs: Vec<McpServerConfig>) -> (Self, Vec<McpError>) {
+        let mut servers = Vec::new();
+        let mut errors = Vec::new();
+        for config in configs {
+            let started = async {
+                let mut client = Client::start(&config).await?;
+                let tools = client.list_tools(config.timeout).await?;
+                Ok::<_, McpError>((client, tools))
+            };
+            match started.await {
+                Ok((client, tools)) => servers.push(Server {
+                    config,
+                    tools,
+                    client: Mutex::new(Some(client)),
+                }),
+                Err(e) => errors.push(e),
+            }
+        }
+        (
+            Mcp {
+                servers: Arc::new(servers),
+            },
+            errors,
+        )
+    }
+
+    /// Names of the connected servers with how many tools each offers.
+    pub fn servers(&self) -> Vec<(&str, usize)> {
+        self.servers
+            .iter()
+            .map(|s| (s.config.name.as_str(), s.tools.len()))
+            .collect()
+    }
+
+    /// The server and tool `mcp.<server>.<tool>` names. Server names may
+    /// contain dots, so the longest one that matches wins: with servers
+    /// `git` and `git.hub`, `mcp.git.hub.search` goes to `git.hub`.
+    fn route<'a>(&self, full_name: &'a str) -> Option<(&Server, &'a str)> {
+        let rest = full_name.strip_prefix("mcp.")?;
+        self.servers
+            .iter()
+            .filter_map(|s| {
+                rest.strip_prefix(s.config.name.as_str())
+                    .and_then(|r| r.strip_prefix('.'))
+                    .filter(|tool| !tool.is_empty())
+                    .map(|tool| (s, tool))
+            })
+            .max_by_key(|(s, _)| s.config.name.len())
+    }
+
+    async fn call(&self, call: &ToolCall) -> Result<Value, McpError> {
+        let full_name = &call.function.name;
+        let (server, tool) = self
+            .route(full_name)
+            .ok_or_else(|| McpError::UnknownTool(full_name.clone()))?;
+
+        let mut guard = server.client.lock().await;
+        if guard.is_none() {
+            *guard = Some(Client::start(&server.config).await?);
+        }
+        let client = guard
+            .as_mut()
+            .ok_or_else(|| McpError::Exited(server.config.name.clone()))?;
+        let arguments = match &call.function.arguments {
+            Value::Null => json!({}),
+            arguments => arguments.clone(),
+        };
+        let result = client
+            .request(
+                "tools/call",
+                json!({"name": tool, "arguments": arguments}),
+                server.config.timeout,
+            )
+            .await;
+        if let Err(McpError::Exited(_)) = result {
+            *guard = None;
+        }
+        result
+    }
+}
+
+impl Tool for Mcp {
+    fn get_tool_defs(&self) -> Vec<ToolDefinition> {
+        self.servers
+            .iter()
+            .flat_map(|server| {
+                server.tools.iter().filter_map(|tool| {
+                    let name = tool.get("name")?.as_str()?;
+                    let description = tool
+                        .get("description")
+                        .and_then(Value::as_str)
+                        .unwrap_or_default();
+                    let parameters = tool
+                        .get("inputSchema")
+                        .cloned()
+                        .unwrap_or_else(|| json!({"type": "object", "properties": {}}));
+                    Some(ToolDefinition {
+                        type_: "function".to_string(),
+                        function: FunctionDefinition {
+                            name: format!("mcp.{}.{}", server.config.name, name),
+                            description: format!("[{}] {}", server.config.name, description),
+                            parameters,
+                        },
+                    })
+                })
+            })
+            .collect()
+    }
+
+    async fn handle_tool_call(&self, call: ToolCall) -> String {
+        match self.call(&call).await {
+            Ok(result) => output_of(&result),
+            Err(e) => json!({"error": e.to_string()}).to_string(),
+        }
+    }
+}
+
+/// Turns a `tools/call` result into the text the model sees: text content
+/// joined by newlines, structured content when there is no text, and an
+/// `error` object when the server flags the call as failed.
+fn output_of(result: &Value) -> String {
+    let parts: Vec<String> = result
+        .get("content")
+        .and_then(Value::as_array)
+        .map(|content| {
+            content
+                .iter()
+                .map(|item| match item.get("type").and_then(Value::as_str) {
+                    Some("text") => item
+                        .get("text")
+                        .and_then(Value::as_str)
+                        .unwrap_or_default()
+                        .to_string(),
+                    Some("resource") => item
+                        .pointer("/resource/text")
+                        .and_then(Value::as_str)
+                        .map(String::from)
+                        .unwrap_or_else(|| "[resource]".to_string()),
+                    Some(other) => format!("[{} content omitted]", other),
+                    None => item.to_string(),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    let text = if parts.is_empty() {
+        result
+            .get("structuredContent")
+            .map(Value::to_string)
+            .unwrap_or_default()
+    } else {
+        parts.join("\n")
+    };
+
+    if result.get("isError").and_then(Value::as_bool) == Some(true) {
+        json!({"error": text}).to_string()
+    } else {
+        text
+    }
+}
@@ -0,0 +1,98 @@
+use ollama::types::{FunctionRef, ToolCall};
+use serde_json::{json, Value};
+use tokio::io::{self, AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+use super::client::PROTOCOL_VERSION;
+use crate::Tool;
+
+/// Protocol revisions a client may ask for; anything else gets ours.
+const SUPPORTED_VERSIONS: &[&str] = &["2024-11-05", "2025-03-26", PROTOCOL_VERSION];
+
+/// Serves `tool`'s definitions as an MCP server on stdin/stdout, one
+/// JSON-RPC message per line, until stdin closes. Requests are answered in
+/// order. Nothing else may write to stdout meanwhile.
+pub async fn serve<T: Tool>(tool: &T) -> io::Result<()> {
+    let mut lines = BufReader::new(io::stdin()).lines();
+    let mut stdout = io::stdout();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let reply = match serde_json::from_str::<Value>(&line) {
+            Ok(message) => answer(tool, message).await,
+            Err(e) => Some(error(Value::Null, -32700, &format!("Parse error: {}", e))),
+        };
+        if let Some(reply) = reply {
+            let mut line = reply.to_string();
+            line.push('\n');
+            stdout.write_all(line.as_bytes()).await?;
+            stdout.flush().await?;
+        }
+    }
+    Ok(())
+}
+
+/// The response to one message; notifications get none.
+async fn answer<T: Tool>(tool: &T, message: Value) -> Option<Value> {
+    let id = message.get("id").cloned()?;
+    let params = message.get("params").cloned().unwrap_or(Value::Null);
+    let result = match message.get("method").and_then(Value::as_str) {
+        Some("initialize") => {
+            let requested = params.get("protocolVersion").and_then(Value::as_str);
+            let version = requested
+                .filter(|v| SUPPORTED_VERSIONS.contains(v))
+                .unwrap_or(PROTOCOL_VERSION);
+            json!({
+                "protocolVersion": version,
+                "capabilities": {"tools": {"listChanged": false}},
+                "serverInfo": {"name": "viktor", "version": env!("CARGO_PKG_VERSION")},
+            })
+        }
+        Some("ping") => json!({}),
+        Some("tools/list") => {
+            let tools: Vec<Value> = tool
+                .get_tool_defs()
+                .into_iter()
+                .map(|def| {
+                    json!({
+                        "name": def.function.name,
+                        "description": def.function.description,
+                        "inputSchema": def.function.parameters,
+                    })
+                })
+                .collect();
+            json!({"tools": tools})
+        }
+        Some("tools/call") => {
+            let Some(name) = params.get("name").and_then(Value::as_str) else {
+                return Some(error(id, -32602, "Missing tool name"));
+            };
+            if !tool.get_tool_defs().iter().any(|d| d.function.name == name) {
+                return Some(error(id, -32602, &format!("Unknown tool: {}", name)));
+            }
+            let arguments = params.get("arguments").cloned().unwrap_or(json!({}));
+            let output = tool
+                .handle_tool_call(ToolCall {
+                    function: FunctionRef {
+                        name: name.to_string(),
+                        arguments,
+                    },
+                })
+                .await;
+            // Tools report failures as a JSON object with an `error` key.
+            let failed =
+                serde_json::from_str::<Value>(&output).is_ok_and(|v| v.get("error").is_some());
+            json!({
+                "content": [{"type": "text", "text": output}],
+                "isError": failed,
+            })
+        }
+        Some(method) => return Some(error(id, -32601, &format!("Method not found: {}", method))),
+        None => return Some(error(id, -32600, "Invalid request")),
+    };
+    Some(json!({"jsonrpc": "2.0", "id": id, "result": result}))
+}
+
+fn error(id: Value, code: i64, message: &str) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}})
+}
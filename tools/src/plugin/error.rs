@@ -0,0 +1,22 @@
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// Reasons a plugin declaration is rejected or a plugin fails to run.
+#[derive(Debug, Error)]
+pub enum PluginError {
+    /// The plugin directory or a declaration could not be read.
+    #[error("Unable to read '{0}': {1}")]
+    Read(PathBuf, std::io::Error),
+    /// A declaration is not valid TOML or misses a field.
+    #[error("Unable to parse '{0}': {1}")]
+    Parse(PathBuf, toml::de::Error),
+    /// Names become part of `plugin.<name>` and must stay simple.
+    #[error("'{0}': plugin name '{1}' may only contain letters, digits, '_' and '-'.")]
+    InvalidName(PathBuf, String),
+    /// Two declarations use the same name.
+    #[error("Plugin '{0}' is declared more than once.")]
+    Duplicate(String),
+    /// The command could not be started.
+    #[error("Unable to start plugin '{0}': {1}")]
+    Spawn(String, std::io::Error),
+}
@@ -0,0 +1,209 @@
+//! `plugin.<name>`: project-specific tools declared in `.viktor/tools/*.toml`.
+//! Each runs a command in the repository root with the call's arguments as
+//! JSON on stdin; what it prints on stdout is the tool output.
+
+pub mod error;
+
+use crate::{
+    shell::{Capture, KEPT_ENV},
+    Tool,
+};
+use ollama::types::{FunctionDefinition, ToolCall, ToolDefinition};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{io::AsyncWriteExt, process::Command, time};
+
+pub use self::error::PluginError;
+
+/// Bytes kept per output stream; the middle of longer output is dropped.
+const MAX_OUTPUT: usize = 64 * 1024;
+
+/// One declaration file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PluginSpec {
+    /// Offered to the model as `plugin.<name>`.
+    pub name: String,
+    pub description: String,
+    /// JSON schema of the arguments.
+    #[serde(default = "empty_schema")]
+    pub parameters: Value,
+    /// Program to run; relative paths are taken from the repository root.
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default = "default_timeout")]
+    pub timeout_secs: u64,
+    /// Environment variables passed through besides those `shell.run` keeps.
+    #[serde(default)]
+    pub pass_env: Vec<String>,
+}
+
+fn empty_schema() -> Value {
+    json!({"type": "object", "properties": {}})
+}
+
+fn default_timeout() -> u64 {
+    30
+}
+
+/// The plugins of a repository. Clones share the declarations.
+#[derive(Clone, Default)]
+pub struct Plugins {
+    root_path: PathBuf,
+    specs: Arc<Vec<PluginSpec>>,
+}
+
+impl Plugins {
+    /// Reads every `*.toml` in `dir`, in file name order. A missing
+    /// directory means no plugins.
+    pub fn load<P: AsRef<Path>>(root_path: P, dir: &Path) -> Result<Self, PluginError> {
+        let mut files = match fs::read_dir(dir) {
+            Ok(entries) => entries
+                .filter_map(Result::ok)
+                .map(|e| e.path())
+                .filter(|p| p.extension().is_some_and(|e| e == "toml"))
+                .collect::<Vec<_>>(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(PluginError::Read(dir.to_path_buf(), e)),
+        };
+        files.sort();
+
+        let mut specs: Vec<PluginSpec> = Vec::new();
+        for file in files {
+            let content =
+                fs::read_to_string(&file).map_err(|e| PluginError::Read(file.clone(), e))?;
+            let spec: PluginSpec =
+                toml::from_str(&content).map_err(|e| PluginError::Parse(file.clone(), e))?;
+            let valid = !spec.name.is_empty()
+                && spec
+                    .name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+            if !valid {
+                return Err(PluginError::InvalidName(file, spec.name));
+            }
+            if specs.iter().any(|s| s.name == spec.name) {
+                return Err(PluginError::Duplicate(spec.name));
+            }
+            specs.push(spec);
+        }
+
+        Ok(Plugins {
+            root_path: root_path.as_ref().to_path_buf(),
+            specs: Arc::new(specs),
+        })
+    }
+
+    pub fn specs(&self) -> &[PluginSpec] {
+        &self.specs
+    }
+
+    /// Runs `spec` with `arguments` on stdin, killing it once its timeout
+    /// expires. Exit status 0 yields stdout; anything else an error object.
+    async fn run(&self, spec: &PluginSpec, arguments: &Value) -> Result<String, PluginError> {
+        let program = if spec.command.contains('/') {
+            self.root_path.join(&spec.command)
+        } else {
+            PathBuf::from(&spec.command)
+        };
+        let mut command = Command::new(program);
+        command
+            .args(&spec.args)
+            .current_dir(&self.root_path)
+            .env_clear()
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        let kept = KEPT_ENV
+            .iter()
+            .map(|name| name.to_string())
+            .chain(spec.pass_env.iter().cloned());
+        for name in kept {
+            if let Some(value) = env::var_os(&name) {
+                command.env(name, value);
+            }
+        }
+
+        let mut child = command
+            .spawn()
+            .map_err(|e| PluginError::Spawn(spec.name.clone(), e))?;
+        let stdout = Capture::spawn(child.stdout.take(), MAX_OUTPUT / 2);
+        let stderr = Capture::spawn(child.stderr.take(), MAX_OUTPUT / 2);
+        if let Some(mut stdin) = child.stdin.take() {
+            let input = arguments.to_string();
+            // A plugin that ignores its input may exit before reading it.
+            tokio::spawn(async move {
+                let _ = stdin.write_all(input.as_bytes()).await;
+            });
+        }
+
+        let timeout = Duration::from_secs(spec.timeout_secs);
+        let status = match time::timeout(timeout, child.wait()).await {
+            Ok(status) => status.ok(),
+            Err(_) => {
+                let _ = child.kill().await;
+                let error = format!(
+                    "Plugin '{}' timed out after {} seconds.",
+                    spec.name, spec.timeout_secs
+                );
+                return Ok(json!({"error": error, "stderr": stderr.finish().await}).to_string());
+            }
+        };
+        let (stdout, stderr) = (stdout.finish().await, stderr.finish().await);
+
+        match status.and_then(|s| s.code()) {
+            Some(0) => Ok(stdout.trim_end().to_string()),
+            code => {
+                let error = match code {
+                    Some(code) => format!("Plugin '{}' exited with status {}.", spec.name, code),
+                    None => format!("Plugin '{}' was killed by a signal.", spec.name),
+                };
+                Ok(json!({"error": error, "stdout": stdout, "stderr": stderr}).to_string())
+            }
+        }
+    }
+}
+
+impl Tool for Plugins {
+    fn get_tool_defs(&self) -> Vec<ToolDefinition> {
+        self.specs
+            .iter()
+            .map(|spec| ToolDefinition {
+                type_: "function".into(),
+                function: FunctionDefinition {
+                    name: format!("plugin.{}", spec.name),
+                    description: spec.description.clone(),
+                    parameters: spec.parameters.clone(),
+                },
+            })
+            .collect()
+    }
+
+    /// Runs the plugin a call names. Permission checks are up to the caller.
+    async fn handle_tool_call(&self, call: ToolCall) -> String {
+        let name = &call.function.name;
+        let spec = name
+            .strip_prefix("plugin.")
+            .and_then(|n| self.specs.iter().find(|s| s.name == n));
+        let Some(spec) = spec else {
+            return json!({"error": format!("Unknown plugin tool: {}", name)}).to_string();
+        };
+        let arguments = match &call.function.arguments {
+            Value::Null => json!({}),
+            arguments => arguments.clone(),
+        };
+        match self.run(spec, &arguments).await {
+            Ok(output) => output,
+            Err(e) => json!({"error": e.to_string()}).to_string(),
+        }
+    }
+}
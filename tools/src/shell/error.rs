@@ -0,0 +1,27 @@
+use thiserror::Error;
+
+/// Reasons a `shell.run` command is refused or fails to start.
+#[derive(Debug, Error)]
+pub enum ShellError {
+    /// The command line was empty.
+    #[error("Empty command.")]
+    EmptyCommand,
+    /// A quote was opened but never closed.
+    #[error("Unbalanced quote in command.")]
+    UnbalancedQuote,
+    /// Commands run without a shell, so pipes, redirects and expansions are refused.
+    #[error("Shell operator '{0}' is not supported; run a single command without pipes, redirects or variables.")]
+    ShellOperator(char),
+    /// The command does not start with any allowlisted prefix.
+    #[error("Command '{0}' is not allowlisted.")]
+    NotAllowed(String),
+    /// An option that writes files or reads outside the repository.
+    #[error("Option '{0}' is not allowed: it can write files or read outside the repository.")]
+    ForbiddenOption(String),
+    /// An argument naming a path outside the repository root.
+    #[error("Argument '{0}' points outside the repository.")]
+    OutsideRoot(String),
+    /// The process could not be started.
+    #[error("Unable to start command: {0}")]
+    Spawn(#[from] std::io::Error),
+}
@@ -0,0 +1,87 @@
+//! `shell.run`: allowlisted commands executed in the repository root
+//! without a shell, with a timeout, bounded output and a scrubbed environment.
+
+pub mod error;
+mod tool;
+
+use crate::Tool;
+use ollama::types::{FunctionDefinition, ToolCall, ToolDefinition};
+use serde_json::{json, Value};
+
+pub use self::error::ShellError;
+pub(crate) use self::tool::{Capture, KEPT_ENV};
+pub use self::tool::{Shell, ShellConfig, ShellOutput};
+
+impl Shell {
+    /// The command line of a `shell.run` call, if it has one.
+    pub fn command_of(call: &ToolCall) -> Option<&str> {
+        call.function
+            .arguments
+            .get("command")
+            .and_then(Value::as_str)
+    }
+
+    /// The arguments of a `shell.run` call as they would be passed to the
+    /// program, if its command line splits.
+    pub fn argv_of(call: &ToolCall) -> Option<Vec<String>> {
+        Self::command_of(call).and_then(|command| tool::split(command).ok())
+    }
+}
+
+impl Tool for Shell {
+    /// The definition lists the configured allowlist so the model knows
+    /// what it may run.
+    fn get_tool_defs(&self) -> Vec<ToolDefinition> {
+        let allowed = if self.config().allow.is_empty() {
+            "none".to_string()
+        } else {
+            self.config().allow.join(", ")
+        };
+        vec![ToolDefinition {
+            type_: "function".into(),
+            function: FunctionDefinition {
+                name: "shell.run".into(),
+                description: format!(
+                    "Runs a command in the repository root and returns its exit code, stdout and stderr. \
+Only commands starting with one of these prefixes are allowed: {}. \
+There is no shell: pipes, redirects, globs and variables are not supported. \
+Options that write files (such as --output) and paths outside the repository are refused.",
+                    allowed
+                ),
+                parameters: json!({
+                    "type": "object",
+                    "properties": {
+                        "command": {
+                            "type": "string",
+                            "description": "Command line to run, e.g. \"cargo check\""
+                        }
+                    },
+                    "required": ["command"]
+                }),
+            },
+        }]
+    }
+
+    /// Runs the command of a `shell.run` call. Permission checks are up to the caller.
+    async fn handle_tool_call(&self, call: ToolCall) -> String {
+        let Some(command) = Self::command_of(&call) else {
+            return json!({"error": "Missing 'command' argument"}).to_string();
+        };
+        let argv = match self.parse(command) {
+            Ok(argv) => argv,
+            Err(e) => return json!({"error": e.to_string()}).to_string(),
+        };
+
+        match self.run(&argv).await {
+            Ok(output) => json!({
+                "command": argv.join(" "),
+                "exit_code": output.exit_code,
+                "timed_out": output.timed_out,
+                "stdout": output.stdout,
+                "stderr": output.stderr,
+            })
+            .to_string(),
+            Err(e) => json!({"error": e.to_string()}).to_string(),
+        }
+    }
+}
@@ -0,0 +1,412 @@
+use std::{
+    collections::VecDeque,
+    env,
+    path::{Component, Path, PathBuf},
+    process::Stdio,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt},
+    process::Command,
+    task::JoinHandle,
+    time,
+};
+
+use super::error::ShellError;
+
+/// Environment variables passed through to commands; everything else is scrubbed.
+pub(crate) const KEPT_ENV: &[&str] = &[
+    "PATH",
+    "HOME",
+    "USER",
+    "LANG",
+    "LC_ALL",
+    "TERM",
+    "TMPDIR",
+    "CARGO_HOME",
+    "RUSTUP_HOME",
+    "RUSTUP_TOOLCHAIN",
+];
+
+/// Characters that would need a shell to mean anything.
+const SHELL_OPERATORS: &[char] = &['|', '&', ';', '<', '>', '`', '$', '(', ')', '*', '?'];
+
+/// Long options refused whatever the command: `git diff --output` writes a
+/// file, `--no-index` diffs arbitrary files, `--ext-diff` runs a configured
+/// program and `cargo --config` can swap in other executables. Unambiguous
+/// abbreviations, which git accepts, are refused too.
+const FORBIDDEN_OPTIONS: &[&str] = &["--output", "--no-index", "--ext-diff", "--config"];
+
+/// How long to wait for output pipes to drain once the process is gone.
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// What commands may run and how.
+#[derive(Debug, Clone)]
+pub struct ShellConfig {
+    /// Command prefixes that may run at all, e.g. `cargo test`.
+    pub allow: Vec<String>,
+    pub timeout: Duration,
+    /// Bytes kept per output stream; the middle of longer output is dropped.
+    pub max_output: usize,
+    /// Extra environment variables passed through besides the defaults.
+    pub pass_env: Vec<String>,
+}
+
+/// Runs allowlisted commands in a repository root, without a shell.
+pub struct Shell {
+    root_path: PathBuf,
+    config: ShellConfig,
+}
+
+/// Result of a finished or timed-out command.
+#[derive(Debug)]
+pub struct ShellOutput {
+    /// `None` when the process was killed by a signal or the timeout.
+    pub exit_code: Option<i32>,
+    pub timed_out: bool,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl Shell {
+    pub fn new<P: AsRef<Path>>(root_path: P, config: ShellConfig) -> Self {
+        Shell {
+            root_path: root_path.as_ref().to_path_buf(),
+            config,
+        }
+    }
+
+    pub fn config(&self) -> &ShellConfig {
+        &self.config
+    }
+
+    /// Splits `command` into arguments and checks it against the allowlist
+    /// and the argument rules.
+    pub fn parse(&self, command: &str) -> Result<Vec<String>, ShellError> {
+        let argv = split(command)?;
+        if !matches_any(&self.config.allow, &argv) {
+            return Err(ShellError::NotAllowed(argv.join(" ")));
+        }
+        check_arguments(&argv[1..])?;
+        Ok(argv)
+    }
+
+    /// Runs `argv` in the root with a scrubbed environment, killing it once
+    /// the timeout expires.
+    pub async fn run(&self, argv: &[String]) -> Result<ShellOutput, ShellError> {
+        let (program, args) = argv.split_first().ok_or(ShellError::EmptyCommand)?;
+        let mut command = Command::new(program);
+        command
+            .args(args)
+            .current_dir(&self.root_path)
+            .env_clear()
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        let kept = KEPT_ENV
+            .iter()
+            .map(|name| name.to_string())
+            .chain(self.config.pass_env.iter().cloned());
+        for name in kept {
+            if let Some(value) = env::var_os(&name) {
+                command.env(name, value);
+            }
+        }
+
+        let mut child = command.spawn()?;
+        let half = self.config.max_output / 2;
+        let stdout = Capture::spawn(child.stdout.take(), half);
+        let stderr = Capture::spawn(child.stderr.take(), half);
+
+        let (status, timed_out) = match time::timeout(self.config.timeout, child.wait()).await {
+            Ok(status) => (status.ok(), false),
+            Err(_) => {
+                let _ = child.kill().await;
+                (None, true)
+            }
+        };
+
+        Ok(ShellOutput {
+            exit_code: status.and_then(|s| s.code()),
+            timed_out,
+            stdout: stdout.finish().await,
+            stderr: stderr.finish().await,
+        })
+    }
+}
+
+/// Splits a command line into arguments, honoring single and double quotes
+/// and backslash escapes. Unquoted shell operators are rejected.
+pub(super) fn split(command: &str) -> Result<Vec<String>, ShellError> {
+    let mut argv = Vec::new();
+    let mut current = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+    let mut chars = command.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"'), '\\') => current.extend(chars.next()),
+            (Some(_), c) => current.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, '\\') => {
+                current.extend(chars.next());
+                in_word = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    argv.push(std::mem::take(&mut current));
+                    in_word = false;
+                }
+            }
+            (None, c) if SHELL_OPERATORS.contains(&c) => return Err(ShellError::ShellOperator(c)),
+            (None, c) => {
+                current.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if quote.is_some() {
+        return Err(ShellError::UnbalancedQuote);
+    }
+    if in_word {
+        argv.push(current);
+    }
+    if argv.is_empty() {
+        return Err(ShellError::EmptyCommand);
+    }
+    Ok(argv)
+}
+
+/// True if `argv` starts with the words of one of `prefixes`.
+fn matches_any(prefixes: &[String], argv: &[String]) -> bool {
+    prefixes.iter().any(|prefix| {
+        let words = prefix.split_whitespace().collect::<Vec<_>>();
+        !words.is_empty()
+            && words.len() <= argv.len()
+            && words.iter().zip(argv).all(|(w, a)| w == a)
+    })
+}
+
+/// Refuses forbidden options and arguments that name a path outside the
+/// root: absolute paths, `~` and `..` components, also as an option value
+/// (`--manifest-path=/x`, `-O/x`).
+fn check_arguments(args: &[String]) -> Result<(), ShellError> {
+    let mut options_ended = false;
+    for arg in args {
+        if arg == "--" {
+            options_ended = true;
+            continue;
+        }
+        if !options_ended && arg.starts_with("--") {
+            let name = arg.split('=').next().unwrap_or(arg);
+            if name.len() > 2 && FORBIDDEN_OPTIONS.iter().any(|o| o.starts_with(name)) {
+                return Err(ShellError::ForbiddenOption(arg.clone()));
+            }
+        }
+
+        let mut values = vec![arg.as_str()];
+        if !options_ended {
+            if let Some((_, value)) = arg.split_once('=') {
+                values.push(value);
+            }
+            if arg.starts_with('-') && !arg.starts_with("--") && arg.len() > 2 {
+                values.push(&arg[2..]);
+            }
+        }
+        let escapes = |value: &&str| {
+            value.starts_with('/')
+                || value.starts_with('~')
+                || Path::new(value)
+                    .components()
+                    .any(|c| c == Component::ParentDir)
+        };
+        if values.iter().any(escapes) {
+            return Err(ShellError::OutsideRoot(arg.clone()));
+        }
+    }
+    Ok(())
+}
+
+/// Head and tail of an output stream, bounded in memory.
+#[derive(Default)]
+struct Buffer {
+    head: Vec<u8>,
+    tail: VecDeque<u8>,
+    total: usize,
+}
+
+pub(crate) struct Capture {
+    buffer: Arc<Mutex<Buffer>>,
+    task: Option<JoinHandle<()>>,
+}
+
+impl Capture {
+    /// Reads `stream` in the background, keeping `keep` bytes from each end.
+    pub(crate) fn spawn<R: AsyncRead + Unpin + Send + 'static>(
+        stream: Option<R>,
+        keep: usize,
+    ) -> Self {
+        let buffer = Arc::new(Mutex::new(Buffer::default()));
+        let task = stream.map(|mut stream| {
+            let buffer = buffer.clone();
+            tokio::spawn(async move {
+                let mut chunk = [0u8; 8192];
+                while let Ok(n) = stream.read(&mut chunk).await {
+                    if n == 0 {
+                        break;
+                    }
+                    let mut buffer = buffer.lock().unwrap();
+                    buffer.total += n;
+                    for &byte in &chunk[..n] {
+                        if buffer.head.len() < keep {
+                            buffer.head.push(byte);
+                        } else {
+                            buffer.tail.push_back(byte);
+                            if buffer.tail.len() > keep {
+                                buffer.tail.pop_front();
+                            }
+                        }
+                    }
+                }
+            })
+        });
+        Capture { buffer, task }
+    }
+
+    /// Waits briefly for the stream to drain and renders what was kept.
+    pub(crate) async fn finish(self) -> String {
+        if let Some(task) = self.task {
+            // Grandchildren may hold the pipe open after the process is gone.
+            let _ = time::timeout(DRAIN_TIMEOUT, task).await;
+        }
+        let buffer = self.buffer.lock().unwrap();
+        let head = String::from_utf8_lossy(&buffer.head);
+        let tail = buffer.tail.iter().copied().collect::<Vec<_>>();
+        let tail = String::from_utf8_lossy(&tail);
+        let omitted = buffer.total - buffer.head.len() - buffer.tail.len();
+        if omitted == 0 {
+            format!("{}{}", head, tail)
+        } else {
+            format!("{}\n[... {} bytes omitted ...]\n{}", head, omitted, tail)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn argv(words: &[&str]) -> Vec<String> {
+        words.iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn split_handles_quotes_and_escapes() {
+        assert_eq!(
+            split(r#"cargo test -p 'my crate' "a \"b\"" c\ d"#).unwrap(),
+            argv(&["cargo", "test", "-p", "my crate", r#"a "b""#, "c d"])
+        );
+        assert_eq!(split("  ls   src ").unwrap(), argv(&["ls", "src"]));
+        assert_eq!(split("git diff ''").unwrap(), argv(&["git", "diff", ""]));
+    }
+
+    #[test]
+    fn split_keeps_operators_inside_quotes() {
+        assert_eq!(
+            split("cargo test 'a|b'").unwrap(),
+            argv(&["cargo", "test", "a|b"])
+        );
+    }
+
+    #[test]
+    fn split_rejects_shell_syntax() {
+        for (command, operator) in [
+            ("ls | wc", '|'),
+            ("ls > out", '>'),
+            ("cargo test; rm x", ';'),
+            ("ls $HOME", '$'),
+            ("ls *.rs", '*'),
+            ("ls `pwd`", '`'),
+        ] {
+            assert!(
+                matches!(split(command), Err(ShellError::ShellOperator(c)) if c == operator),
+                "{command}"
+            );
+        }
+        assert!(matches!(split("ls 'src"), Err(ShellError::UnbalancedQuote)));
+        assert!(matches!(split("   "), Err(ShellError::EmptyCommand)));
+    }
+
+    #[test]
+    fn matches_any_compares_whole_words() {
+        let allow = argv(&["cargo test", "git diff", "ls"]);
+        assert!(matches_any(&allow, &argv(&["cargo", "test", "-p", "x"])));
+        assert!(matches_any(&allow, &argv(&["ls"])));
+        assert!(!matches_any(&allow, &argv(&["cargo"])));
+        assert!(!matches_any(&allow, &argv(&["cargo", "testing"])));
+        assert!(!matches_any(&allow, &argv(&["lsof"])));
+        assert!(!matches_any(&argv(&[" "]), &argv(&["ls"])));
+        assert!(!matches_any(&[], &argv(&["ls"])));
+    }
+
+    #[test]
+    fn check_arguments_refuses_writing_options() {
+        for args in [
+            &["diff", "--output=notes.txt"][..],
+            &["diff", "--output", "notes.txt"],
+            &["diff", "--outp=notes.txt"],
+            &["diff", "--no-index", "a", "b"],
+            &["diff", "--ext-diff"],
+            &["test", "--config", "build.rustc-wrapper='x'"],
+        ] {
+            assert!(
+                matches!(
+                    check_arguments(&argv(args)),
+                    Err(ShellError::ForbiddenOption(_))
+                ),
+                "{args:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn check_arguments_refuses_paths_outside_the_root() {
+        for args in [
+            &["/etc"][..],
+            &["../other"],
+            &["src/../../other"],
+            &["~"],
+            &["check", "--manifest-path=/x/Cargo.toml"],
+            &["diff", "-O/etc/passwd"],
+            &["diff", "--", "../x"],
+        ] {
+            assert!(
+                matches!(
+                    check_arguments(&argv(args)),
+                    Err(ShellError::OutsideRoot(_))
+                ),
+                "{args:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn check_arguments_accepts_ordinary_commands() {
+        for args in [
+            &["test", "-p", "tools", "--", "--nocapture"][..],
+            &["diff", "--stat", "HEAD~1"],
+            &["diff", "--no-color", "src/main.rs"],
+            &["-la", "src"],
+            &["check", "--manifest-path=tools/Cargo.toml"],
+        ] {
+            assert!(check_arguments(&argv(args)).is_ok(), "{args:?}");
+        }
+    }
+}
@@ -0,0 +1,193 @@
+//! `mcp.*` against a scripted MCP server: the handshake, paginated tool
+//! lists, routing between servers whose names share a prefix, requests
+//! from the server, failed calls, timeouts and restarts.
+
+use ollama::types::{FunctionRef, ToolCall};
+use serde_json::{json, Value};
+use std::{collections::HashMap, fs, path::Path, time::Duration};
+use tempfile::TempDir;
+use tools::{
+    mcp::{Mcp, McpError, McpServerConfig},
+    Tool,
+};
+
+/// Speaks one JSON message per line. Its name comes from argv; `echo`
+/// first asks the client for its roots, which the client must refuse.
+const FAKE_SERVER: &str = r#"
+import json, os, sys
+
+name = sys.argv[1]
+
+def send(message):
+    sys.stdout.write(json.dumps(message) + "\n")
+    sys.stdout.flush()
+
+def read():
+    line = sys.stdin.readline()
+    if not line:
+        sys.exit(0)
+    return json.loads(line)
+
+def text(value):
+    return {"content": [{"type": "text", "text": value}]}
+
+print("starting up, not JSON", flush=True)
+while True:
+    message = read()
+    method, params = message.get("method"), message.get("params") or {}
+    if "id" not in message:
+        continue
+    if method == "initialize":
+        result = {"protocolVersion": params["protocolVersion"], "capabilities": {"tools": {}},
+                  "serverInfo": {"name": name, "version": "1"}}
+    elif method == "tools/list" and "cursor" not in params:
+        result = {"tools": [{"name": "echo", "description": "Echo the arguments",
+                             "inputSchema": {"type": "object"}}], "nextCursor": "2"}
+    elif method == "tools/list":
+        result = {"tools": [{"name": "fail"}, {"name": "exit"}, {"name": "hang"}]}
+    elif method == "tools/call" and params["name"] == "echo":
+        send({"jsonrpc": "2.0", "id": "roots", "method": "roots/list"})
+        refused = read()["error"]["code"]
+        result = text("%s %d %s %d" % (name, os.getpid(), json.dumps(params["arguments"]), refused))
+    elif method == "tools/call" and params["name"] == "fail":
+        result = dict(text("boom"), isError=True)
+    elif method == "tools/call" and params["name"] == "exit":
+        sys.exit(0)
+    elif method == "tools/call" and params["name"] == "hang":
+        continue
+    else:
+        send({"jsonrpc": "2.0", "id": message["id"],
+              "error": {"code": -32602, "message": "Unknown tool"}})
+        continue
+    send({"jsonrpc": "2.0", "id": message["id"], "result": result})
+"#;
+
+fn config(dir: &Path, name: &str) -> McpServerConfig {
+    McpServerConfig {
+        name: name.to_string(),
+        command: "python3".to_string(),
+        args: vec![
+            dir.join("fake_server.py").to_string_lossy().into_owned(),
+            name.to_string(),
+        ],
+        env: HashMap::new(),
+        dir: dir.to_path_buf(),
+        timeout: Duration::from_secs(2),
+    }
+}
+
+/// Servers `git` and `git.hub`. `None` when python3 is not installed.
+async fn setup() -> Option<(TempDir, Mcp)> {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("fake_server.py"), FAKE_SERVER).unwrap();
+    let configs = vec![config(dir.path(), "git"), config(dir.path(), "git.hub")];
+    let (mcp, errors) = Mcp::connect(configs).await;
+    if let Some(McpError::Spawn(..)) = errors.first() {
+        eprintln!("python3 not found; skipping");
+        return None;
+    }
+    assert!(errors.is_empty(), "{errors:?}");
+    Some((dir, mcp))
+}
+
+async fn call(mcp: &Mcp, name: &str, arguments: Value) -> String {
+    mcp.handle_tool_call(ToolCall {
+        function: FunctionRef {
+            name: name.to_string(),
+            arguments,
+        },
+    })
+    .await
+}
+
+/// Server name, process id and arguments an `echo` call reports.
+async fn echo(mcp: &Mcp, name: &str, arguments: Value) -> (String, String, Value) {
+    let output = call(mcp, name, arguments).await;
+    let mut parts = output.splitn(3, ' ');
+    let server = parts.next().unwrap().to_string();
+    let pid = parts.next().unwrap().to_string();
+    let (arguments, refused) = parts.next().unwrap().rsplit_once(' ').unwrap();
+    assert_eq!(refused, "-32601", "{output}");
+    (server, pid, serde_json::from_str(arguments).unwrap())
+}
+
+#[tokio::test]
+async fn every_page_of_tools_is_offered() {
+    let Some((_dir, mcp)) = setup().await else {
+        return;
+    };
+    assert_eq!(mcp.servers(), [("git", 4), ("git.hub", 4)]);
+    let defs = mcp.get_tool_defs();
+    let names: Vec<_> = defs.iter().map(|d| d.function.name.as_str()).collect();
+    assert_eq!(
+        names,
+        [
+            "mcp.git.echo",
+            "mcp.git.fail",
+            "mcp.git.exit",
+            "mcp.git.hang",
+            "mcp.git.hub.echo",
+            "mcp.git.hub.fail",
+            "mcp.git.hub.exit",
+            "mcp.git.hub.hang",
+        ]
+    );
+    assert_eq!(defs[0].function.description, "[git] Echo the arguments");
+    assert_eq!(defs[1].function.parameters["type"], "object");
+}
+
+#[tokio::test]
+async fn calls_go_to_the_longest_matching_server() {
+    let Some((_dir, mcp)) = setup().await else {
+        return;
+    };
+    let (server, _, arguments) = echo(&mcp, "mcp.git.hub.echo", json!({"q": 1})).await;
+    assert_eq!((server.as_str(), arguments), ("git.hub", json!({"q": 1})));
+    let (server, _, arguments) = echo(&mcp, "mcp.git.echo", Value::Null).await;
+    assert_eq!((server.as_str(), arguments), ("git", json!({})));
+
+    // A bare `mcp.git.hub` names no `git.hub` tool, so it is `git`'s `hub`.
+    let unknown = call(&mcp, "mcp.git.hub", json!({})).await;
+    assert_eq!(
+        unknown,
+        json!({"error": "MCP server 'git' error -32602: Unknown tool"}).to_string()
+    );
+    let elsewhere = call(&mcp, "mcp.svn.echo", json!({})).await;
+    assert_eq!(
+        elsewhere,
+        json!({"error": "Unknown MCP tool 'mcp.svn.echo'."}).to_string()
+    );
+}
+
+#[tokio::test]
+async fn failed_calls_become_errors() {
+    let Some((_dir, mcp)) = setup().await else {
+        return;
+    };
+    let failed = call(&mcp, "mcp.git.fail", json!({})).await;
+    assert_eq!(failed, json!({"error": "boom"}).to_string());
+
+    let hung = call(&mcp, "mcp.git.hang", json!({})).await;
+    assert_eq!(
+        hung,
+        json!({"error": "MCP server 'git' did not answer 'tools/call' in time."}).to_string()
+    );
+    // The late answer never comes; the next call still gets its own.
+    let (server, _, _) = echo(&mcp, "mcp.git.echo", json!({})).await;
+    assert_eq!(server, "git");
+}
+
+#[tokio::test]
+async fn an_exited_server_is_restarted_on_the_next_call() {
+    let Some((_dir, mcp)) = setup().await else {
+        return;
+    };
+    let (_, before, _) = echo(&mcp, "mcp.git.echo", json!({})).await;
+    let exited = call(&mcp, "mcp.git.exit", json!({})).await;
+    assert_eq!(
+        exited,
+        json!({"error": "MCP server 'git' exited."}).to_string()
+    );
+    let (_, after, _) = echo(&mcp, "mcp.git.echo", json!({})).await;
+    assert_ne!(before, after);
+}
@@ -0,0 +1,145 @@
+//! `plugin.*` loading and runs, with shell scripts as plugins.
+
+use ollama::types::{FunctionRef, ToolCall};
+use serde_json::{json, Value};
+use std::{fs, path::Path};
+use tempfile::TempDir;
+use tools::{
+    plugin::{PluginError, Plugins},
+    Tool,
+};
+
+fn declare(dir: &Path, file: &str, content: &str) {
+    fs::create_dir_all(dir).unwrap();
+    fs::write(dir.join(file), content).unwrap();
+}
+
+/// A repository with the given declarations in `.viktor/tools`.
+fn load(declarations: &[(&str, &str)]) -> (TempDir, Result<Plugins, PluginError>) {
+    let root = TempDir::new().unwrap();
+    let dir = root.path().join(".viktor/tools");
+    for (file, content) in declarations {
+        declare(&dir, file, content);
+    }
+    let plugins = Plugins::load(root.path(), &dir);
+    (root, plugins)
+}
+
+async fn call(plugins: &Plugins, name: &str, arguments: Value) -> String {
+    plugins
+        .handle_tool_call(ToolCall {
+            function: FunctionRef {
+                name: name.to_string(),
+                arguments,
+            },
+        })
+        .await
+}
+
+const ECHO: &str = r#"
+name = "echo"
+description = "Prints its input and working directory"
+command = "sh"
+args = ["-c", "cat; echo; pwd"]
+"#;
+
+#[test]
+fn declarations_are_read_in_file_name_order() {
+    let (_root, plugins) = load(&[
+        ("b.toml", ECHO),
+        (
+            "a.toml",
+            "name = \"lint\"\ndescription = \"Lints\"\ncommand = \"scripts/lint.sh\"\n",
+        ),
+        ("notes.txt", "not a declaration"),
+    ]);
+    let plugins = plugins.unwrap();
+    let names: Vec<_> = plugins.specs().iter().map(|s| s.name.as_str()).collect();
+    assert_eq!(names, ["lint", "echo"]);
+    assert_eq!(plugins.specs()[0].timeout_secs, 30);
+
+    let defs = plugins.get_tool_defs();
+    assert_eq!(defs[1].function.name, "plugin.echo");
+    assert_eq!(
+        defs[0].function.parameters,
+        json!({"type": "object", "properties": {}})
+    );
+}
+
+#[test]
+fn a_missing_directory_means_no_plugins() {
+    let root = TempDir::new().unwrap();
+    let plugins = Plugins::load(root.path(), &root.path().join(".viktor/tools")).unwrap();
+    assert!(plugins.specs().is_empty());
+}
+
+#[test]
+fn bad_declarations_are_rejected() {
+    let (_root, plugins) = load(&[("a.toml", &ECHO.replace("\"echo\"", "\"e.cho\""))]);
+    assert!(matches!(plugins, Err(PluginError::InvalidName(_, name)) if name == "e.cho"));
+
+    let (_root, plugins) = load(&[("a.toml", ECHO), ("b.toml", ECHO)]);
+    assert!(matches!(plugins, Err(PluginError::Duplicate(name)) if name == "echo"));
+
+    let (_root, plugins) = load(&[("a.toml", &format!("{}\nshell = true\n", ECHO))]);
+    assert!(matches!(plugins, Err(PluginError::Parse(..))));
+}
+
+#[tokio::test]
+async fn plugins_run_in_the_root_with_arguments_on_stdin() {
+    let (root, plugins) = load(&[("echo.toml", ECHO)]);
+    let plugins = plugins.unwrap();
+    let output = call(&plugins, "plugin.echo", json!({"path": "src"})).await;
+    let root = root.path().canonicalize().unwrap();
+    assert_eq!(
+        output,
+        format!("{}\n{}", json!({"path": "src"}), root.display())
+    );
+    assert_eq!(
+        call(&plugins, "plugin.echo", Value::Null).await,
+        "{}\n".to_string() + &root.display().to_string()
+    );
+}
+
+#[tokio::test]
+async fn failures_and_timeouts_become_errors() {
+    let (_root, plugins) = load(&[
+        (
+            "fail.toml",
+            "name = \"fail\"\ndescription = \"\"\ncommand = \"sh\"\nargs = [\"-c\", \"echo out; echo err >&2; exit 3\"]\n",
+        ),
+        (
+            "slow.toml",
+            "name = \"slow\"\ndescription = \"\"\ncommand = \"sleep\"\nargs = [\"10\"]\ntimeout_secs = 1\n",
+        ),
+        (
+            "gone.toml",
+            "name = \"gone\"\ndescription = \"\"\ncommand = \"bin/missing\"\n",
+        ),
+    ]);
+    let plugins = plugins.unwrap();
+
+    let failed: Value =
+        serde_json::from_str(&call(&plugins, "plugin.fail", json!({})).await).unwrap();
+    assert_eq!(
+        failed,
+        json!({"error": "Plugin 'fail' exited with status 3.", "stdout": "out\n", "stderr": "err\n"})
+    );
+    let slow: Value =
+        serde_json::from_str(&call(&plugins, "plugin.slow", json!({})).await).unwrap();
+    assert_eq!(slow["error"], "Plugin 'slow' timed out after 1 seconds.");
+    let gone: Value =
+        serde_json::from_str(&call(&plugins, "plugin.gone", json!({})).await).unwrap();
+    assert!(
+        gone["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("Unable to start plugin 'gone'"),
+        "{gone}"
+    );
+    let unknown = call(&plugins, "plugin.other", json!({})).await;
+    assert_eq!(
+        unknown,
+        json!({"error": "Unknown plugin tool: plugin.other"}).to_string()
+    );
+}